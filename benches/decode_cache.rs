@@ -0,0 +1,29 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use chip8_rust::Chip8;
+
+// 1命令ごとのデコードの費用が目立つ狭いループ（LD V0, 0; ADD V0, 1; JP 0x202）
+const HOT_LOOP: [u8; 6] = [0x60, 0x00, 0x70, 0x01, 0x12, 0x02];
+
+// 1回の測定で実行する命令数
+const CYCLES: usize = 10_000;
+
+fn decode_cache(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hot_loop");
+    for (name, enabled) in [("uncached", false), ("cached", true)] {
+        let mut chip8 = Chip8::new();
+        chip8.set_decode_cache(enabled);
+        chip8.load(&HOT_LOOP);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for _ in 0..CYCLES {
+                    chip8.step().unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode_cache);
+criterion_main!(benches);
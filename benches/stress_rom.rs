@@ -0,0 +1,18 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use chip8_rust::{stress_rom, Chip8};
+
+// 1回の測定で実行する命令数
+const CYCLES: usize = 10_000;
+
+// 画面全体に8x15のスプライトを描画し続けるROMで，DRWの費用を測る
+fn stress_rom_drw(c: &mut Criterion) {
+    let mut chip8 = Chip8::new();
+    chip8.load(&stress_rom());
+    c.bench_function("stress_rom", |b| {
+        b.iter(|| chip8.run_cycles(CYCLES).unwrap())
+    });
+}
+
+criterion_group!(benches, stress_rom_drw);
+criterion_main!(benches);
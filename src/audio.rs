@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+// ブザーの音色の既定値
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+pub const DEFAULT_FREQUENCY: f32 = 440.0;
+pub const DEFAULT_DUTY: f32 = 0.5;
+
+// 出力する矩形波の振幅
+const AMPLITUDE: f32 = 0.25;
+
+// サウンドタイマが0でない間に鳴らす矩形波のブザー
+//
+// オーディオの出力先には依存せず，指定されたサンプリング周波数でサンプル列を生成する．
+#[derive(Debug, Clone, PartialEq)]
+pub struct Beeper {
+    sample_rate: u32, // サンプリング周波数
+    frequency: f32,   // 矩形波の周波数（Hz）
+    duty: f32,        // 1周期のうち高い値を出力する割合（0.0〜1.0）
+    phase: f32,       // 現在の位相（0.0〜1.0）
+}
+
+impl Beeper {
+    pub fn new(sample_rate: u32) -> Beeper {
+        Beeper {
+            sample_rate: sample_rate.max(1),
+            frequency: DEFAULT_FREQUENCY,
+            duty: DEFAULT_DUTY,
+            phase: 0.0,
+        }
+    }
+
+    // 音の高さとデューティ比を設定する（周波数はナイキスト周波数以下，デューティ比は0.0〜1.0に丸める）
+    pub fn set_tone(&mut self, hz: f32, duty: f32) {
+        let nyquist = self.sample_rate as f32 / 2.0;
+        self.frequency = hz.clamp(1.0, nyquist.max(1.0));
+        self.duty = duty.clamp(0.0, 1.0);
+    }
+
+    // サンプリング周波数を設定する（出力先の設定に合わせる）
+    // 位相は周期に対する割合で保持しているため，変更しても波形は途切れずに続く
+    pub fn set_sample_rate(&mut self, hz: u32) {
+        self.sample_rate = hz.max(1);
+        self.set_tone(self.frequency, self.duty);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn duty(&self) -> f32 {
+        self.duty
+    }
+
+    // 指定された時間分のサンプル列を生成する
+    pub fn generate(&mut self, duration: Duration, active: bool) -> Vec<f32> {
+        let len = (duration.as_secs_f64() * self.sample_rate as f64).round() as usize;
+        let mut buffer = vec![0.0; len];
+        self.fill(&mut buffer, active);
+        buffer
+    }
+
+    // サンプル列を生成する（鳴らさない場合は無音にし，次に鳴らすときは周期の先頭から始める）
+    pub fn fill(&mut self, buffer: &mut [f32], active: bool) {
+        if !active {
+            buffer.fill(0.0);
+            self.phase = 0.0;
+            return;
+        }
+
+        let step = self.frequency / self.sample_rate as f32;
+        for sample in buffer {
+            *sample = if self.phase < self.duty {
+                AMPLITUDE
+            } else {
+                -AMPLITUDE
+            };
+            self.phase = (self.phase + step).fract();
+        }
+    }
+}
+
+impl Default for Beeper {
+    fn default() -> Beeper {
+        Beeper::new(DEFAULT_SAMPLE_RATE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 低い値から高い値に変わるサンプルの位置
+    fn rising_edges(samples: &[f32]) -> Vec<usize> {
+        (1..samples.len())
+            .filter(|&i| samples[i - 1] < 0.0 && samples[i] > 0.0)
+            .collect()
+    }
+
+    // 指定した数のサンプルを生成する
+    fn generate(beeper: &mut Beeper, len: usize, active: bool) -> Vec<f32> {
+        let mut buffer = vec![0.0; len];
+        beeper.fill(&mut buffer, active);
+        buffer
+    }
+
+    // 1周期の平均のサンプル数
+    fn period(samples: &[f32]) -> f32 {
+        let edges = rising_edges(samples);
+        let (first, last) = (edges[0], edges[edges.len() - 1]);
+        (last - first) as f32 / (edges.len() - 1) as f32
+    }
+
+    #[test]
+    fn period_matches_the_configured_frequency() {
+        let mut beeper = Beeper::new(44_100);
+        beeper.set_tone(441.0, 0.5);
+        let samples = generate(&mut beeper, 4410, true);
+        assert!((period(&samples) - 100.0).abs() <= 1.0);
+
+        // 既定の440Hzでは約100.2サンプル
+        let mut beeper = Beeper::default();
+        let samples = generate(&mut beeper, DEFAULT_SAMPLE_RATE as usize, true);
+        let expected = DEFAULT_SAMPLE_RATE as f32 / DEFAULT_FREQUENCY;
+        assert!((period(&samples) - expected).abs() <= 1.0);
+    }
+
+    #[test]
+    fn duty_sets_the_high_part_of_each_period() {
+        let mut beeper = Beeper::new(44_100);
+        beeper.set_tone(441.0, 0.25);
+        let samples = generate(&mut beeper, 441, true);
+        let high = samples[..100].iter().filter(|&&s| s > 0.0).count();
+        assert!((24..=26).contains(&high), "{}", high);
+    }
+
+    #[test]
+    fn inactive_beeper_is_silent() {
+        let mut beeper = Beeper::default();
+        let samples = generate(&mut beeper, 441, false);
+        assert!(samples.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn one_second_matches_each_sample_rate() {
+        for sample_rate in [22_050, 48_000] {
+            let mut beeper = Beeper::default();
+            beeper.set_sample_rate(sample_rate);
+            let samples = beeper.generate(Duration::from_secs(1), true);
+            assert_eq!(samples.len(), sample_rate as usize);
+            let expected = sample_rate as f32 / DEFAULT_FREQUENCY;
+            assert!(
+                (period(&samples) - expected).abs() <= 1.0,
+                "{}",
+                sample_rate
+            );
+        }
+    }
+
+    #[test]
+    fn phase_continues_across_calls() {
+        // 2回に分けて生成しても1回で生成した波形と同じになる
+        let mut whole = Beeper::new(48_000);
+        let expected = whole.generate(Duration::from_millis(20), true);
+        let mut split = Beeper::new(48_000);
+        let mut samples = split.generate(Duration::from_millis(7), true);
+        samples.extend(split.generate(Duration::from_millis(13), true));
+        assert_eq!(samples, expected);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{Chip8, Chip8Error, Quirks, Variant};
+
+// 設定を指定してChip8を作成する
+//
+// 指定しなかった設定はChip8::new()と同じ値になる．
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chip8Builder {
+    quirks: Quirks,                       // 互換性に関わる挙動の設定
+    variant: Variant,                     // エミュレートするCHIP-8の系統
+    cpu_hz: Option<u32>,                  // 1秒あたりに実行する命令数（Noneは標準値）
+    display_size: Option<(usize, usize)>, // 論理的なディスプレイの大きさ（Noneは系統の起動時の大きさ）
+}
+
+impl Chip8Builder {
+    pub fn new() -> Chip8Builder {
+        Chip8Builder {
+            quirks: Quirks::default(),
+            variant: Variant::default(),
+            cpu_hz: None,
+            display_size: None,
+        }
+    }
+
+    // 互換性に関わる挙動を設定する
+    pub fn quirks(mut self, quirks: Quirks) -> Chip8Builder {
+        self.quirks = quirks;
+        self
+    }
+
+    // エミュレートするCHIP-8の系統を設定する
+    pub fn variant(mut self, variant: Variant) -> Chip8Builder {
+        self.variant = variant;
+        self
+    }
+
+    // 1秒あたりに実行する命令数を設定する
+    pub fn cpu_hz(mut self, cpu_hz: u32) -> Chip8Builder {
+        self.cpu_hz = Some(cpu_hz);
+        self
+    }
+
+    // 論理的なディスプレイの大きさを設定する（表示の際の拡大はレンダラーが行う）
+    pub fn display_size(mut self, width: usize, height: usize) -> Chip8Builder {
+        self.display_size = Some((width, height));
+        self
+    }
+
+    // 設定に従ってChip8を作成する（ディスプレイの大きさが範囲外の場合はエラー）
+    pub fn build(&self) -> Result<Chip8, Chip8Error> {
+        let (width, height) = self
+            .display_size
+            .unwrap_or_else(|| self.variant.display_size());
+        let mut chip8 = Chip8::with_display_size(width, height)?;
+        chip8.quirks = self.quirks;
+        chip8.set_variant(self.variant);
+        if let Some(cpu_hz) = self.cpu_hz {
+            chip8.set_cpu_hz(cpu_hz);
+        }
+        Ok(chip8)
+    }
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Chip8Builder {
+        Chip8Builder::new()
+    }
+}
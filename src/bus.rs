@@ -0,0 +1,163 @@
+use crate::{Chip8Error, ErrorKind};
+
+// メモリの範囲外（何も割り当てられていないアドレス）からデータを読み込んだ場合の扱い
+//
+// 対象はBus::readによるデータの読み込みのみで，命令の読み込み（read_u16）は設定によらず範囲外をエラーとする．
+// 書き込みも常にエラーとなる．Chip8のFX65（LD Vx, [I]），5XY3，DRWはIにオフセットを足したアドレスを
+// 折り返さずに読み込むため，メモリの末尾を超えた分（4KBなら0x1000以降，XO-CHIPの64KBなら0x10000以降）は
+// この設定に従う．
+// 多くのインタプリタはデータバスに何も出力されない場合に0を読むため，既定値はZeroとする．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnmappedReadPolicy {
+    #[default]
+    Zero, // 0を返す
+    Ones,  // 0xFFを返す
+    Error, // エラーとして実行を停止する
+    Wrap,  // メモリの大きさで折り返して先頭から読み込む
+}
+
+// CPUから見たメモリ空間
+//
+// 命令によるメモリへのアクセスは全てここを通す．範囲外のアドレスへの書き込みはエラーとし，
+// 範囲外からの読み込みはUnmappedReadPolicyの設定に従う．
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bus {
+    memory: Vec<u8>,                   // メモリ
+    unmapped_read: UnmappedReadPolicy, // 範囲外からの読み込みの扱い
+}
+
+impl Bus {
+    // 指定された大きさの0で埋めたメモリ空間を作成する
+    pub fn new(size: usize) -> Bus {
+        Bus {
+            memory: vec![0; size],
+            unmapped_read: UnmappedReadPolicy::default(),
+        }
+    }
+
+    // メモリの大きさ
+    pub fn len(&self) -> usize {
+        self.memory.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty()
+    }
+
+    // 範囲外からの読み込みの扱いを設定する
+    pub fn set_unmapped_read(&mut self, policy: UnmappedReadPolicy) {
+        self.unmapped_read = policy;
+    }
+
+    pub fn unmapped_read(&self) -> UnmappedReadPolicy {
+        self.unmapped_read
+    }
+
+    // 指定されたアドレスの値を読み込む（範囲外の場合はunmapped_readの設定に従う）
+    pub fn read(&self, addr: usize) -> Result<u8, Chip8Error> {
+        match (self.memory.get(addr), self.unmapped_read) {
+            (Some(&value), _) => Ok(value),
+            (None, UnmappedReadPolicy::Zero) => Ok(0x00),
+            (None, UnmappedReadPolicy::Ones) => Ok(0xFF),
+            (None, UnmappedReadPolicy::Error) => Err(ErrorKind::MemoryOutOfRange(addr).into()),
+            (None, UnmappedReadPolicy::Wrap) => self
+                .get(addr % self.len().max(1))
+                .ok_or_else(|| ErrorKind::MemoryOutOfRange(addr).into()),
+        }
+    }
+
+    // 指定されたアドレスの値（範囲外の場合は設定によらずNone）
+    pub fn get(&self, addr: usize) -> Option<u8> {
+        self.memory.get(addr).copied()
+    }
+
+    // 指定されたアドレスからビッグエンディアンの2バイトの命令を読み込む（範囲外は設定によらずエラー）
+    pub fn read_u16(&self, addr: usize) -> Result<u16, Chip8Error> {
+        let high = self.get(addr).ok_or(ErrorKind::MemoryOutOfRange(addr))?;
+        let low = self
+            .get(addr + 1)
+            .ok_or(ErrorKind::MemoryOutOfRange(addr + 1))?;
+        Ok((high as u16) << 8 | low as u16)
+    }
+
+    // 指定されたアドレスに値を書き込む
+    pub fn write(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let byte = self
+            .memory
+            .get_mut(addr)
+            .ok_or(ErrorKind::MemoryOutOfRange(addr))?;
+        *byte = value;
+        Ok(())
+    }
+
+    // 指定されたアドレスからバイト列を書き込み，書き込めたバイト数を返す（末尾からはみ出した分は切り捨てる）
+    pub fn load(&mut self, addr: usize, data: &[u8]) -> usize {
+        let Some(dest) = self.memory.get_mut(addr..) else {
+            return 0;
+        };
+        let len = data.len().min(dest.len());
+        dest[..len].copy_from_slice(&data[..len]);
+        len
+    }
+
+    // メモリ全体を0で埋める
+    pub fn clear(&mut self) {
+        self.memory.fill(0);
+    }
+
+    // メモリの大きさを変更する（大きくした部分は0で埋め，小さくした場合は末尾の内容が失われる）
+    pub fn resize(&mut self, size: usize) {
+        self.memory.resize(size, 0);
+    }
+
+    // メモリ全体
+    pub fn as_slice(&self) -> &[u8] {
+        &self.memory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_outside_memory_is_an_error() {
+        let mut bus = Bus::new(0x1000);
+        bus.write(0xFFF, 0x12).unwrap();
+        assert_eq!(bus.read(0xFFF).unwrap(), 0x12);
+        let err = bus.write(0x1000, 0x34).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::MemoryOutOfRange(0x1000));
+    }
+
+    #[test]
+    fn unmapped_read_follows_the_policy() {
+        let mut bus = Bus::new(0x1000);
+        assert_eq!(bus.read(0x1000).unwrap(), 0x00);
+        bus.set_unmapped_read(UnmappedReadPolicy::Ones);
+        assert_eq!(bus.read(0x1000).unwrap(), 0xFF);
+        bus.set_unmapped_read(UnmappedReadPolicy::Error);
+        let err = bus.read(0x1000).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::MemoryOutOfRange(0x1000));
+        bus.load(0x0001, &[0x12]);
+        bus.set_unmapped_read(UnmappedReadPolicy::Wrap);
+        assert_eq!(bus.read(0x1001).unwrap(), 0x12);
+        assert_eq!(bus.get(0x1000), None);
+    }
+
+    #[test]
+    fn instruction_read_at_the_last_byte_is_an_error() {
+        let mut bus = Bus::new(0x1000);
+        bus.load(0xFFE, &[0x12, 0x34]);
+        assert_eq!(bus.read_u16(0xFFE).unwrap(), 0x1234);
+        let err = bus.read_u16(0xFFF).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::MemoryOutOfRange(0x1000));
+    }
+
+    #[test]
+    fn load_truncates_at_the_end_of_memory() {
+        let mut bus = Bus::new(0x1000);
+        assert_eq!(bus.load(0xFFE, &[0x01, 0x02, 0x03]), 2);
+        assert_eq!(bus.load(0x1000, &[0x01]), 0);
+        assert_eq!(bus.as_slice()[0xFFE..], [0x01, 0x02]);
+    }
+}
@@ -0,0 +1,159 @@
+use chip8_rust::frontend::SCALE_RANGE;
+use clap::{Args, Parser, Subcommand};
+
+// コマンドライン引数の解析結果
+pub struct Options {
+    pub info: bool,                     // ROMの情報を表示するだけで実行しないか
+    pub rom_path: String,               // ROMファイルのパス（"-"は標準入力）
+    pub scale: Option<u32>,             // 描画倍率（指定されなかった場合は設定ファイルの値）
+    pub window: bool,                   // 端末ではなくウィンドウに描画するか
+    pub half_block: bool,               // 端末に半角ブロック文字で描画するか
+    pub invert: bool,                   // 白黒を反転して描画するか
+    pub dump_frame_on_exit: bool,       // 終了時にディスプレイの内容をASCIIアートで出力するか
+    pub debug: bool,                    // 対話的なデバッガで実行するか
+    pub trace: Option<String>,          // 実行した命令を書き出すファイルのパス
+    pub compare_trace: Option<String>,  // 描画せずに比較する参照トレースのパス
+    pub screenshot_dir: Option<String>, // スクリーンショットを保存するディレクトリ
+    pub symbols: Option<String>,        // シンボルファイルのパス（省略時はROMと同じ名前の.sym）
+    pub display_log: Option<String>,    // ディスプレイの変化を書き出すファイルのパス
+    pub patch: Option<String>,          // 読み込む前にROMに適用するパッチ（IPS形式）のパス
+    pub watch: bool,                    // ROMファイルが変更されたら読み込み直すか
+    pub cycles: Option<usize>,          // 描画せずに実行する命令数
+    pub timeout: Option<u64>,           // 描画せずに実行する実時間の上限（ミリ秒）
+    pub auto_profile: bool,             // 既知のROMであれば推奨設定を適用するか
+}
+
+// コマンドライン
+//
+// サブコマンドを指定しない場合はROMを実行する．"info"という名前のROMは"--"の後に指定する．
+#[derive(Parser)]
+#[command(
+    name = "chip8-rust",
+    about = "CHIP-8 emulator",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    // ROMの情報を表示して終了する
+    #[command(about = "Print ROM metadata without running it")]
+    Info {
+        #[arg(value_name = "rom | -", help = "ROM file (- reads standard input)")]
+        rom: String,
+    },
+}
+
+// ROMを実行する際の引数
+#[derive(Args)]
+struct RunArgs {
+    #[arg(
+        value_name = "rom | -",
+        required = true,
+        help = "ROM file (- reads standard input)"
+    )]
+    rom: Option<String>,
+    #[arg(long, value_name = "n", value_parser = parse_scale, help = "Rendering scale")]
+    scale: Option<u32>,
+    #[arg(long, help = "Render to a window (requires the window feature)")]
+    window: bool,
+    #[arg(long, help = "Render two rows per character in the terminal")]
+    half_block: bool,
+    #[arg(long, help = "Swap foreground and background colors")]
+    invert: bool,
+    #[arg(long, help = "Print the display as ASCII art on exit")]
+    dump_frame_on_exit: bool,
+    #[arg(long, value_name = "n", help = "Run n instructions without rendering")]
+    cycles: Option<usize>,
+    #[arg(
+        long,
+        value_name = "ms",
+        help = "Run for at most ms milliseconds without rendering"
+    )]
+    timeout: Option<u64>,
+    #[arg(long, help = "Start the interactive debugger")]
+    debug: bool,
+    #[arg(
+        long,
+        value_name = "path",
+        help = "Write every executed instruction to a file"
+    )]
+    trace: Option<String>,
+    #[arg(
+        long,
+        value_name = "path",
+        help = "Compare execution with a reference trace"
+    )]
+    compare_trace: Option<String>,
+    #[arg(long, value_name = "dir", help = "Directory for screenshots")]
+    screenshot_dir: Option<String>,
+    #[arg(
+        long,
+        value_name = "path",
+        help = "Symbol file (defaults to the ROM's .sym)"
+    )]
+    symbols: Option<String>,
+    #[arg(long, value_name = "path", help = "Write display changes to a file")]
+    display_log: Option<String>,
+    #[arg(long, value_name = "path", help = "Apply an IPS patch before loading")]
+    patch: Option<String>,
+    #[arg(long, help = "Reload the ROM when the file changes")]
+    watch: bool,
+    #[arg(long, help = "Do not apply the profile recommended for known ROMs")]
+    no_auto_profile: bool,
+}
+
+// コマンドライン引数を解析する（誤りがあれば使い方を表示して終了する）
+pub fn parse() -> Options {
+    let cli = Cli::parse();
+    let run = cli.run;
+    let (info, rom_path) = match cli.command {
+        Some(Command::Info { rom }) => (true, rom),
+        None => (false, run.rom.unwrap_or_default()),
+    };
+
+    Options {
+        info,
+        rom_path,
+        scale: run.scale,
+        window: run.window,
+        half_block: run.half_block,
+        invert: run.invert,
+        dump_frame_on_exit: run.dump_frame_on_exit,
+        debug: run.debug,
+        trace: run.trace,
+        compare_trace: run.compare_trace,
+        screenshot_dir: run.screenshot_dir,
+        symbols: run.symbols,
+        display_log: run.display_log,
+        patch: run.patch,
+        watch: run.watch,
+        cycles: run.cycles,
+        timeout: run.timeout,
+        auto_profile: !run.no_auto_profile,
+    }
+}
+
+// 描画倍率を解析し，範囲内であることを確認する
+fn parse_scale(value: &str) -> Result<u32, String> {
+    let out_of_range = || {
+        format!(
+            "--scale must be an integer between {} and {} (got {})",
+            SCALE_RANGE.start(),
+            SCALE_RANGE.end(),
+            value
+        )
+    };
+
+    let scale = value.parse::<u32>().map_err(|_| out_of_range())?;
+    if !SCALE_RANGE.contains(&scale) {
+        return Err(out_of_range());
+    }
+    Ok(scale)
+}
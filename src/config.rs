@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::frontend::{Palette, SCALE_RANGE};
+use crate::{rom_sha256, Chip8Builder, QuirkProfile, Quirks, Variant};
+
+// 設定ファイルの名前
+pub const CONFIG_FILE_NAME: &str = "chip8.toml";
+
+// 設定ファイル（chip8.toml）の内容
+//
+// 全ての項目は省略でき，省略した項目は標準値になる．コマンドライン引数で指定した値が優先される．
+// 既知のROMに推奨設定を適用する場合も，ここに書いたvariantと[quirks]の項目は推奨設定より優先される．
+//
+//     cpu_hz = 600                # 1秒あたりに実行する命令数
+//     scale = 2                   # 描画倍率（1〜20）
+//     screenshot_dir = "png"      # スクリーンショットを保存するディレクトリ
+//     variant = "chip8"           # "chip8", "super_chip", "xo_chip"
+//
+//     [quirks]
+//     logic = "leave_vf"          # "reset_vf", "leave_vf"
+//     jump = "wrap"               # "wrap", "clamp", "error"
+//     h_edge = "clip"             # "clip", "wrap"（左右の端）
+//     v_edge = "clip"             # "clip", "wrap"（上下の端）
+//     key_wait = "on_press"       # "on_press", "on_release"
+//     shift = "vx_only"           # "vx_only", "vy"
+//     load_store = "keep_i"       # "keep_i", "increment_i"
+//     scroll = "blank"            # "blank", "wrap"
+//     lores_scroll = "full"       # "full", "half"
+//     display_wait = "immediate"  # "immediate", "vblank"
+//     sys = "ignore"              # "ignore", "error"
+//     pc_wrap = false             # pcがメモリの末尾を超えたら0に折り返すか
+//
+//     [palette]                   # RGBAの配列
+//     background = [0, 0, 0, 255]
+//     foreground = [255, 255, 255, 255]
+//     plane2 = [255, 102, 0, 255]
+//     both = [102, 34, 0, 255]
+//
+//     [gamepad]                   # ボタン名とキー（gamepad機能を有効にした場合のみ使う）
+//     DPadUp = 2
+//     South = 5
+//
+//     [profiles]                  # ROMのSHA-256と推奨設定（"cosmac_vip", "super_chip", "xo_chip"）
+//     "0123...cdef" = "cosmac_vip"
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub cpu_hz: Option<u32>,                      // 1秒あたりに実行する命令数
+    pub scale: Option<u32>,                       // 描画倍率
+    pub screenshot_dir: Option<PathBuf>,          // スクリーンショットを保存するディレクトリ
+    pub variant: Variant,                         // エミュレートするCHIP-8の系統
+    pub quirks: Quirks,                           // 互換性に関わる挙動の設定
+    pub palette: Option<Palette>,                 // 描画に使う色
+    pub gamepad: BTreeMap<String, u8>,            // ゲームパッドのボタン名と対応するキー
+    pub profiles: BTreeMap<String, QuirkProfile>, // ROMのSHA-256と推奨設定（組み込みのものより優先される）
+    #[serde(skip)]
+    explicit: Explicit,         // 設定ファイルに書かれていた項目（推奨設定より優先する）
+}
+
+// 設定ファイルに明示的に書かれていた項目
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct Explicit {
+    variant: bool,            // variantが書かれていたか
+    quirks: BTreeSet<String>, // [quirks]に書かれていた項目の名前
+}
+
+impl Config {
+    // TOMLの文字列から設定を読み込む
+    pub fn parse(text: &str) -> io::Result<Config> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        let mut config: Config = toml::from_str(text).map_err(invalid)?;
+        let table: toml::Table = toml::from_str(text).map_err(invalid)?;
+        config.explicit = Explicit {
+            variant: table.contains_key("variant"),
+            quirks: table
+                .get("quirks")
+                .and_then(toml::Value::as_table)
+                .map(|quirks| quirks.keys().cloned().collect())
+                .unwrap_or_default(),
+        };
+        if let Some(scale) = config.scale {
+            if !SCALE_RANGE.contains(&scale) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "scale must be an integer between {} and {} (got {})",
+                        SCALE_RANGE.start(),
+                        SCALE_RANGE.end(),
+                        scale
+                    ),
+                ));
+            }
+        }
+        Ok(config)
+    }
+
+    // 設定ファイルを読み込む
+    pub fn load(path: &Path) -> io::Result<Config> {
+        Config::parse(&fs::read_to_string(path)?)
+    }
+
+    // 設定ファイルを探す（ROMのあるディレクトリ，カレントディレクトリの順）
+    pub fn find(rom_path: Option<&Path>) -> Option<PathBuf> {
+        let rom_dir = rom_path
+            .and_then(Path::parent)
+            .map(|dir| dir.join(CONFIG_FILE_NAME));
+        rom_dir
+            .into_iter()
+            .chain(std::iter::once(PathBuf::from(CONFIG_FILE_NAME)))
+            .find(|path| path.is_file())
+    }
+
+    // ROMの推奨設定を返す（設定ファイルに登録されていなければ組み込みのものを探す）
+    pub fn recommend_profile(&self, rom: &[u8]) -> Option<QuirkProfile> {
+        let hash = rom_sha256(rom);
+        self.profiles
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(&hash))
+            .map(|(_, &profile)| profile)
+            .or_else(|| crate::recommend_profile(rom))
+    }
+
+    // 設定を反映したChip8Builder
+    pub fn builder(&self) -> Chip8Builder {
+        let builder = Chip8Builder::new()
+            .quirks(self.quirks)
+            .variant(self.variant);
+        match self.cpu_hz {
+            Some(cpu_hz) => builder.cpu_hz(cpu_hz),
+            None => builder,
+        }
+    }
+
+    // 推奨設定を反映したChip8Builder（設定ファイルに書かれた系統と互換性の設定の項目はそちらを優先する）
+    pub fn builder_with_profile(&self, profile: QuirkProfile) -> Chip8Builder {
+        let variant = if self.explicit.variant {
+            self.variant
+        } else {
+            profile.variant()
+        };
+        let mut quirks = profile.quirks();
+        for name in &self.explicit.quirks {
+            match name.as_str() {
+                "logic" => quirks.logic = self.quirks.logic,
+                "jump" => quirks.jump = self.quirks.jump,
+                "h_edge" => quirks.h_edge = self.quirks.h_edge,
+                "v_edge" => quirks.v_edge = self.quirks.v_edge,
+                "key_wait" => quirks.key_wait = self.quirks.key_wait,
+                "shift" => quirks.shift = self.quirks.shift,
+                "load_store" => quirks.load_store = self.quirks.load_store,
+                "scroll" => quirks.scroll = self.quirks.scroll,
+                "lores_scroll" => quirks.lores_scroll = self.quirks.lores_scroll,
+                "display_wait" => quirks.display_wait = self.quirks.display_wait,
+                "sys" => quirks.sys = self.quirks.sys,
+                "pc_wrap" => quirks.pc_wrap = self.quirks.pc_wrap,
+                // 未知の項目はQuirksの解析でエラーになる
+                _ => {}
+            }
+        }
+        self.builder().variant(variant).quirks(quirks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JumpQuirk, ShiftQuirk};
+
+    const SAMPLE: &str = r#"
+cpu_hz = 1200
+scale = 4
+variant = "super_chip"
+
+[quirks]
+jump = "clamp"
+shift = "vy"
+
+[palette]
+foreground = [0, 255, 0, 255]
+"#;
+
+    #[test]
+    fn sample_config_sets_builder() {
+        let config = Config::parse(SAMPLE).unwrap();
+        assert_eq!(config.scale, Some(4));
+        assert_eq!(config.palette.unwrap().foreground, [0x00, 0xFF, 0x00, 0xFF]);
+
+        // 省略した項目は標準値になる
+        let quirks = Quirks {
+            jump: JumpQuirk::Clamp,
+            shift: ShiftQuirk::Vy,
+            ..Quirks::default()
+        };
+        let expected = Chip8Builder::new()
+            .quirks(quirks)
+            .variant(Variant::SuperChip)
+            .cpu_hz(1200);
+        assert_eq!(config.builder(), expected);
+        let chip8 = config.builder().build().unwrap();
+        assert_eq!(chip8.cpu_hz(), 1200);
+        assert_eq!(chip8.variant(), Variant::SuperChip);
+    }
+
+    #[test]
+    fn explicit_values_override_the_profile_field_by_field() {
+        let config = Config::parse(SAMPLE).unwrap();
+        let quirks = Quirks {
+            jump: JumpQuirk::Clamp,
+            shift: ShiftQuirk::Vy,
+            ..QuirkProfile::XoChip.quirks()
+        };
+        let expected = Chip8Builder::new()
+            .quirks(quirks)
+            .variant(Variant::SuperChip)
+            .cpu_hz(1200);
+        assert_eq!(config.builder_with_profile(QuirkProfile::XoChip), expected);
+
+        // 何も書かれていなければ推奨設定をそのまま使う
+        let config = Config::parse("").unwrap();
+        let expected = Chip8Builder::new()
+            .quirks(QuirkProfile::XoChip.quirks())
+            .variant(Variant::XoChip);
+        assert_eq!(config.builder_with_profile(QuirkProfile::XoChip), expected);
+    }
+
+    #[test]
+    fn out_of_range_scale_is_rejected() {
+        let err = Config::parse("scale = 0").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
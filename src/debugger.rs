@@ -0,0 +1,717 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::frontend::Screenshot;
+use crate::{Chip8, Chip8Error, Instruction, Opcode, MNEMONICS};
+
+const HELP: &str = "\
+commands:
+  s            execute one instruction
+  n            execute one instruction, stepping over subroutine calls
+  c            continue until a breakpoint (Ctrl-C to interrupt)
+  c <n>        execute n instructions, stopping early at a breakpoint
+  draw         execute until an instruction changes the display (CLS, DRW, scroll)
+  b <addr>     set a breakpoint (hex address)
+  d <addr>     delete a breakpoint
+  find <bytes> search memory for hex bytes (e.g. find F0 90 F0)
+  break-if <reg> <op> <value>
+               break when a register matches (op: == != < >, hex value)
+  break-op <op>
+               break before an instruction by high nibble (e.g. D) or mnemonic (e.g. DRW)
+  disas [n]    disassemble n instructions from pc (default 8)
+  memmap       show an overview of memory (font, ROM, stack, I, pc)
+  macro <steps>
+               queue key input for the next frames (e.g. macro hold 5 10; press 6; wait 3)
+               steps: hold <keys> <frames>, press <keys>, wait <frames> (keys: hex, 5+6 for chords)
+  r            show registers
+  p            print the display
+  shot         save a PNG screenshot of the display
+  h            show this help
+  q            quit";
+
+// disasで逆アセンブルする命令数の既定値
+const DISAS_COUNT: usize = 8;
+
+// drawで画面を変更する命令を探す最大の命令数
+const DRAW_CYCLE_LIMIT: usize = 1_000_000;
+
+// コマンドの実行結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Continue(String), // 出力を表示して次のコマンドを待つ
+    Quit,             // デバッガを終了する
+}
+
+// 条件付きブレークポイントの比較演算子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq, // ==
+    Ne, // !=
+    Lt, // <
+    Gt, // >
+}
+
+// レジスタの値に関する条件付きブレークポイント
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub register: usize,        // 比較するレジスタの番号
+    pub comparison: Comparison, // 比較演算子
+    pub value: u8,              // 比較する値
+}
+
+impl Condition {
+    // 条件を満たしているか
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        let register = chip8.registers()[self.register];
+        match self.comparison {
+            Comparison::Eq => register == self.value,
+            Comparison::Ne => register != self.value,
+            Comparison::Lt => register < self.value,
+            Comparison::Gt => register > self.value,
+        }
+    }
+
+    // "V5 == 0A"の形式の条件を解析する
+    fn parse(register: &str, comparison: &str, value: &str) -> Option<Condition> {
+        let register = register.trim_start_matches(['V', 'v']);
+        let register = usize::from_str_radix(register, 16)
+            .ok()
+            .filter(|&r| r < 16)?;
+        let comparison = match comparison {
+            "==" => Comparison::Eq,
+            "!=" => Comparison::Ne,
+            "<" => Comparison::Lt,
+            ">" => Comparison::Gt,
+            _ => return None,
+        };
+        let value = value.trim_start_matches("0x").trim_start_matches("0X");
+        let value = u8::from_str_radix(value, 16).ok()?;
+        Some(Condition {
+            register,
+            comparison,
+            value,
+        })
+    }
+}
+
+// 命令の種類に関するブレークポイント
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpBreakpoint {
+    Nibble(u8),             // opcodeの上位4ビット
+    Mnemonic(&'static str), // 命令のニーモニック
+}
+
+impl OpBreakpoint {
+    // 命令が一致するか
+    pub fn matches(&self, opcode: u16, instruction: Option<Instruction>) -> bool {
+        match self {
+            OpBreakpoint::Nibble(nibble) => Opcode(opcode).high_nibble() == *nibble,
+            OpBreakpoint::Mnemonic(mnemonic) => {
+                instruction.is_some_and(|instruction| instruction.mnemonic() == *mnemonic)
+            }
+        }
+    }
+
+    // 1桁の16進数，またはニーモニック（大文字小文字を区別しない）を解析する
+    fn parse(s: &str) -> Option<OpBreakpoint> {
+        if s.len() == 1 {
+            return u8::from_str_radix(s, 16).ok().map(OpBreakpoint::Nibble);
+        }
+        MNEMONICS
+            .iter()
+            .find(|mnemonic| mnemonic.eq_ignore_ascii_case(s))
+            .map(|mnemonic| OpBreakpoint::Mnemonic(mnemonic))
+    }
+}
+
+// フレームごとのキー入力を組み立てる簡単なマクロ
+//
+// ";"か改行で区切った次の手順を順に並べる．キーは16進数の1桁で，"+"でつないだものは同時に押す．
+// - hold <keys> <frames>: キーをframesフレームの間押し続ける
+// - press <keys>: キーを1フレームだけ押し，次のフレームで離す
+// - wait <frames>: 何も押さずにframesフレーム待つ
+// キーが押されたまま再生を終えないように，最後のフレームでキーが押されていれば離すフレームを追加する．
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct KeyMacro {
+    masks: Vec<u16>, // フレームごとのキーの状態（ビットnがキーnに対応する）
+}
+
+impl KeyMacro {
+    // マクロを解析する
+    pub fn parse(text: &str) -> Result<KeyMacro, String> {
+        let mut masks = Vec::new();
+        for step in text.split([';', '\n']).map(str::trim) {
+            let words: Vec<_> = step.split_whitespace().collect();
+            match words[..] {
+                [] => {}
+                ["hold", keys, frames] => {
+                    let mask = parse_keys(keys)?;
+                    masks.extend(std::iter::repeat_n(mask, parse_frames(frames)?));
+                }
+                ["press", keys] => masks.extend([parse_keys(keys)?, 0]),
+                ["wait", frames] => masks.extend(std::iter::repeat_n(0, parse_frames(frames)?)),
+                _ => return Err(format!("invalid step: {}", step)),
+            }
+        }
+        if masks.last().is_some_and(|&mask| mask != 0) {
+            masks.push(0);
+        }
+        Ok(KeyMacro { masks })
+    }
+
+    // フレームごとのキーの状態
+    pub fn masks(&self) -> &[u16] {
+        &self.masks
+    }
+}
+
+// "5"や"5+6"の形式のキーを解析する
+fn parse_keys(keys: &str) -> Result<u16, String> {
+    keys.split('+')
+        .try_fold(0u16, |mask, key| match u8::from_str_radix(key, 16) {
+            Ok(key) if key < 16 => Ok(mask | 1 << key),
+            _ => Err(format!("invalid key: {}", key)),
+        })
+}
+
+fn parse_frames(frames: &str) -> Result<usize, String> {
+    frames
+        .parse()
+        .map_err(|_| format!("invalid frame count: {}", frames))
+}
+
+// 対話的に命令を実行するデバッガ
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,        // ブレークポイントを設定したアドレス
+    conditions: Vec<Condition>,        // 条件付きブレークポイント（各命令の実行後に評価する）
+    op_breakpoints: Vec<OpBreakpoint>, // 命令の種類に関するブレークポイント（実行する直前に止まる）
+    interrupt: Arc<AtomicBool>,        // 実行の中断の要求（SIGINTのハンドラなどから設定する）
+    screenshot: Screenshot,            // スクリーンショットの保存先と描画の設定
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger::default()
+    }
+
+    // ブレークポイントを設定する
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    // ブレークポイントを削除する
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    // 条件付きブレークポイントを設定する
+    pub fn add_condition(&mut self, condition: Condition) {
+        self.conditions.push(condition);
+    }
+
+    // 命令の種類に関するブレークポイントを設定する
+    pub fn add_op_breakpoint(&mut self, breakpoint: OpBreakpoint) {
+        self.op_breakpoints.push(breakpoint);
+    }
+
+    // shotコマンドで保存するスクリーンショットの設定
+    pub fn set_screenshot(&mut self, screenshot: Screenshot) {
+        self.screenshot = screenshot;
+    }
+
+    // 実行の中断を要求するためのフラグ
+    //
+    // trueにすると，cやnで実行中の命令列がその時点の命令で止まり，コマンドの入力に戻る．
+    // 中断した時点でフラグはfalseに戻る．
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupt)
+    }
+
+    // 実行を止めるべきか（ブレークポイントに到達したか条件を満たしたか，中断が要求されたか）
+    fn should_break(&self, chip8: &Chip8) -> bool {
+        self.interrupt.swap(false, Ordering::SeqCst)
+            || self.breakpoints.contains(&chip8.pc())
+            || self
+                .conditions
+                .iter()
+                .any(|condition| condition.holds(chip8))
+            || self.matches_op_breakpoint(chip8)
+    }
+
+    // 次に実行する命令が命令の種類に関するブレークポイントに一致するか
+    fn matches_op_breakpoint(&self, chip8: &Chip8) -> bool {
+        let Some(opcode) = chip8.opcode_at(chip8.pc()) else {
+            return false;
+        };
+        let instruction = Instruction::decode(opcode, chip8.variant());
+        self.op_breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.matches(opcode, instruction))
+    }
+
+    // 標準入力からコマンドを読み込み，終了するまで実行する
+    pub fn repl(&mut self, chip8: &mut Chip8) -> io::Result<()> {
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+
+        writeln!(stdout, "{}", describe(chip8))?;
+        loop {
+            write!(stdout, "(chip8) ")?;
+            stdout.flush()?;
+
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+
+            match self.execute(chip8, line.trim()) {
+                Ok(Outcome::Continue(output)) => writeln!(stdout, "{}", output)?,
+                Ok(Outcome::Quit) => return Ok(()),
+                Err(e) => writeln!(stdout, "error: {}", e)?,
+            }
+        }
+    }
+
+    // 1つのコマンドを実行する
+    pub fn execute(&mut self, chip8: &mut Chip8, command: &str) -> Result<Outcome, Chip8Error> {
+        let mut words = command.split_whitespace();
+        if command.starts_with("break-if") {
+            let args: Vec<_> = words.skip(1).collect();
+            let output = match args[..] {
+                [register, comparison, value] => {
+                    match Condition::parse(register, comparison, value) {
+                        Some(condition) => {
+                            self.add_condition(condition);
+                            format!("break if {} {} {}", register, comparison, value)
+                        }
+                        None => format!("invalid condition: {}", args.join(" ")),
+                    }
+                }
+                _ => "usage: break-if <reg> <op> <value>".to_string(),
+            };
+            return Ok(Outcome::Continue(output));
+        }
+
+        if command.starts_with("break-op") {
+            let output = match (words.nth(1), words.next()) {
+                (Some(op), None) => match OpBreakpoint::parse(op) {
+                    Some(breakpoint) => {
+                        self.add_op_breakpoint(breakpoint);
+                        format!("break on {}", op.to_ascii_uppercase())
+                    }
+                    None => format!("invalid instruction: {}", op),
+                },
+                _ => "usage: break-op <nibble-or-mnemonic>".to_string(),
+            };
+            return Ok(Outcome::Continue(output));
+        }
+
+        if let Some(steps) = command.strip_prefix("macro") {
+            let output = match KeyMacro::parse(steps.trim()) {
+                Ok(key_macro) if !key_macro.masks().is_empty() => {
+                    chip8.replay_key_masks(key_macro.masks());
+                    format!("queued {} frames of key input", key_macro.masks().len())
+                }
+                Ok(_) => "usage: macro <steps>".to_string(),
+                Err(e) => e,
+            };
+            return Ok(Outcome::Continue(output));
+        }
+
+        if command.starts_with("find") {
+            let bytes: Option<Vec<u8>> = words
+                .skip(1)
+                .map(|byte| u8::from_str_radix(byte, 16).ok())
+                .collect();
+            let output = match bytes {
+                Some(bytes) if !bytes.is_empty() => {
+                    let addrs: Vec<_> = chip8
+                        .find_in_memory(&bytes)
+                        .iter()
+                        .map(|addr| format!("{:#05X}", addr))
+                        .collect();
+                    if addrs.is_empty() {
+                        "not found".to_string()
+                    } else {
+                        addrs.join(" ")
+                    }
+                }
+                _ => "usage: find <bytes>".to_string(),
+            };
+            return Ok(Outcome::Continue(output));
+        }
+
+        let output = match (words.next(), words.next()) {
+            (Some("s"), None) => {
+                chip8.run_cycles(1)?;
+                describe(chip8)
+            }
+            (Some("n"), None) => {
+                self.step_over(chip8)?;
+                describe(chip8)
+            }
+            (Some("c"), None) => {
+                self.continue_to_breakpoint(chip8)?;
+                describe(chip8)
+            }
+            (Some("c"), Some(n)) => match n.parse() {
+                Ok(n) => {
+                    let executed = self.continue_cycles(chip8, n)?;
+                    format!("executed {} instructions\n{}", executed, describe(chip8))
+                }
+                Err(_) => format!("invalid count: {}", n),
+            },
+            (Some("draw"), None) => {
+                let executed = chip8.step_to_next_draw(DRAW_CYCLE_LIMIT)?;
+                format!("executed {} instructions\n{}", executed, describe(chip8))
+            }
+            (Some("b"), Some(addr)) => match parse_addr(addr) {
+                Some(addr) => {
+                    self.add_breakpoint(addr);
+                    format!("breakpoint at {:#05X}", addr)
+                }
+                None => format!("invalid address: {}", addr),
+            },
+            (Some("d"), Some(addr)) => match parse_addr(addr) {
+                Some(addr) if self.remove_breakpoint(addr) => {
+                    format!("deleted breakpoint at {:#05X}", addr)
+                }
+                Some(addr) => format!("no breakpoint at {:#05X}", addr),
+                None => format!("invalid address: {}", addr),
+            },
+            (Some("disas"), count) => match count.map_or(Ok(DISAS_COUNT), str::parse) {
+                Ok(count) => chip8
+                    .disassemble_at(chip8.pc(), count)
+                    .iter()
+                    .map(|(addr, opcode, text)| {
+                        let line = format!("{:#05X}: {:04X} {}", addr, opcode, text);
+                        with_label(chip8, *addr, line)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Err(_) => format!("invalid count: {}", count.unwrap_or_default()),
+            },
+            (Some("memmap"), None) => chip8.memory_map(),
+            (Some("r"), None) => registers(chip8),
+            (Some("p"), None) => chip8.display_ascii(),
+            (Some("shot"), None) => match self.screenshot.save(chip8.display()) {
+                Ok(path) => format!("saved {}", path.display()),
+                Err(e) => format!("failed to save screenshot: {}", e),
+            },
+            (Some("h"), None) | (None, _) => HELP.to_string(),
+            (Some("q"), None) => return Ok(Outcome::Quit),
+            _ => format!("unknown command: {} (h for help)", command),
+        };
+        Ok(Outcome::Continue(output))
+    }
+
+    // 1命令を実行する．CALLの場合は，そのサブルーチンから戻るまで実行する
+    //
+    // 戻り先のアドレスは計算せず，スタックの深さが呼び出す前に戻ったことで判断する．
+    // RETはCALLが積んだアドレスに戻るため，pcがメモリの末尾で折り返す場合も正しく止まる．
+    pub fn step_over(&mut self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let is_call = matches!(chip8.opcode_at(chip8.pc()), Some(opcode) if Opcode(opcode).high_nibble() == 0x2);
+        let depth = chip8.stack_depth();
+        self.interrupt.store(false, Ordering::SeqCst);
+
+        chip8.run_cycles(1)?;
+        if !is_call {
+            return Ok(());
+        }
+
+        // 再帰的な呼び出しの途中で止まらないように，同じアドレスではなくスタックの深さで判断する
+        while chip8.stack_depth() > depth {
+            if self.should_break(chip8) || chip8.is_halted() {
+                break;
+            }
+            chip8.run_cycles(1)?;
+        }
+        Ok(())
+    }
+
+    // ブレークポイントに到達するまで実行する
+    pub fn continue_to_breakpoint(&mut self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        // 現在のアドレスのブレークポイントで止まらないように，まず1命令実行する
+        self.interrupt.store(false, Ordering::SeqCst);
+        chip8.run_cycles(1)?;
+        while !self.should_break(chip8) && !chip8.is_halted() {
+            chip8.run_cycles(1)?;
+        }
+        Ok(())
+    }
+
+    // 最大でn個の命令を実行し，実行した命令数を返す（ブレークポイントに到達した場合はそこで止まる）
+    pub fn continue_cycles(&mut self, chip8: &mut Chip8, n: usize) -> Result<usize, Chip8Error> {
+        self.interrupt.store(false, Ordering::SeqCst);
+        let mut executed = 0;
+        while executed < n && !chip8.is_halted() {
+            // 現在のアドレスのブレークポイントでは止まらない
+            if executed > 0 && self.should_break(chip8) {
+                break;
+            }
+            executed += chip8.run_cycles(1)?;
+        }
+        Ok(executed)
+    }
+}
+
+// 16進数のアドレスを解析する（"0x"は省略可能）
+fn parse_addr(s: &str) -> Option<u16> {
+    let s = s.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(s, 16).ok()
+}
+
+// 次に実行する命令
+fn describe(chip8: &Chip8) -> String {
+    let line = match chip8.opcode_at(chip8.pc()) {
+        Some(opcode) => match Instruction::decode(opcode, chip8.variant()) {
+            Some(instruction) => format!("{:#05X}: {:04X} {:?}", chip8.pc(), opcode, instruction),
+            None => format!("{:#05X}: {:04X} (unknown)", chip8.pc(), opcode),
+        },
+        None => format!("{:#05X}: (out of memory)", chip8.pc()),
+    };
+    with_label(chip8, chip8.pc(), line)
+}
+
+// アドレスに名前が登録されていれば，その名前の行を前に付ける
+fn with_label(chip8: &Chip8, addr: u16, line: String) -> String {
+    match chip8.symbols().get(addr) {
+        Some(name) => format!("{}:\n{}", name, line),
+        None => line,
+    }
+}
+
+// レジスタの内容
+fn registers(chip8: &Chip8) -> String {
+    let mut out = String::new();
+    for (i, value) in chip8.registers().iter().enumerate() {
+        out.push_str(&format!("V{:X}={:02X} ", i, value));
+        if i % 8 == 7 {
+            out.push('\n');
+        }
+    }
+    out.push_str(&format!(
+        "I={:#05X} PC={:#05X} SP={}",
+        chip8.i(),
+        chip8.pc(),
+        chip8.stack_depth()
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quirks;
+
+    // 16進数の文字列のプログラムを読み込んだChip8を作成する
+    fn chip8_with(hex: &str) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_hex(hex).unwrap();
+        chip8
+    }
+
+    // CALL 0x206; LD V1, 2; JP 0x204; LD V0, 1（サブルーチン）; RET
+    const CALL_PROGRAM: &str = "2206 6102 1204 6001 00EE";
+
+    #[test]
+    fn step_over_runs_the_whole_subroutine() {
+        let mut chip8 = chip8_with(CALL_PROGRAM);
+        let mut debugger = Debugger::new();
+        debugger.execute(&mut chip8, "n").unwrap();
+        assert_eq!(chip8.pc(), 0x202);
+        assert_eq!(chip8.registers()[0], 1);
+        assert_eq!(chip8.stack_depth(), 0);
+    }
+
+    #[test]
+    fn step_over_steps_once_for_other_instructions() {
+        let mut chip8 = chip8_with("6001 6102");
+        Debugger::new().step_over(&mut chip8).unwrap();
+        assert_eq!(chip8.pc(), 0x202);
+        assert_eq!(chip8.registers()[1], 0);
+    }
+
+    #[test]
+    fn step_over_handles_recursive_calls() {
+        // CALL 0x204; JP 0x202; ADD V0, 1; SE V0, 3; CALL 0x204; RET
+        let mut chip8 = chip8_with("2204 1202 7001 3003 2204 00EE");
+        Debugger::new().step_over(&mut chip8).unwrap();
+        assert_eq!(chip8.pc(), 0x202);
+        assert_eq!(chip8.registers()[0], 3);
+        assert_eq!(chip8.stack_depth(), 0);
+    }
+
+    #[test]
+    fn step_over_a_call_at_the_end_of_memory() {
+        let mut chip8 = Chip8::with_quirks(Quirks {
+            pc_wrap: true,
+            ..Quirks::default()
+        });
+        // JP 0xFFE; 0xFFE: CALL 0x300; 0x300: LD V0, 1; RET
+        chip8.load_hex("1FFE").unwrap();
+        for (addr, value) in [(0xFFE, 0x23), (0xFFF, 0x00), (0x300, 0x60), (0x301, 0x01)] {
+            chip8.poke(addr, value).unwrap();
+        }
+        chip8.poke(0x302, 0x00).unwrap();
+        chip8.poke(0x303, 0xEE).unwrap();
+        chip8.step().unwrap();
+
+        Debugger::new().step_over(&mut chip8).unwrap();
+        assert_eq!(chip8.pc(), 0x000);
+        assert_eq!(chip8.registers()[0], 1);
+    }
+
+    #[test]
+    fn break_if_halts_when_register_hits_the_target() {
+        // ADD V5, 1; JP 0x200
+        let mut chip8 = chip8_with("7501 1200");
+        let mut debugger = Debugger::new();
+        let output = debugger.execute(&mut chip8, "break-if V5 == 0A").unwrap();
+        assert_eq!(output, Outcome::Continue("break if V5 == 0A".to_string()));
+        debugger.execute(&mut chip8, "c").unwrap();
+        assert_eq!(chip8.registers()[5], 0x0A);
+        assert_eq!(chip8.pc(), 0x202);
+    }
+
+    #[test]
+    fn break_if_supports_each_comparison() {
+        let mut chip8 = chip8_with("6503");
+        chip8.step().unwrap();
+        // V5 = 3を2と比べる
+        for (comparison, expected) in [("==", false), ("!=", true), ("<", false), (">", true)] {
+            let condition = Condition::parse("V5", comparison, "2").unwrap();
+            assert_eq!(condition.holds(&chip8), expected, "{}", comparison);
+        }
+    }
+
+    #[test]
+    fn find_command_prints_addresses() {
+        let mut chip8 = Chip8::new();
+        let output = Debugger::new()
+            .execute(&mut chip8, "find F0 90 F0 90 F0")
+            .unwrap();
+        assert_eq!(output, Outcome::Continue("0x028".to_string()));
+    }
+
+    #[test]
+    fn interrupt_flag_yields_to_the_debugger() {
+        // 無限ループの実行中に，別のスレッドからSIGINTのハンドラと同じようにフラグを設定する
+        let mut chip8 = chip8_with("1200");
+        let mut debugger = Debugger::new();
+        let interrupt = debugger.interrupt_flag();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            interrupt.store(true, Ordering::SeqCst);
+        });
+
+        debugger.continue_to_breakpoint(&mut chip8).unwrap();
+        handle.join().unwrap();
+        assert_eq!(chip8.pc(), 0x200);
+        // 中断した時点でフラグは戻り，次のSIGINTは再び中断として扱われる
+        assert!(!debugger.interrupt_flag().load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn break_op_halts_at_the_first_draw() {
+        let mut chip8 = chip8_with("A000 6000 6100 D015 1208");
+        let mut debugger = Debugger::new();
+        let output = debugger.execute(&mut chip8, "break-op drw").unwrap();
+        assert_eq!(output, Outcome::Continue("break on DRW".to_string()));
+        debugger.execute(&mut chip8, "c").unwrap();
+        assert_eq!(chip8.pc(), 0x206);
+    }
+
+    #[test]
+    fn break_op_matches_the_high_nibble() {
+        let breakpoint = OpBreakpoint::parse("d").unwrap();
+        assert_eq!(breakpoint, OpBreakpoint::Nibble(0xD));
+        assert!(breakpoint.matches(0xD015, None));
+        assert!(!breakpoint.matches(0x1208, None));
+        assert_eq!(OpBreakpoint::parse("XYZ"), None);
+    }
+
+    #[test]
+    fn continue_n_executes_exactly_n_instructions() {
+        // ADD V0, 1を100個並べる
+        let mut chip8 = chip8_with(&["7001"; 100].join(" "));
+        let mut debugger = Debugger::new();
+        let Outcome::Continue(output) = debugger.execute(&mut chip8, "c 50").unwrap() else {
+            panic!("debugger quit");
+        };
+        assert!(
+            output.starts_with("executed 50 instructions\n"),
+            "{}",
+            output
+        );
+        assert_eq!(chip8.pc(), 0x200 + 2 * 50);
+        assert_eq!(chip8.registers()[0], 50);
+    }
+
+    #[test]
+    fn continue_n_stops_at_a_breakpoint() {
+        let mut chip8 = chip8_with(&["7001"; 100].join(" "));
+        let mut debugger = Debugger::new();
+        debugger.execute(&mut chip8, "b 220").unwrap();
+        let Outcome::Continue(output) = debugger.execute(&mut chip8, "c 50").unwrap() else {
+            panic!("debugger quit");
+        };
+        assert!(
+            output.starts_with("executed 16 instructions\n"),
+            "{}",
+            output
+        );
+        assert_eq!(chip8.pc(), 0x220);
+    }
+
+    #[test]
+    fn disas_shows_instructions_from_pc() {
+        let mut chip8 = chip8_with("6005 A234 00E0");
+        let mut debugger = Debugger::new();
+        let Outcome::Continue(output) = debugger.execute(&mut chip8, "disas 2").unwrap() else {
+            panic!("debugger quit");
+        };
+        assert_eq!(output, "0x200: 6005 LD V0, 0x05\n0x202: A234 LD I, 0x234");
+    }
+
+    #[test]
+    fn key_macro_builds_per_frame_masks() {
+        let key_macro = KeyMacro::parse("hold 5 3; press 6; wait 2").unwrap();
+        assert_eq!(key_macro.masks(), [0x0020, 0x0020, 0x0020, 0x0040, 0, 0, 0]);
+
+        // 同時押しと改行区切り（最後まで押したままなら離すフレームを追加する）
+        let key_macro = KeyMacro::parse("press 1+a\nhold f 2").unwrap();
+        assert_eq!(key_macro.masks(), [0x0402, 0, 0x8000, 0x8000, 0]);
+
+        assert!(KeyMacro::parse("hold 5").is_err());
+        assert!(KeyMacro::parse("press 10").is_err());
+        assert!(KeyMacro::parse("wait x").is_err());
+    }
+
+    #[test]
+    fn macro_command_drives_the_keys_frame_by_frame() {
+        let mut chip8 = chip8_with("1200");
+        let mut debugger = Debugger::new();
+        let outcome = debugger
+            .execute(&mut chip8, "macro hold 5 2; press 6")
+            .unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::Continue("queued 4 frames of key input".to_string())
+        );
+
+        let mut masks = Vec::new();
+        for _ in 0..5 {
+            chip8.run_frame().unwrap();
+            let mask = (0..16)
+                .filter(|&k| chip8.keyboard[k])
+                .fold(0u16, |m, k| m | 1 << k);
+            masks.push(mask);
+        }
+        assert_eq!(masks, [0x0020, 0x0020, 0x0040, 0, 0]);
+        assert!(!chip8.is_replaying());
+    }
+}
@@ -0,0 +1,418 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{Chip8Error, DrawMode, EdgeQuirk, ErrorKind, Variant};
+
+// 論理的なディスプレイの大きさ（画素数）
+//
+// 命令が読み書きする画素の数であり，画面に表示する大きさではない．
+// 表示の際の拡大はレンダラー（TerminalFrontend，RgbaRenderer，save_pngなど）がそれぞれ行う．
+pub const LORES_WIDTH: usize = 64; // 低解像度（全ての系統の起動時の大きさ）
+pub const LORES_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128; // SUPER-CHIPの高解像度
+pub const HIRES_HEIGHT: usize = 64;
+
+// ディスプレイの大きさの上限
+pub const MAX_DISPLAY_WIDTH: usize = 256;
+pub const MAX_DISPLAY_HEIGHT: usize = 128;
+
+// DRWで描画する1つのプレーン分のスプライト
+#[derive(Debug, Clone, Copy)]
+pub struct Sprite<'a> {
+    pub rows: &'a [u16], // 各行の画素（下位widthビットを使い，上位のビットが左）
+    pub width: usize,    // 横の画素数（8または16）
+}
+
+// 画素を読み書きできるディスプレイ
+//
+// DRWによるスプライトの描画（座標の折り返しや切り捨て）はwidthとheightだけを使って行うため，
+// 実行時の大きさ（00FE/00FFや系統の切り替え）に応じて同じ計算で描画できる．
+// Chip8は大きさを実行時に変えるため，大きさを型で固定したディスプレイではなくLogicalDisplayに描画する．
+pub trait Framebuffer {
+    fn width(&self) -> usize;
+
+    fn height(&self) -> usize;
+
+    // (x, y)の画素
+    fn get(&self, x: usize, y: usize) -> u8;
+
+    // (x, y)の画素を設定する
+    fn set(&mut self, x: usize, y: usize, pixel: u8);
+
+    // (x, y)から指定されたプレーン（ビットマスクの1ビット）にスプライトを描画し，衝突したかを返す
+    //
+    // 開始座標は画面の大きさで折り返し，はみ出した画素はedges（横，縦）の設定に応じて切り捨てるか
+    // 反対側に折り返す．
+    fn draw_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: Sprite,
+        plane: u8,
+        edges: (EdgeQuirk, EdgeQuirk),
+        mode: DrawMode,
+    ) -> bool {
+        let (width, height) = (self.width(), self.height());
+        let (x, y) = (x % width, y % height);
+        let (h_edge, v_edge) = edges;
+
+        let mut collision = false;
+        for (i, &line) in sprite.rows.iter().enumerate() {
+            let Some(screen_y) = edge_position(v_edge, y + i, height) else {
+                continue;
+            };
+            for j in 0..sprite.width {
+                let Some(screen_x) = edge_position(h_edge, x + j, width) else {
+                    continue;
+                };
+                let sprite_pixel = ((line >> (sprite.width - 1 - j)) & 0x01) as u8;
+
+                let screen = self.get(screen_x, screen_y);
+                let screen_pixel = (screen & plane != 0) as u8;
+                collision |= screen_pixel == 1 && sprite_pixel == 1;
+                let pixel = match mode {
+                    DrawMode::Xor => screen_pixel ^ sprite_pixel,
+                    DrawMode::Or => screen_pixel | sprite_pixel,
+                };
+                let screen = if pixel == 1 {
+                    screen | plane
+                } else {
+                    screen & !plane
+                };
+                self.set(screen_x, screen_y, screen);
+            }
+        }
+        collision
+    }
+}
+
+// スプライトの画素の座標を画面内の座標に変換する（切り捨てる場合はNone）
+fn edge_position(edge: EdgeQuirk, pos: usize, size: usize) -> Option<usize> {
+    match edge {
+        _ if pos < size => Some(pos),
+        EdgeQuirk::Clip => None,
+        EdgeQuirk::Wrap => Some(pos % size),
+    }
+}
+
+// 論理的なディスプレイの画素
+//
+// 命令（CLS，DRW，スクロールなど）はこの画素だけを読み書きし，表示の際の大きさは扱わない．
+// 1画素は2ビットで，最下位ビットがプレーン1，次のビットがプレーン2の点灯を表す．
+// XO-CHIP以外の系統ではプレーン1のみを使うため，画素は0または1になる．
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogicalDisplay {
+    width: usize,    // 横の画素数
+    height: usize,   // 縦の画素数
+    pixels: Vec<u8>, // 画素（左上から行ごとに並べたもの）
+}
+
+impl LogicalDisplay {
+    // 指定された大きさのディスプレイを作成する（大きさは1以上，上限以下）
+    pub fn new(width: usize, height: usize) -> Result<LogicalDisplay, Chip8Error> {
+        if !(1..=MAX_DISPLAY_WIDTH).contains(&width) || !(1..=MAX_DISPLAY_HEIGHT).contains(&height)
+        {
+            return Err(ErrorKind::InvalidDisplaySize(width, height).into());
+        }
+        Ok(LogicalDisplay {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // (x, y)の画素
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    // (x, y)の画素を設定する
+    pub fn set(&mut self, x: usize, y: usize, pixel: u8) {
+        self.pixels[y * self.width + x] = pixel;
+    }
+
+    // 全ての画素（左上から行ごとに並べたもの）
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    // 各行の画素
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks(self.width)
+    }
+
+    // 全ての画素を消す
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+    }
+
+    // 指定されたプレーン（ビットマスク）の画素を(dx, dy)だけずらす
+    //
+    // wrapがfalseの場合，空いた行や列は消灯した画素で埋める．
+    // trueの場合は反対側の端からはみ出した画素で埋める．
+    pub fn scroll(&mut self, dx: isize, dy: isize, planes: u8, wrap: bool) {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let source = self.pixels.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let shifted = if wrap {
+                    source[(sy.rem_euclid(height) * width + sx.rem_euclid(width)) as usize]
+                } else if (0..width).contains(&sx) && (0..height).contains(&sy) {
+                    source[(sy * width + sx) as usize]
+                } else {
+                    0
+                };
+                let pixel = &mut self.pixels[(y * width + x) as usize];
+                *pixel = (*pixel & !planes) | (shifted & planes);
+            }
+        }
+    }
+
+    // 指定されたプレーン（ビットマスク）の画素のみを消す
+    pub fn clear_planes(&mut self, planes: u8) {
+        for pixel in &mut self.pixels {
+            *pixel &= !planes;
+        }
+    }
+}
+
+impl Framebuffer for LogicalDisplay {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn get(&self, x: usize, y: usize) -> u8 {
+        LogicalDisplay::get(self, x, y)
+    }
+
+    fn set(&mut self, x: usize, y: usize, pixel: u8) {
+        LogicalDisplay::set(self, x, y, pixel);
+    }
+}
+
+impl Default for LogicalDisplay {
+    // 標準の系統の起動時の大きさのディスプレイ
+    fn default() -> LogicalDisplay {
+        let (width, height) = Variant::default().display_size();
+        LogicalDisplay {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+}
+
+// 別のスレッドで描画するためのディスプレイの共有
+//
+// CPUを実行するスレッドは各フレームの終了時に画面全体をpublishし，描画するスレッドはreadで
+// 最後にpublishされたフレームを取得する．どちらもロックを取ったまま画面全体をコピーするため，
+// 描画するスレッドがフレームの途中の状態（ティアリング）を見ることはない．
+// ロックはコピーの間だけ保持し，描画に時間がかかってもCPUのスレッドは待たされない．
+#[derive(Debug, Clone, Default)]
+pub struct SharedDisplay {
+    frame: Arc<Mutex<LogicalDisplay>>, // 最後にpublishされたフレーム
+}
+
+impl SharedDisplay {
+    // 指定されたディスプレイの内容で作成する
+    pub fn new(display: &LogicalDisplay) -> SharedDisplay {
+        SharedDisplay {
+            frame: Arc::new(Mutex::new(display.clone())),
+        }
+    }
+
+    // 完成したフレームを共有する
+    pub fn publish(&self, display: &LogicalDisplay) {
+        let mut frame = self.frame.lock().unwrap_or_else(PoisonError::into_inner);
+        frame.clone_from(display);
+    }
+
+    // 最後に共有されたフレーム
+    pub fn read(&self) -> LogicalDisplay {
+        self.frame
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+// ディスプレイが変化した時点
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayChange {
+    pub cycle: u64,    // 変化させた命令が何命令目か（0始まり）
+    pub pixels: usize, // 変化した画素の数
+}
+
+// ディスプレイの変化の記録（変化のなかった命令は記録しない）
+#[derive(Debug, Clone)]
+pub(crate) struct DisplayLog {
+    previous: LogicalDisplay,    // 前回確認したときのディスプレイ
+    changes: Vec<DisplayChange>, // 取得されていない変化
+}
+
+impl DisplayLog {
+    pub(crate) fn new(display: &LogicalDisplay) -> DisplayLog {
+        DisplayLog {
+            previous: display.clone(),
+            changes: Vec::new(),
+        }
+    }
+
+    // 前回から変化していれば記録する
+    pub(crate) fn update(&mut self, display: &LogicalDisplay, cycle: u64) {
+        let size = (display.width(), display.height());
+        let pixels = if size == (self.previous.width(), self.previous.height()) {
+            display
+                .pixels()
+                .iter()
+                .zip(self.previous.pixels())
+                .filter(|(a, b)| a != b)
+                .count()
+        } else {
+            // 大きさが変わった場合は全ての画素が変化したとみなす
+            display.pixels().len()
+        };
+        if pixels > 0 {
+            self.changes.push(DisplayChange { cycle, pixels });
+            self.previous.clone_from(display);
+        }
+    }
+
+    // 記録した変化を取り出す
+    pub(crate) fn take(&mut self) -> Vec<DisplayChange> {
+        std::mem::take(&mut self.changes)
+    }
+}
+
+// 変化を監視するディスプレイの矩形領域
+#[derive(Debug, Clone)]
+pub(crate) struct RegionWatch {
+    x: usize,        // 左上のx座標
+    y: usize,        // 左上のy座標
+    width: usize,    // 幅（ディスプレイからはみ出した部分は確認の際に除く）
+    height: usize,   // 高さ（ディスプレイからはみ出した部分は確認の際に除く）
+    pixels: Vec<u8>, // 前回確認したときの領域内の画素
+    changed: bool,   // 領域内の画素が変化したか（取得するまで保持する）
+}
+
+impl RegionWatch {
+    pub(crate) fn new(
+        display: &LogicalDisplay,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+    ) -> RegionWatch {
+        let mut watch = RegionWatch {
+            x,
+            y,
+            width: w,
+            height: h,
+            pixels: Vec::new(),
+            changed: false,
+        };
+        watch.pixels = watch.capture(display);
+        watch
+    }
+
+    // 領域内の画素を取得する
+    // 解像度の切り替えでディスプレイが小さくなる場合があるため，その時点の大きさに収まる部分のみを取得する
+    fn capture(&self, display: &LogicalDisplay) -> Vec<u8> {
+        let x = self.x.min(display.width());
+        let width = self.width.min(display.width() - x);
+        display
+            .rows()
+            .skip(self.y)
+            .take(self.height)
+            .flat_map(|row| &row[x..x + width])
+            .copied()
+            .collect()
+    }
+
+    // 領域内の画素が前回から変化したかを確認する
+    pub(crate) fn update(&mut self, display: &LogicalDisplay) {
+        let pixels = self.capture(display);
+        if pixels != self.pixels {
+            self.changed = true;
+            self.pixels = pixels;
+        }
+    }
+
+    // 変化したかを取得し，変化していない状態に戻す
+    pub(crate) fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (x, 0)から8x1のスプライト（全ての画素が点灯）を，はみ出した部分を折り返して描画し，点灯した列を返す
+    fn lit_columns_after_drawing_at(display: &mut impl Framebuffer, x: usize) -> Vec<usize> {
+        let sprite = Sprite {
+            rows: &[0xFF],
+            width: 8,
+        };
+        let edges = (EdgeQuirk::Wrap, EdgeQuirk::Wrap);
+        display.draw_sprite(x, 0, sprite, 0x01, edges, DrawMode::Xor);
+        (0..display.width())
+            .filter(|&x| display.get(x, 0) != 0)
+            .collect()
+    }
+
+    #[test]
+    fn drw_wraps_at_the_width_of_each_size() {
+        let mut lores = LogicalDisplay::new(LORES_WIDTH, LORES_HEIGHT).unwrap();
+        assert_eq!(
+            lit_columns_after_drawing_at(&mut lores, 60),
+            [0, 1, 2, 3, 60, 61, 62, 63]
+        );
+        let mut hires = LogicalDisplay::new(HIRES_WIDTH, HIRES_HEIGHT).unwrap();
+        assert_eq!(
+            lit_columns_after_drawing_at(&mut hires, 124),
+            [0, 1, 2, 3, 124, 125, 126, 127]
+        );
+
+        // 開始座標もそれぞれの幅で折り返す（64は低解像度では0，高解像度ではそのまま）
+        let mut lores = LogicalDisplay::new(LORES_WIDTH, LORES_HEIGHT).unwrap();
+        assert_eq!(
+            lit_columns_after_drawing_at(&mut lores, 64),
+            (0..8).collect::<Vec<_>>()
+        );
+        let mut hires = LogicalDisplay::new(HIRES_WIDTH, HIRES_HEIGHT).unwrap();
+        assert_eq!(
+            lit_columns_after_drawing_at(&mut hires, 64),
+            (64..72).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn display_log_counts_a_height_change_as_a_full_change() {
+        let mut log = DisplayLog::new(&LogicalDisplay::new(64, 32).unwrap());
+        // 幅が同じで高さだけが違う，消灯したディスプレイ
+        let display = LogicalDisplay::new(64, 16).unwrap();
+        log.update(&display, 7);
+        assert_eq!(
+            log.take(),
+            [DisplayChange {
+                cycle: 7,
+                pixels: 64 * 16
+            }]
+        );
+        log.update(&display, 8);
+        assert!(log.take().is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use std::error::Error;
+use std::fmt;
+
+// エラーの種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnknownOpcode(u16),               // 未知の命令
+    JumpOutOfRange(u16),              // メモリの範囲外へのジャンプ
+    CycleLimitReached(usize),         // 指定された命令数を実行しても条件を満たさなかった
+    MemoryOutOfRange(usize),          // メモリの範囲外へのアクセス
+    StackOverflow,                    // スタックの段数を超えるサブルーチンの呼び出し
+    StackUnderflow,                   // サブルーチンの外でのRET
+    InvalidDisplaySize(usize, usize), // 対応していないディスプレイの大きさ
+    ProtectedWrite(usize),            // インタプリタの領域への書き込み
+    KeyWaitTimeout(usize),            // 指定されたフレーム数を待ってもキーが入力されなかった
+    InvalidRegister(usize),           // 存在しないレジスタの番号
+    InvalidHexDigit(char),            // 16進数の文字列に含まれる16進数でない文字
+    OddHexLength(usize),              // 16進数の文字列の桁数が奇数
+    InvalidSaveState(String),         // 内容が正しくないセーブデータ
+    UnsupportedSaveStateVersion(u64), // 対応していない（新しい）版のセーブデータ
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownOpcode(opcode) => write!(f, "unknown opcode {:#06X}", opcode),
+            ErrorKind::JumpOutOfRange(addr) => {
+                write!(f, "jump target {:#06X} is out of range", addr)
+            }
+            ErrorKind::CycleLimitReached(cycles) => {
+                write!(f, "condition not met within {} cycles", cycles)
+            }
+            ErrorKind::MemoryOutOfRange(addr) => {
+                write!(f, "memory access at {:#06X} is out of range", addr)
+            }
+            ErrorKind::StackOverflow => write!(f, "stack overflow"),
+            ErrorKind::StackUnderflow => write!(f, "stack underflow"),
+            ErrorKind::InvalidDisplaySize(width, height) => {
+                write!(f, "unsupported display size {}x{}", width, height)
+            }
+            ErrorKind::KeyWaitTimeout(frames) => {
+                write!(f, "no key pressed within {} frames", frames)
+            }
+            ErrorKind::InvalidRegister(x) => write!(f, "invalid register V{:X}", x),
+            ErrorKind::InvalidHexDigit(c) => write!(f, "invalid hex digit {:?}", c),
+            ErrorKind::InvalidSaveState(reason) => write!(f, "invalid save state: {}", reason),
+            ErrorKind::UnsupportedSaveStateVersion(version) => write!(
+                f,
+                "save state version {} is newer than supported ({})",
+                version,
+                crate::SAVE_STATE_VERSION
+            ),
+            ErrorKind::OddHexLength(digits) => {
+                write!(f, "hex string has an odd number of digits ({})", digits)
+            }
+            ErrorKind::ProtectedWrite(addr) => {
+                write!(f, "write to protected interpreter region at {:#06X}", addr)
+            }
+        }
+    }
+}
+
+// エミュレータの実行中に発生するエラー
+//
+// 命令の実行中に発生した場合は，その命令のアドレスとopcodeを保持する．
+// 命令の実行とは関係なく発生した場合（メモリの読み書きの関数など）はどちらもNoneになる．
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chip8Error {
+    pub kind: ErrorKind,     // エラーの種類
+    pub pc: Option<u16>,     // エラーが発生した命令のアドレス
+    pub opcode: Option<u16>, // エラーが発生した命令
+}
+
+impl Chip8Error {
+    // 発生した命令の位置を設定する（設定済みの場合は変更しない）
+    pub(crate) fn at(mut self, pc: u16, opcode: Option<u16>) -> Chip8Error {
+        if self.pc.is_none() {
+            self.pc = Some(pc);
+            self.opcode = opcode;
+        }
+        self
+    }
+}
+
+impl From<ErrorKind> for Chip8Error {
+    fn from(kind: ErrorKind) -> Chip8Error {
+        Chip8Error {
+            kind,
+            pc: None,
+            opcode: None,
+        }
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(pc) = self.pc {
+            write!(f, " at pc {:#06X}", pc)?;
+        }
+        // 未知の命令ではopcodeがメッセージに含まれている
+        match (self.opcode, &self.kind) {
+            (_, ErrorKind::UnknownOpcode(_)) | (None, _) => Ok(()),
+            (Some(opcode), _) => write!(f, " (opcode {:#06X})", opcode),
+        }
+    }
+}
+
+impl Error for Chip8Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_includes_the_location_when_known() {
+        let err = Chip8Error::from(ErrorKind::StackUnderflow);
+        assert_eq!(err.to_string(), "stack underflow");
+        let err = err.at(0x20A, Some(0x00EE));
+        assert_eq!(
+            err.to_string(),
+            "stack underflow at pc 0x020A (opcode 0x00EE)"
+        );
+        // 設定済みの位置は変更しない
+        assert_eq!(err.at(0x300, None).pc, Some(0x20A));
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, HashMap};
+
+use gilrs::{Button, EventType, Gilrs};
+
+use crate::Chip8;
+
+// ゲームパッドのボタンの入力イベントを取得する
+pub trait ButtonSource {
+    // 次のイベント（ボタンと，押されたか）を取得する（イベントがなければNone）
+    fn next_button_event(&mut self) -> Option<(Button, bool)>;
+}
+
+// gilrsで接続されたゲームパッドから入力を取得する
+pub struct GilrsSource {
+    gilrs: Gilrs,
+}
+
+impl GilrsSource {
+    pub fn new() -> Result<GilrsSource, Box<gilrs::Error>> {
+        Ok(GilrsSource {
+            gilrs: Gilrs::new().map_err(Box::new)?,
+        })
+    }
+}
+
+impl ButtonSource for GilrsSource {
+    fn next_button_event(&mut self) -> Option<(Button, bool)> {
+        // ボタン以外のイベント（スティックの操作や接続など）は読み飛ばす
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => return Some((button, true)),
+                EventType::ButtonReleased(button, _) => return Some((button, false)),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+// ゲームパッドのボタンとCHIP-8のキーの対応
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GamepadLayout {
+    keys: HashMap<Button, u8>, // ボタンに対応するキー（0x0〜0xF）
+}
+
+impl GamepadLayout {
+    // 設定ファイルの[gamepad]のようなボタン名とキーの対応から作成する
+    pub fn from_names(names: &BTreeMap<String, u8>) -> Result<GamepadLayout, String> {
+        let mut keys = HashMap::new();
+        for (name, &key) in names {
+            let button = parse_button(name).ok_or(format!("unknown gamepad button: {}", name))?;
+            if key > 0xF {
+                return Err(format!("gamepad key must be 0-F (got {:X})", key));
+            }
+            keys.insert(button, key);
+        }
+        Ok(GamepadLayout { keys })
+    }
+
+    // ボタンに対応するキー
+    pub fn key(&self, button: Button) -> Option<u8> {
+        self.keys.get(&button).copied()
+    }
+
+    // ボタンに対応するキーを設定する
+    pub fn set_key(&mut self, button: Button, key: u8) {
+        self.keys.insert(button, key & 0xF);
+    }
+}
+
+impl Default for GamepadLayout {
+    // 十字キーを2/8/4/6（上下左右），South（Aボタンなど）を5，StartをF，Selectを0とする
+    fn default() -> GamepadLayout {
+        let keys = [
+            (Button::DPadUp, 0x2),
+            (Button::DPadDown, 0x8),
+            (Button::DPadLeft, 0x4),
+            (Button::DPadRight, 0x6),
+            (Button::South, 0x5),
+            (Button::Start, 0xF),
+            (Button::Select, 0x0),
+        ];
+        GamepadLayout {
+            keys: keys.into_iter().collect(),
+        }
+    }
+}
+
+// ゲームパッドの入力をCHIP-8のキーに反映する
+pub struct Gamepad<S: ButtonSource> {
+    source: S,             // 入力を取得する先
+    layout: GamepadLayout, // ボタンとキーの対応
+}
+
+impl<S: ButtonSource> Gamepad<S> {
+    pub fn new(source: S, layout: GamepadLayout) -> Gamepad<S> {
+        Gamepad { source, layout }
+    }
+
+    // 溜まっている入力を全て読み，対応するキーの状態を設定する
+    pub fn poll(&mut self, chip8: &mut Chip8) {
+        while let Some((button, pressed)) = self.source.next_button_event() {
+            if let Some(key) = self.layout.key(button) {
+                chip8.set_key(key, pressed);
+            }
+        }
+    }
+}
+
+// ボタン名を解析する（gilrsのButtonの名前と同じで，大文字小文字を区別しない）
+fn parse_button(name: &str) -> Option<Button> {
+    const BUTTONS: [(&str, Button); 19] = [
+        ("South", Button::South),
+        ("East", Button::East),
+        ("North", Button::North),
+        ("West", Button::West),
+        ("C", Button::C),
+        ("Z", Button::Z),
+        ("LeftTrigger", Button::LeftTrigger),
+        ("LeftTrigger2", Button::LeftTrigger2),
+        ("RightTrigger", Button::RightTrigger),
+        ("RightTrigger2", Button::RightTrigger2),
+        ("Select", Button::Select),
+        ("Start", Button::Start),
+        ("Mode", Button::Mode),
+        ("LeftThumb", Button::LeftThumb),
+        ("RightThumb", Button::RightThumb),
+        ("DPadUp", Button::DPadUp),
+        ("DPadDown", Button::DPadDown),
+        ("DPadLeft", Button::DPadLeft),
+        ("DPadRight", Button::DPadRight),
+    ];
+    BUTTONS
+        .iter()
+        .find(|(button_name, _)| button_name.eq_ignore_ascii_case(name))
+        .map(|&(_, button)| button)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    // 決められた順にイベントを返す入力元
+    struct MockSource {
+        events: VecDeque<(Button, bool)>,
+    }
+
+    impl ButtonSource for MockSource {
+        fn next_button_event(&mut self) -> Option<(Button, bool)> {
+            self.events.pop_front()
+        }
+    }
+
+    // イベントを入力した後，キー入力を待つ命令（FX0A）で読み取ったキー
+    fn key_read_after(events: &[(Button, bool)], layout: GamepadLayout) -> u8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_hex("F00A 1202").unwrap();
+        let source = MockSource {
+            events: events.iter().copied().collect(),
+        };
+        Gamepad::new(source, layout).poll(&mut chip8);
+        chip8.run_frame().unwrap();
+        chip8.registers()[0x0]
+    }
+
+    #[test]
+    fn button_events_set_the_mapped_keys() {
+        let layout = GamepadLayout::default();
+        assert_eq!(
+            key_read_after(&[(Button::South, true)], layout.clone()),
+            0x5
+        );
+        assert_eq!(
+            key_read_after(&[(Button::DPadRight, true)], layout.clone()),
+            0x6
+        );
+        // 対応するキーのないボタンは無視する
+        assert_eq!(
+            key_read_after(&[(Button::North, true), (Button::Start, true)], layout),
+            0xF
+        );
+    }
+
+    #[test]
+    fn layout_is_remapped_from_names() {
+        let names = BTreeMap::from([("north".to_string(), 0xA), ("DPadUp".to_string(), 0x1)]);
+        let layout = GamepadLayout::from_names(&names).unwrap();
+        assert_eq!(layout.key(Button::North), Some(0xA));
+        assert_eq!(layout.key(Button::DPadUp), Some(0x1));
+        assert_eq!(layout.key(Button::South), None);
+        assert_eq!(key_read_after(&[(Button::North, true)], layout), 0xA);
+
+        let unknown = BTreeMap::from([("Turbo".to_string(), 0x1)]);
+        assert!(GamepadLayout::from_names(&unknown).is_err());
+        let out_of_range = BTreeMap::from([("South".to_string(), 0x10)]);
+        assert!(GamepadLayout::from_names(&out_of_range).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::io;
+
+use gif::{Encoder, Frame, Repeat};
+
+use super::Palette;
+use crate::Chip8;
+
+// 記録するフレーム数の既定の上限（60fpsで1分）
+pub const DEFAULT_MAX_FRAMES: usize = 3600;
+
+// 記録した1フレーム
+struct Capture {
+    width: usize,
+    height: usize,
+    packed: Vec<u8>, // 1画素1ビットに詰めたディスプレイの内容
+}
+
+// 実行中のディスプレイの内容を記録し，アニメーションGIFとして保存する
+pub struct GifRecorder {
+    palette: Palette,       // GIFで使う色
+    frame_step: u32,        // 何フレームごとに記録するか（1で60fps）
+    max_frames: usize,      // 記録するフレーム数の上限
+    counter: u32,           // 前回記録してからのフレーム数
+    captures: Vec<Capture>, // 記録したフレーム
+}
+
+impl GifRecorder {
+    pub fn new(palette: Palette) -> GifRecorder {
+        GifRecorder {
+            palette,
+            frame_step: 1,
+            max_frames: DEFAULT_MAX_FRAMES,
+            counter: 0,
+            captures: Vec::new(),
+        }
+    }
+
+    // 何フレームごとに記録するかを設定する（2なら30fps）
+    pub fn set_frame_step(&mut self, frame_step: u32) {
+        self.frame_step = frame_step.max(1);
+    }
+
+    // 記録するフレーム数の上限を設定する（上限に達すると以降のフレームは記録しない）
+    pub fn set_max_frames(&mut self, max_frames: usize) {
+        self.max_frames = max_frames;
+    }
+
+    // 記録したフレーム数
+    pub fn len(&self) -> usize {
+        self.captures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.captures.is_empty()
+    }
+
+    // 1フレームごとに呼び出し，現在のディスプレイの内容を記録する
+    pub fn capture(&mut self, chip8: &Chip8) {
+        let skip = !self.counter.is_multiple_of(self.frame_step);
+        self.counter = self.counter.wrapping_add(1);
+        if skip || self.captures.len() >= self.max_frames {
+            return;
+        }
+
+        let display = chip8.display();
+        self.captures.push(Capture {
+            width: display.width(),
+            height: display.height(),
+            packed: chip8.packed_display(),
+        });
+    }
+
+    // 記録したフレームをscale倍に拡大してGIFとして保存する
+    //
+    // GIFの大きさは最初のフレームで決まり，途中で解像度が変わったフレームはその大きさに拡大・縮小する．
+    // 拡大後の大きさがGIFで表せる範囲（65535画素）を超える場合はエラーとなる．
+    pub fn save_gif(&self, path: &str, scale: u32) -> io::Result<()> {
+        let Some(first) = self.captures.first() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no frames recorded",
+            ));
+        };
+
+        let scale = scale.max(1) as usize;
+        let (width, height) = (first.width * scale, first.height * scale);
+        let too_large = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("GIF size {}x{} exceeds 65535 pixels", width, height),
+            )
+        };
+        let gif_width = u16::try_from(width).map_err(|_| too_large())?;
+        let gif_height = u16::try_from(height).map_err(|_| too_large())?;
+        let palette: Vec<u8> = [self.palette.background, self.palette.foreground]
+            .iter()
+            .flat_map(|color| color[..3].to_vec())
+            .collect();
+
+        // GIFのフレームの表示時間は1/100秒単位
+        let delay = (self.frame_step as f32 * 100.0 / 60.0).round() as u16;
+
+        let file = File::create(path)?;
+        let mut encoder =
+            Encoder::new(file, gif_width, gif_height, &palette).map_err(io::Error::other)?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(io::Error::other)?;
+
+        for capture in &self.captures {
+            // GIFの各画素に対応するフレームの画素を選ぶ（大きさが同じならscale倍の拡大になる）
+            let mut pixels = Vec::with_capacity(width * height);
+            for gy in 0..height {
+                let y = gy * capture.height / height;
+                for gx in 0..width {
+                    let i = y * capture.width + gx * capture.width / width;
+                    pixels.push((capture.packed[i / 8] >> (7 - i % 8)) & 0x01);
+                }
+            }
+
+            let mut frame = Frame::from_indexed_pixels(gif_width, gif_height, pixels, None);
+            frame.delay = delay;
+            encoder.write_frame(&frame).map_err(io::Error::other)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for GifRecorder {
+    fn default() -> GifRecorder {
+        GifRecorder::new(Palette::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // テストごとの一時ファイルのパス
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chip8-rust-{}-{}.gif", name, std::process::id()))
+    }
+
+    // GIFを読み込み，大きさと各フレームの画素を返す
+    fn decode(path: &PathBuf) -> ((u16, u16), Vec<Vec<u8>>) {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(File::open(path).unwrap()).unwrap();
+        let size = (decoder.width(), decoder.height());
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push(frame.buffer.to_vec());
+        }
+        (size, frames)
+    }
+
+    #[test]
+    fn recorded_frames_are_saved_as_gif() {
+        // フォントの"0"を(0, 0)に描画する
+        let mut chip8 = Chip8::new();
+        chip8.load_hex("A000 6000 D005 1206").unwrap();
+        let mut recorder = GifRecorder::default();
+        for _ in 0..3 {
+            chip8.run_frame().unwrap();
+            recorder.capture(&chip8);
+        }
+        assert_eq!(recorder.len(), 3);
+
+        let path = temp_path("recorded");
+        recorder.save_gif(path.to_str().unwrap(), 2).unwrap();
+        let ((width, height), frames) = decode(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((width, height), (128, 64));
+        assert_eq!(frames.len(), 3);
+        // 左上の画素は2x2に拡大されている
+        assert_eq!(frames[0][..3], [1, 1, 1]);
+        assert_eq!(frames[0][128..131], [1, 1, 1]);
+    }
+
+    #[test]
+    fn frames_of_a_different_size_are_rescaled() {
+        let mut lores = Chip8::new();
+        lores.load_hex("A000 6000 D005 1206").unwrap();
+        lores.run_frame().unwrap();
+        let hires = Chip8::with_display_size(128, 64).unwrap();
+        let mut recorder = GifRecorder::default();
+        recorder.capture(&lores);
+        recorder.capture(&hires);
+
+        let path = temp_path("rescaled");
+        recorder.save_gif(path.to_str().unwrap(), 1).unwrap();
+        let ((width, height), frames) = decode(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((width, height), (64, 32));
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|frame| frame.len() == 64 * 32));
+    }
+
+    #[test]
+    fn oversized_gif_is_rejected() {
+        let mut recorder = GifRecorder::default();
+        recorder.capture(&Chip8::new());
+        let path = temp_path("oversized");
+        let err = recorder.save_gif(path.to_str().unwrap(), 2000).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
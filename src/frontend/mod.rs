@@ -0,0 +1,45 @@
+use std::ops::RangeInclusive;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::{Chip8, LogicalDisplay};
+
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod gif;
+pub mod overlay;
+pub mod rgba;
+pub mod screenshot;
+pub mod terminal;
+pub mod watch;
+#[cfg(feature = "window")]
+pub mod window;
+
+pub use self::gif::GifRecorder;
+pub use overlay::OverlayInfo;
+pub use rgba::{Palette, RgbaRenderer};
+pub use screenshot::{save_screenshot, Screenshot};
+pub use terminal::{TerminalFrontend, TerminalStyle};
+pub use watch::RomWatcher;
+#[cfg(feature = "window")]
+pub use window::WindowFrontend;
+
+// 描画倍率の範囲と既定値
+pub const SCALE_RANGE: RangeInclusive<u32> = 1..=20;
+pub const DEFAULT_SCALE: u32 = 1;
+
+// ディスプレイの内容を出力するフロントエンド
+//
+// タイマーは常にコアが60Hzで進め，描画の頻度はフロントエンドが決める．
+// 独自のループで描画する場合は，Chip8::advanceで経過時間の分だけ実行し，
+// 任意の間隔でChip8::take_display_updatedを確認して，変化していればChip8::displayを描画する．
+pub trait Frontend {
+    // ディスプレイの内容を描画する
+    fn render(&mut self, display: &LogicalDisplay);
+
+    // runの開始時に停止用のフラグを受け取る（ウィンドウを閉じたときなどにfalseにする）
+    fn attach_running_flag(&mut self, _running: Arc<AtomicBool>) {}
+
+    // runの各フレームの後に呼び出される（キー入力の反映やホットキーの処理を行う）
+    fn update(&mut self, _chip8: &mut Chip8) {}
+}
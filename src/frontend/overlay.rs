@@ -0,0 +1,135 @@
+use crate::Chip8;
+
+// 文字の大きさ（画素）と，文字や行の間隔を含めた大きさ
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+// 文字の色と，文字の背後の画素を暗くする割合
+const TEXT_COLOR: [u8; 4] = [0xFF, 0xFF, 0x00, 0xFF];
+const SHADE: u16 = 2;
+
+// デバッグ用のオーバーレイに表示する内容
+//
+// 描画の層だけで扱い，マシンの状態やディスプレイの内容には影響しない．
+// フロントエンドはフレームごとにfrom_chip8で作り直し，RgbaRenderer::set_overlay_infoで渡す．
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayInfo {
+    pub pc: u16,             // プログラムカウンタ
+    pub i: u16,              // インデックスレジスタ
+    pub registers: [u8; 4],  // V0〜V3
+    pub fps: f64,            // 1秒あたりのフレーム数
+    pub instruction: String, // pcの命令の逆アセンブル
+}
+
+impl OverlayInfo {
+    pub fn from_chip8(chip8: &Chip8) -> OverlayInfo {
+        let pc = chip8.pc();
+        let mut registers = [0; 4];
+        registers.copy_from_slice(&chip8.registers()[..4]);
+        OverlayInfo {
+            pc,
+            i: chip8.i(),
+            registers,
+            fps: chip8.fps(),
+            instruction: chip8
+                .disassemble_at(pc, 1)
+                .into_iter()
+                .next()
+                .map_or_else(String::new, |(_, _, text)| text),
+        }
+    }
+
+    // 表示する各行の文字列
+    pub fn lines(&self) -> [String; 4] {
+        let [v0, v1, v2, v3] = self.registers;
+        [
+            format!("PC {:04X} I {:04X}", self.pc, self.i),
+            format!("V0-3 {:02X}{:02X}{:02X}{:02X}", v0, v1, v2, v3),
+            format!("FPS {:.0}", self.fps),
+            self.instruction.clone(),
+        ]
+    }
+}
+
+// RGBAのフレームバッファの左上にオーバーレイの文字を重ねる（はみ出した部分は描画しない）
+pub(crate) fn draw(frame: &mut [u8], width: usize, height: usize, info: &OverlayInfo) {
+    for (row, line) in info.lines().iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            let glyph = glyph(c);
+            for gy in 0..CELL_HEIGHT {
+                for gx in 0..CELL_WIDTH {
+                    let x = column * CELL_WIDTH + gx;
+                    let y = row * CELL_HEIGHT + gy;
+                    if x >= width || y >= height {
+                        continue;
+                    }
+                    let pixel = &mut frame[(y * width + x) * 4..][..4];
+                    let bits = glyph.get(gy).copied().unwrap_or(0);
+                    let lit = gx < GLYPH_WIDTH && bits & (0b100 >> gx) != 0;
+                    if lit {
+                        pixel.copy_from_slice(&TEXT_COLOR);
+                    } else {
+                        // 文字を読みやすくするため，背後の画素を暗くする
+                        for channel in &mut pixel[..3] {
+                            *channel = (*channel as u16 / SHADE) as u8;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 3x5画素の文字（各行の下位3ビット，上位のビットが左）
+//
+// 英字は大文字と小文字を区別しない．対応していない文字は空白になる．
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        _ => [0; GLYPH_HEIGHT],
+    }
+}
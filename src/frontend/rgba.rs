@@ -0,0 +1,301 @@
+use serde::Deserialize;
+
+use super::overlay::{self, OverlayInfo};
+use crate::LogicalDisplay;
+
+// 描画に使う色（RGBA）
+//
+// XO-CHIPでは2つのプレーンの組み合わせで4色を表示する．
+// それ以外の系統ではbackgroundとforegroundのみを使う．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Palette {
+    pub background: [u8; 4], // 消灯している画素の色
+    pub foreground: [u8; 4], // 点灯している画素の色（プレーン1のみ）
+    pub plane2: [u8; 4],     // プレーン2のみが点灯している画素の色
+    pub both: [u8; 4],       // 両方のプレーンが点灯している画素の色
+}
+
+impl Palette {
+    // 画素（プレーンのビットの組み合わせ）に対応する色
+    pub fn color(&self, pixel: u8) -> [u8; 4] {
+        match pixel & 0x03 {
+            0 => self.background,
+            1 => self.foreground,
+            2 => self.plane2,
+            _ => self.both,
+        }
+    }
+
+    // 白黒を反転した色（点灯している画素は全て元の背景色，消灯している画素は元の前景色になる）
+    pub fn inverted(&self) -> Palette {
+        Palette {
+            background: self.foreground,
+            foreground: self.background,
+            plane2: self.background,
+            both: self.background,
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            background: [0x00, 0x00, 0x00, 0xFF],
+            foreground: [0xFF, 0xFF, 0xFF, 0xFF],
+            plane2: [0xFF, 0x66, 0x00, 0xFF],
+            both: [0x66, 0x22, 0x00, 0xFF],
+        }
+    }
+}
+
+// ディスプレイの内容をRGBAのフレームバッファに変換する
+//
+// 残光（フェード）を有効にすると，消灯した画素はすぐに背景色にならず，
+// フレームごとに輝度が減衰していく．衝突判定に使う論理的なディスプレイには影響しない．
+// デバッグ用のオーバーレイを有効にすると，マシンの状態を表す文字を左上に重ねて描画する．
+// 論理的なディスプレイの1画素は，フレームバッファではscale×scaleの画素に拡大する．
+pub struct RgbaRenderer {
+    palette: Palette,                  // 描画に使う色
+    fade_rate: Option<f32>,            // 1フレームあたりに失われる輝度の割合（0.0〜1.0）
+    intensity: Vec<f32>,               // 画素ごとの輝度（0.0〜1.0）
+    last_lit: Vec<u8>,                 // 画素ごとに最後に点灯していたときのプレーンの組み合わせ
+    invert: bool,                      // 白黒を反転して描画するか
+    scale: usize,                      // 描画倍率
+    overlay: bool,                     // デバッグ用のオーバーレイを描画するか
+    overlay_info: Option<OverlayInfo>, // オーバーレイに表示する内容
+}
+
+impl RgbaRenderer {
+    pub fn new(palette: Palette) -> RgbaRenderer {
+        RgbaRenderer {
+            palette,
+            fade_rate: None,
+            intensity: Vec::new(),
+            last_lit: Vec::new(),
+            invert: false,
+            scale: 1,
+            overlay: false,
+            overlay_info: None,
+        }
+    }
+
+    // 白黒を反転して描画するかを設定する（ディスプレイの内容や衝突判定には影響しない）
+    pub fn set_invert(&mut self, invert: bool) {
+        self.invert = invert;
+    }
+
+    pub fn invert(&self) -> bool {
+        self.invert
+    }
+
+    // 描画倍率を設定する（0は1として扱う．既定値は1）
+    pub fn set_scale(&mut self, scale: u32) {
+        self.scale = scale.max(1) as usize;
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale as u32
+    }
+
+    // ディスプレイを描画したフレームバッファの大きさ（横，縦の画素数）
+    pub fn frame_size(&self, display: &LogicalDisplay) -> (usize, usize) {
+        (display.width() * self.scale, display.height() * self.scale)
+    }
+
+    // デバッグ用のオーバーレイを描画するかを設定する（既定値は描画しない）
+    pub fn set_overlay(&mut self, overlay: bool) {
+        self.overlay = overlay;
+    }
+
+    pub fn overlay(&self) -> bool {
+        self.overlay
+    }
+
+    // オーバーレイの表示を切り替える（ウィンドウを持つフロントエンドはこれをホットキーに割り当てる）
+    pub fn toggle_overlay(&mut self) {
+        self.overlay = !self.overlay;
+    }
+
+    // オーバーレイに表示する内容を設定する（次のrenderから反映する）
+    pub fn set_overlay_info(&mut self, info: OverlayInfo) {
+        self.overlay_info = Some(info);
+    }
+
+    // 残光の減衰率を設定する（Noneで無効）
+    pub fn set_fade_rate(&mut self, fade_rate: Option<f32>) {
+        self.fade_rate = fade_rate.map(|rate| rate.clamp(0.0, 1.0));
+    }
+
+    // 1フレーム分のRGBAのフレームバッファを生成する
+    pub fn render(&mut self, display: &LogicalDisplay) -> Vec<u8> {
+        let pixels = display.pixels();
+        let mut colors = Vec::with_capacity(pixels.len());
+
+        // ディスプレイの大きさが変わった場合は輝度を初期化する
+        if self.intensity.len() != pixels.len() {
+            self.intensity = vec![0.0; pixels.len()];
+            self.last_lit = vec![0; pixels.len()];
+        }
+
+        let palette = if self.invert {
+            self.palette.inverted()
+        } else {
+            self.palette
+        };
+        for (i, &pixel) in pixels.iter().enumerate() {
+            // 点灯している画素は最大輝度，消灯した画素は減衰させる
+            let intensity = &mut self.intensity[i];
+            *intensity = match (pixel, self.fade_rate) {
+                (1..=3, _) => 1.0,
+                (_, Some(rate)) => *intensity * (1.0 - rate),
+                (_, None) => 0.0,
+            };
+            if *intensity < 1.0 / 255.0 {
+                *intensity = 0.0;
+            }
+
+            // 消灯した画素は最後に点灯していたときの色から減衰させる
+            if pixel != 0 {
+                self.last_lit[i] = pixel;
+            }
+            colors.push(blend(
+                palette.background,
+                palette.color(self.last_lit[i]),
+                *intensity,
+            ));
+        }
+
+        // 各画素を縦横scale倍に拡大する
+        let (width, height) = self.frame_size(display);
+        let mut frame = Vec::with_capacity(width * height * 4);
+        for row in colors.chunks(display.width()) {
+            let line: Vec<u8> = row
+                .iter()
+                .flat_map(|color| color.repeat(self.scale))
+                .collect();
+            for _ in 0..self.scale {
+                frame.extend_from_slice(&line);
+            }
+        }
+
+        if let (true, Some(info)) = (self.overlay, &self.overlay_info) {
+            overlay::draw(&mut frame, width, height, info);
+        }
+
+        frame
+    }
+}
+
+impl Default for RgbaRenderer {
+    fn default() -> RgbaRenderer {
+        RgbaRenderer::new(Palette::default())
+    }
+}
+
+// 輝度に応じて背景色と前景色を混ぜる
+fn blend(background: [u8; 4], foreground: [u8; 4], intensity: f32) -> [u8; 4] {
+    let mut color = [0; 4];
+    for (c, (&b, &f)) in color.iter_mut().zip(background.iter().zip(&foreground)) {
+        *c = (b as f32 + (f as f32 - b as f32) * intensity).round() as u8;
+    }
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleared_pixel_fades_over_several_frames() {
+        let mut renderer = RgbaRenderer::default();
+        renderer.set_fade_rate(Some(0.25));
+        let mut display = LogicalDisplay::default();
+        display.set(0, 0, 1);
+        renderer.render(&display);
+
+        // 消灯した後も数フレームは輝度が残り，フレームごとに暗くなる
+        display.set(0, 0, 0);
+        let mut last = u8::MAX;
+        for _ in 0..4 {
+            let red = renderer.render(&display)[0];
+            assert!(red > 0 && red < last, "{} after {}", red, last);
+            last = red;
+        }
+        // 論理的なディスプレイは消灯したまま
+        assert_eq!(display.get(0, 0), 0);
+    }
+
+    #[test]
+    fn cleared_pixel_is_blank_without_fade() {
+        let mut renderer = RgbaRenderer::default();
+        let mut display = LogicalDisplay::default();
+        display.set(0, 0, 1);
+        renderer.render(&display);
+        display.set(0, 0, 0);
+        assert_eq!(renderer.render(&display)[..4], [0x00, 0x00, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn plane_two_pixels_use_the_plane2_color() {
+        // XO-CHIPでプレーン2のみに描画した画素はplane2の色になる
+        let mut chip8 = crate::Chip8::new();
+        chip8.set_variant(crate::Variant::XoChip);
+        chip8.load_hex("F201 A000 6000 D005").unwrap();
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        let palette = Palette::default();
+        let frame = RgbaRenderer::new(palette).render(chip8.display());
+        assert_eq!(frame[..4], palette.plane2);
+        assert_eq!(frame[4 * 4..4 * 5], palette.background);
+        assert_eq!(palette.color(0x02), palette.plane2);
+    }
+
+    #[test]
+    fn inverted_rendering_swaps_the_colors() {
+        let palette = Palette::default();
+        let mut renderer = RgbaRenderer::new(palette);
+        renderer.set_invert(true);
+        let mut display = LogicalDisplay::default();
+        display.set(0, 0, 1);
+        let frame = renderer.render(&display);
+        assert_eq!(frame[..4], palette.background);
+        assert_eq!(frame[4..8], palette.foreground);
+        // 論理的なディスプレイは変わらない
+        assert_eq!((display.get(0, 0), display.get(1, 0)), (1, 0));
+    }
+
+    #[test]
+    fn each_logical_pixel_is_upsampled_to_a_scale_square() {
+        let palette = Palette::default();
+        let mut renderer = RgbaRenderer::new(palette);
+        renderer.set_scale(3);
+        let mut display = LogicalDisplay::default();
+        display.set(1, 0, 1);
+        assert_eq!(renderer.frame_size(&display), (64 * 3, 32 * 3));
+
+        let frame = renderer.render(&display);
+        assert_eq!(frame.len(), 64 * 3 * 32 * 3 * 4);
+        let color = |x: usize, y: usize| {
+            let i = (y * 64 * 3 + x) * 4;
+            [frame[i], frame[i + 1], frame[i + 2], frame[i + 3]]
+        };
+        for y in 0..6 {
+            for x in 0..9 {
+                let lit = (3..6).contains(&x) && y < 3;
+                let expected = if lit {
+                    palette.foreground
+                } else {
+                    palette.background
+                };
+                assert_eq!(color(x, y), expected, "({}, {})", x, y);
+            }
+        }
+
+        // 高解像度のディスプレイは同じ倍率で2倍の大きさになる
+        let hires = LogicalDisplay::new(128, 64).unwrap();
+        assert_eq!(renderer.frame_size(&hires), (128 * 3, 64 * 3));
+        assert_eq!(renderer.render(&hires).len(), 128 * 3 * 64 * 3 * 4);
+    }
+}
@@ -0,0 +1,166 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use png::{ColorType, Encoder};
+
+use super::{Palette, DEFAULT_SCALE};
+use crate::LogicalDisplay;
+
+// スクリーンショットの保存先と描画の設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    pub dir: PathBuf,     // 保存するディレクトリ
+    pub palette: Palette, // 描画に使う色
+    pub scale: u32,       // 描画倍率
+}
+
+impl Screenshot {
+    pub fn new(dir: impl Into<PathBuf>, palette: Palette, scale: u32) -> Screenshot {
+        Screenshot {
+            dir: dir.into(),
+            palette,
+            scale,
+        }
+    }
+
+    // 現在のディスプレイの内容を保存し，保存したファイルのパスを返す
+    pub fn save(&self, display: &LogicalDisplay) -> io::Result<PathBuf> {
+        save_screenshot(display, &self.palette, self.scale, &self.dir)
+    }
+}
+
+impl Default for Screenshot {
+    fn default() -> Screenshot {
+        Screenshot::new(".", Palette::default(), DEFAULT_SCALE)
+    }
+}
+
+// ディスプレイの内容を時刻から名前を付けたPNGファイルとしてdirに保存し，そのパスを返す
+// 同じ名前のファイルがある場合は末尾に"-1"などの番号を付ける
+pub fn save_screenshot(
+    display: &LogicalDisplay,
+    palette: &Palette,
+    scale: u32,
+    dir: &Path,
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let mut path = screenshot_path(dir, SystemTime::now());
+    let stem = path.with_extension("");
+    let mut n = 1;
+    while path.exists() {
+        path = PathBuf::from(format!("{}-{}.png", stem.display(), n));
+        n += 1;
+    }
+    save_png(display, palette, scale, &path)?;
+    Ok(path)
+}
+
+// スクリーンショットのパス（"chip8-YYYYMMDD-HHMMSS.png"の形式で，時刻はUTC）
+pub fn screenshot_path(dir: &Path, time: SystemTime) -> PathBuf {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs_of_day = secs % 86_400;
+    dir.join(format!(
+        "chip8-{:04}{:02}{:02}-{:02}{:02}{:02}.png",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    ))
+}
+
+// ディスプレイの内容をscale倍に拡大してPNGとして保存する
+pub fn save_png(
+    display: &LogicalDisplay,
+    palette: &Palette,
+    scale: u32,
+    path: &Path,
+) -> io::Result<()> {
+    let scale = scale.max(1) as usize;
+    let (width, height) = (display.width() * scale, display.height() * scale);
+
+    let mut data = Vec::with_capacity(width * height * 4);
+    for row in display.rows() {
+        let mut line = Vec::with_capacity(width * 4);
+        for &pixel in row {
+            let color = palette.color(pixel);
+            for _ in 0..scale {
+                line.extend_from_slice(&color);
+            }
+        }
+        for _ in 0..scale {
+            data.extend_from_slice(&line);
+        }
+    }
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(ColorType::Rgba);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&data).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+// 1970-01-01からの日数をグレゴリオ暦の年月日に変換する
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn screenshot_path_uses_the_utc_timestamp() {
+        // 2023-11-14 22:13:20 UTC
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            screenshot_path(Path::new("shots"), time),
+            Path::new("shots").join("chip8-20231114-221320.png")
+        );
+        assert_eq!(
+            screenshot_path(Path::new("."), UNIX_EPOCH),
+            Path::new(".").join("chip8-19700101-000000.png")
+        );
+    }
+
+    #[test]
+    fn screenshot_is_written_as_png() {
+        let dir = std::env::temp_dir().join(format!("chip8-rust-shot-{}", std::process::id()));
+        let mut display = LogicalDisplay::default();
+        display.set(0, 0, 1);
+        let screenshot = Screenshot::new(&dir, Palette::default(), 2);
+        let first = screenshot.save(&display).unwrap();
+        let second = screenshot.save(&display).unwrap();
+
+        let decoder = png::Decoder::new(io::BufReader::new(File::open(&first).unwrap()));
+        let info = decoder.read_info().unwrap().info().clone();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_ne!(first, second);
+        let name = first.file_name().unwrap().to_str().unwrap();
+        assert!(
+            name.starts_with("chip8-") && name.ends_with(".png"),
+            "{}",
+            name
+        );
+        assert_eq!((info.width, info.height), (128, 64));
+    }
+}
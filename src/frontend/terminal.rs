@@ -0,0 +1,404 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+
+use super::{Frontend, OverlayInfo, Palette, Screenshot, DEFAULT_SCALE};
+use crate::{Chip8, LogicalDisplay};
+
+// スクリーンショットを保存するキー
+pub const SCREENSHOT_KEY: KeyCode = KeyCode::F(12);
+
+// デバッグ用のオーバーレイの表示を切り替えるキー
+pub const OVERLAY_KEY: KeyCode = KeyCode::F(1);
+
+// 押されたキーを押されていることにするフレーム数
+// 端末はキーを離したことを通知しないため，一定のフレーム数で離したものとする（押し続ければキーリピートで延びる）
+const KEY_HOLD_FRAMES: u8 = 6;
+
+// 端末への描画方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TerminalStyle {
+    #[default]
+    Block, // 1画素を横2文字の"█"で描画する（64x32で128x32文字）
+    HalfBlock, // 縦2画素を1文字の"▀"にまとめ，上下の画素を前景色と背景色で描画する（64x32で64x16文字）
+}
+
+// 端末にディスプレイを文字で描画するフロントエンド
+//
+// キー入力を有効にすると，端末をrawモードにしてフレームごとにキーを読み取る．
+// Chip8::keymapのキーはCHIP-8のキーとして押し，F12でスクリーンショットを保存し，Ctrl-Cでrunを終了する．
+// F1でデバッグ用のオーバーレイ（pc，I，V0〜V3，FPS，次の命令）をフレームの左上に重ねて表示する．
+pub struct TerminalFrontend {
+    scale: u32,                        // 描画倍率（1画素を横scale倍，縦scale倍で描画する）
+    style: TerminalStyle,              // 描画方法
+    palette: Palette,                  // HalfBlockで使う色
+    invert: bool,                      // 白黒を反転して描画するか
+    cleared: bool,                     // 端末を消去済みか
+    input: bool,                       // 端末からキー入力を読み取るか
+    raw_mode: bool,                    // 端末をrawモードにしたか
+    held: [u8; 16],                    // キーごとの押されていることにする残りのフレーム数
+    screenshot: Screenshot,            // スクリーンショットの保存先と描画の設定
+    status: Option<String>,            // フレームの下に表示するメッセージ
+    overlay: bool,                     // デバッグ用のオーバーレイを表示するか
+    overlay_info: Option<OverlayInfo>, // オーバーレイに表示する内容
+    lines: usize,                      // 前回描画したフレームの行数
+    running: Option<Arc<AtomicBool>>,  // runを続けるかを表すフラグ
+}
+
+impl TerminalFrontend {
+    pub fn new(scale: u32) -> TerminalFrontend {
+        TerminalFrontend {
+            scale,
+            style: TerminalStyle::default(),
+            palette: Palette::default(),
+            invert: false,
+            cleared: false,
+            input: false,
+            raw_mode: false,
+            held: [0; 16],
+            screenshot: Screenshot::default(),
+            status: None,
+            overlay: false,
+            overlay_info: None,
+            lines: 0,
+            running: None,
+        }
+    }
+
+    // 端末からキー入力を読み取るかを設定する（標準入力が端末の場合のみ有効にする）
+    pub fn set_input(&mut self, input: bool) {
+        self.input = input;
+    }
+
+    // スクリーンショットの保存先と描画の設定
+    pub fn set_screenshot(&mut self, screenshot: Screenshot) {
+        self.screenshot = screenshot;
+    }
+
+    // フレームの下に表示しているメッセージ
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    // デバッグ用のオーバーレイを表示するかを設定する（既定値は表示しない）
+    pub fn set_overlay(&mut self, overlay: bool) {
+        self.overlay = overlay;
+    }
+
+    pub fn overlay(&self) -> bool {
+        self.overlay
+    }
+
+    // オーバーレイに表示する内容を設定する（updateがフレームごとに設定する）
+    pub fn set_overlay_info(&mut self, info: OverlayInfo) {
+        self.overlay_info = Some(info);
+    }
+
+    // フレームの左上にオーバーレイを上書きする文字列（表示しない場合は空）
+    pub fn overlay_text(&self) -> String {
+        let (true, Some(info)) = (self.overlay, &self.overlay_info) else {
+            return String::new();
+        };
+        info.lines()
+            .iter()
+            .enumerate()
+            .map(|(row, line)| format!("\x1b[{};1H\x1b[33;40m{}\x1b[0m", row + 1, line))
+            .collect()
+    }
+
+    // 1つのキー入力を処理する
+    pub fn handle_key(&mut self, chip8: &mut Chip8, key: KeyEvent) {
+        if key.kind == KeyEventKind::Release {
+            return;
+        }
+        match key.code {
+            SCREENSHOT_KEY => {
+                let status = match self.screenshot.save(chip8.display()) {
+                    Ok(path) => format!("saved {}", path.display()),
+                    Err(e) => format!("failed to save screenshot: {}", e),
+                };
+                log::info!("{}", status);
+                self.status = Some(status);
+            }
+            OVERLAY_KEY => self.overlay = !self.overlay,
+            // rawモードではCtrl-CでSIGINTが送られないため，ここで終了する
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                if let Some(running) = &self.running {
+                    running.store(false, Ordering::Relaxed);
+                }
+            }
+            KeyCode::Char(c) => {
+                let c = c.to_ascii_lowercase();
+                if let Some(&(_, key)) = chip8.keymap().iter().find(|&&(k, _)| k == c) {
+                    self.held[key as usize] = KEY_HOLD_FRAMES;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 端末から溜まっているキー入力を全て読み取る
+    fn poll_keys(&mut self, chip8: &mut Chip8) {
+        if !self.raw_mode {
+            if let Err(e) = terminal::enable_raw_mode() {
+                log::warn!("failed to read keys from the terminal: {}", e);
+                self.input = false;
+                return;
+            }
+            self.raw_mode = true;
+        }
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            if let Ok(Event::Key(key)) = event::read() {
+                self.handle_key(chip8, key);
+            }
+        }
+    }
+
+    // フレームの下の行にメッセージを表示する
+    fn write_status(&self) {
+        let status = self.status.as_deref().unwrap_or_default();
+        write_stdout(&format!("\x1b[{};1H{}\x1b[K", self.lines + 1, status));
+    }
+
+    // 描画方法を設定する
+    pub fn set_style(&mut self, style: TerminalStyle) {
+        self.style = style;
+    }
+
+    // HalfBlockで使う色を設定する
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    // 白黒を反転して描画するかを設定する（ディスプレイの内容には影響しない）
+    pub fn set_invert(&mut self, invert: bool) {
+        self.invert = invert;
+    }
+
+    pub fn invert(&self) -> bool {
+        self.invert
+    }
+
+    // 1フレーム分の文字列を生成する（カーソル移動などの制御文字は含まない）
+    pub fn frame(&self, display: &LogicalDisplay) -> String {
+        match self.style {
+            TerminalStyle::Block => self.block_frame(display),
+            TerminalStyle::HalfBlock => self.half_block_frame(display),
+        }
+    }
+
+    fn block_frame(&self, display: &LogicalDisplay) -> String {
+        let mut out = String::new();
+
+        // 端末の文字は縦長なので，1画素を横2文字で描画する
+        let width = 2 * self.scale as usize;
+        for row in display.rows() {
+            let mut line = String::new();
+            for &pixel in row {
+                let c = if (pixel != 0) != self.invert {
+                    "█"
+                } else {
+                    " "
+                };
+                line.push_str(&c.repeat(width));
+            }
+            line.push('\n');
+            out.push_str(&line.repeat(self.scale as usize));
+        }
+
+        out
+    }
+
+    fn half_block_frame(&self, display: &LogicalDisplay) -> String {
+        let mut out = String::new();
+
+        // 縦方向に拡大した行を2行ずつ1文字の行にまとめる
+        let scale = self.scale as usize;
+        let rows: Vec<_> = display
+            .rows()
+            .flat_map(|row| std::iter::repeat_n(row, scale))
+            .collect();
+        for pair in rows.chunks(2) {
+            for (x, &top) in pair[0].iter().enumerate() {
+                let bottom = pair.get(1).map_or(0, |row| row[x]);
+                out.push_str(&self.half_block_cell(top, bottom).repeat(scale));
+            }
+            out.push_str("\x1b[0m\n");
+        }
+
+        out
+    }
+
+    // 上下2画素を1文字で表す
+    pub fn half_block_cell(&self, top: u8, bottom: u8) -> String {
+        let [tr, tg, tb, _] = self.color(top);
+        let [br, bg, bb, _] = self.color(bottom);
+        format!(
+            "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀",
+            tr, tg, tb, br, bg, bb
+        )
+    }
+
+    fn color(&self, pixel: u8) -> [u8; 4] {
+        if self.invert {
+            self.palette.inverted().color(pixel)
+        } else {
+            self.palette.color(pixel)
+        }
+    }
+}
+
+impl Default for TerminalFrontend {
+    fn default() -> TerminalFrontend {
+        TerminalFrontend::new(DEFAULT_SCALE)
+    }
+}
+
+impl Frontend for TerminalFrontend {
+    fn render(&mut self, display: &LogicalDisplay) {
+        let mut out = String::new();
+
+        // 初回のみ端末を消去し，以降はカーソルを左上に戻して上書きする
+        if !self.cleared {
+            out.push_str("\x1b[2J");
+            self.cleared = true;
+        }
+        out.push_str("\x1b[H");
+        let frame = self.frame(display);
+        self.lines = frame.lines().count();
+        // rawモードでは改行で行頭に戻らない
+        if self.raw_mode {
+            out.push_str(&frame.replace('\n', "\r\n"));
+        } else {
+            out.push_str(&frame);
+        }
+        out.push_str(&self.overlay_text());
+        write_stdout(&out);
+    }
+
+    fn attach_running_flag(&mut self, running: Arc<AtomicBool>) {
+        self.running = Some(running);
+    }
+
+    fn update(&mut self, chip8: &mut Chip8) {
+        let (status, overlay) = (self.status.clone(), self.overlay);
+        if self.input {
+            self.poll_keys(chip8);
+        }
+        if self.status != status {
+            self.write_status();
+        }
+
+        // オーバーレイの内容は画面が変化しなくても変わるため，フレームごとに上書きする
+        if self.overlay {
+            self.set_overlay_info(OverlayInfo::from_chip8(chip8));
+            write_stdout(&self.overlay_text());
+        } else if overlay {
+            // 消したオーバーレイの下のフレームを描画し直す
+            self.render(chip8.display());
+        }
+
+        for (key, held) in self.held.iter_mut().enumerate() {
+            chip8.set_key(key as u8, *held > 0);
+            *held = held.saturating_sub(1);
+        }
+    }
+}
+
+// 標準出力に書き出す（端末への描画の失敗は無視する）
+fn write_stdout(out: &str) {
+    let mut stdout = io::stdout().lock();
+    let _ = stdout.write_all(out.as_bytes());
+    let _ = stdout.flush();
+}
+
+impl Drop for TerminalFrontend {
+    fn drop(&mut self) {
+        if self.raw_mode {
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 上の画素が点灯，下の画素が消灯しているセル（前景色が白，背景色が黒）
+    const TOP_ONLY: &str = "\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m▀";
+
+    #[test]
+    fn half_block_packs_two_rows_into_one_cell() {
+        let mut frontend = TerminalFrontend::new(1);
+        frontend.set_style(TerminalStyle::HalfBlock);
+        let mut display = LogicalDisplay::default();
+        display.set(0, 0, 1);
+
+        let frame = frontend.frame(&display);
+        let lines: Vec<&str> = frame.lines().collect();
+        assert_eq!(lines.len(), 16);
+        assert!(lines[0].starts_with(TOP_ONLY));
+        assert_eq!(frontend.half_block_cell(1, 0), TOP_ONLY);
+        assert_eq!(
+            frontend.half_block_cell(0, 1),
+            "\x1b[38;2;0;0;0m\x1b[48;2;255;255;255m▀"
+        );
+    }
+
+    #[test]
+    fn screenshot_key_saves_the_current_frame() {
+        let dir = std::env::temp_dir().join(format!("chip8-rust-hotkey-{}", std::process::id()));
+        let mut frontend = TerminalFrontend::new(1);
+        frontend.set_screenshot(Screenshot::new(&dir, Palette::default(), 2));
+        let mut chip8 = Chip8::new();
+        frontend.handle_key(&mut chip8, KeyEvent::from(SCREENSHOT_KEY));
+
+        let status = frontend.status().unwrap().to_string();
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(status.starts_with("saved "), "{}", status);
+        assert!(status.ends_with(".png"), "{}", status);
+    }
+
+    #[test]
+    fn keymap_keys_are_held_for_a_few_frames() {
+        // キー入力を待ち，押されたキーをV0に読み込む
+        let mut chip8 = Chip8::new();
+        chip8.load_hex("F00A 1202").unwrap();
+        let mut frontend = TerminalFrontend::new(1);
+        frontend.handle_key(&mut chip8, KeyEvent::from(KeyCode::Char('W')));
+        frontend.update(&mut chip8);
+        chip8.run_frame().unwrap();
+        assert_eq!(chip8.registers()[0x0], 0x5);
+
+        for _ in 0..KEY_HOLD_FRAMES {
+            frontend.update(&mut chip8);
+        }
+        assert_eq!(frontend.held, [0; 16]);
+    }
+
+    #[test]
+    fn overlay_key_toggles_the_overlay() {
+        let mut chip8 = Chip8::new();
+        chip8.load_hex("6042").unwrap();
+        let mut frontend = TerminalFrontend::new(1);
+        frontend.set_overlay_info(OverlayInfo::from_chip8(&chip8));
+        assert_eq!(frontend.overlay_text(), "");
+
+        frontend.handle_key(&mut chip8, KeyEvent::from(OVERLAY_KEY));
+        assert!(frontend.overlay());
+        let text = frontend.overlay_text();
+        assert!(text.starts_with("\x1b[1;1H"), "{:?}", text);
+        assert!(text.contains("PC 0200 I 0000"), "{:?}", text);
+        assert!(text.contains("LD V0, 0x42"), "{:?}", text);
+
+        frontend.handle_key(&mut chip8, KeyEvent::from(OVERLAY_KEY));
+        assert!(!frontend.overlay());
+        assert_eq!(frontend.overlay_text(), "");
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+// 変更を検出してから再読み込みするまでに待つ時間の既定値
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(250);
+
+// ROMファイルの変更を監視する（開発中のROMの再読み込み用）
+//
+// notifyでファイルのあるディレクトリを監視し，届いたイベントをpollのたびに確認する．
+// エディタが別のファイルに書いてから置き換える場合も検出できるように，ファイル自体ではなくディレクトリを監視する．
+// 保存が続けて行われた場合に途中の内容を読み込まないように，最後の変更からdebounceの時間だけ変更がなければ変更されたとみなす．
+pub struct RomWatcher {
+    path: PathBuf,                           // 監視するファイルのパス
+    debounce: Duration,                      // 最後の変更から待つ時間
+    events: Receiver<notify::Result<Event>>, // notifyから届くイベント
+    pending: Option<Instant>,                // 再読み込みを待っている最後の変更の時刻
+    _watcher: RecommendedWatcher,            // 監視を続けるために保持する
+}
+
+impl RomWatcher {
+    pub fn new(path: impl Into<PathBuf>, debounce: Duration) -> io::Result<RomWatcher> {
+        let path = path.into();
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx).map_err(io::Error::other)?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+        Ok(RomWatcher {
+            path,
+            debounce,
+            events,
+            pending: None,
+            _watcher: watcher,
+        })
+    }
+
+    // ファイルが変更されたことを通知する（連続した通知は最後の1つから待ち直す）
+    pub fn notify_changed(&mut self, now: Instant) {
+        self.pending = Some(now);
+    }
+
+    // notifyのイベントを処理する（監視するファイルの作成・変更・名前の変更のみを変更として扱う）
+    pub fn handle_event(&mut self, event: &Event, now: Instant) {
+        let changed = matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any
+        );
+        if changed && event.paths.iter().any(|path| self.is_watched(path)) {
+            self.notify_changed(now);
+        }
+    }
+
+    // 届いたイベントを処理し，再読み込みすべきであれば新しい内容を返す
+    //
+    // 保存の途中などでファイルが一時的に存在しない場合は，次の確認を待つ．
+    pub fn poll(&mut self, now: Instant) -> Option<io::Result<Vec<u8>>> {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Ok(event) => self.handle_event(&event, now),
+                Err(e) => log::warn!("failed to watch {}: {}", self.path.display(), e),
+            }
+        }
+
+        let changed = self.pending?;
+        if now.saturating_duration_since(changed) < self.debounce || !self.path.exists() {
+            return None;
+        }
+        self.pending = None;
+        Some(fs::read(&self.path))
+    }
+
+    // イベントのパスが監視するファイルを指しているか
+    //
+    // notifyは絶対パスを返すため，ファイル名が同じであれば同じファイルとみなす（監視するのは1つのディレクトリのみ）．
+    fn is_watched(&self, path: &Path) -> bool {
+        path.file_name().is_some() && path.file_name() == self.path.file_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+    use notify::event::{DataChange, ModifyKind};
+
+    // 一時ディレクトリにROMファイルを作成し，そのパスを返す
+    fn rom_file(name: &str, rom: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chip8-rust-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rom.ch8");
+        fs::write(&path, rom).unwrap();
+        path
+    }
+
+    fn modified(path: &Path) -> Event {
+        Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+            .add_path(path.to_path_buf())
+    }
+
+    #[test]
+    fn change_event_reloads_and_resets_after_the_debounce() {
+        let path = rom_file("watch-reload", &[0x60, 0x11, 0x12, 0x02]);
+        let mut chip8 = Chip8::new();
+        chip8.load(&fs::read(&path).unwrap());
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.registers()[0], 0x11);
+
+        // 実際のイベントが混ざらないように，監視を始める前に書き換え，変更のイベントは模擬する
+        fs::write(&path, [0x61, 0x22, 0x12, 0x02]).unwrap();
+        let mut watcher = RomWatcher::new(&path, DEFAULT_DEBOUNCE).unwrap();
+        let start = Instant::now();
+        watcher.handle_event(&modified(&path), start);
+
+        // 続けて保存されると，最後の変更から待ち直す
+        let later = start + DEFAULT_DEBOUNCE / 2;
+        watcher.handle_event(&modified(&path), later);
+        assert!(watcher.poll(start + DEFAULT_DEBOUNCE).is_none());
+
+        let rom = watcher.poll(later + DEFAULT_DEBOUNCE).unwrap().unwrap();
+        assert_eq!(rom, [0x61, 0x22, 0x12, 0x02]);
+        chip8.load_and_reset(&rom);
+        assert_eq!(chip8.pc(), 0x200);
+        assert_eq!(chip8.registers()[0], 0x00);
+        chip8.step().unwrap();
+        assert_eq!(chip8.registers()[1], 0x22);
+
+        // 再読み込みした後は，次の変更まで何も返さない
+        assert!(watcher.poll(later + DEFAULT_DEBOUNCE * 2).is_none());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn events_for_other_files_are_ignored() {
+        let path = rom_file("watch-other", &[0x12, 0x00]);
+        let mut watcher = RomWatcher::new(&path, DEFAULT_DEBOUNCE).unwrap();
+        let start = Instant::now();
+        watcher.handle_event(&modified(&path.with_file_name("other.ch8")), start);
+        let access =
+            Event::new(EventKind::Access(notify::event::AccessKind::Any)).add_path(path.clone());
+        watcher.handle_event(&access, start);
+        assert!(watcher.poll(start + DEFAULT_DEBOUNCE).is_none());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
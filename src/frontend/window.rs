@@ -0,0 +1,165 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+use super::{Frontend, OverlayInfo, Palette, RgbaRenderer};
+use crate::{Chip8, LogicalDisplay, LORES_HEIGHT, LORES_WIDTH};
+
+// ウィンドウのタイトル
+const TITLE: &str = "chip8-rust";
+
+// デバッグ用のオーバーレイの表示を切り替えるキー
+const OVERLAY_KEY: Key = Key::F1;
+
+// キーボードの数字キー（0〜9）
+const DIGIT_KEYS: [Key; 10] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+// キーボードの英字キー（A〜Z）
+const LETTER_KEYS: [Key; 26] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+
+// ウィンドウにディスプレイを描画するフロントエンド（windowフィーチャーで有効）
+//
+// 論理的なディスプレイの1画素をscale×scaleの画素に拡大して描画する．
+// キー入力はChip8::keymapに従ってフレームごとに反映し，ウィンドウを閉じるとrunを終了する．
+// F1でデバッグ用のオーバーレイの表示を切り替える．
+pub struct WindowFrontend {
+    window: Window,                   // 描画先のウィンドウ
+    renderer: RgbaRenderer,           // ディスプレイをRGBAに変換する
+    buffer: Vec<u32>,                 // ウィンドウに表示している画素（0RGB）
+    size: (usize, usize),             // bufferの大きさ（横，縦の画素数）
+    running: Option<Arc<AtomicBool>>, // runを続けるかを表すフラグ
+}
+
+impl WindowFrontend {
+    // 低解像度のディスプレイをscale倍で表示できる大きさのウィンドウを開く
+    pub fn new(scale: u32, palette: Palette) -> Result<WindowFrontend, String> {
+        let mut renderer = RgbaRenderer::new(palette);
+        renderer.set_scale(scale);
+        let scale = renderer.scale() as usize;
+        let size = (LORES_WIDTH * scale, LORES_HEIGHT * scale);
+        let window = Window::new(TITLE, size.0, size.1, WindowOptions::default())
+            .map_err(|e| e.to_string())?;
+        Ok(WindowFrontend {
+            window,
+            renderer,
+            buffer: vec![0; size.0 * size.1],
+            size,
+            running: None,
+        })
+    }
+
+    // 白黒を反転して描画するかを設定する（ディスプレイの内容には影響しない）
+    pub fn set_invert(&mut self, invert: bool) {
+        self.renderer.set_invert(invert);
+    }
+}
+
+impl Frontend for WindowFrontend {
+    fn render(&mut self, display: &LogicalDisplay) {
+        self.size = self.renderer.frame_size(display);
+        self.buffer = self
+            .renderer
+            .render(display)
+            .chunks(4)
+            .map(|rgba| u32::from_be_bytes([0, rgba[0], rgba[1], rgba[2]]))
+            .collect();
+    }
+
+    fn attach_running_flag(&mut self, running: Arc<AtomicBool>) {
+        self.running = Some(running);
+    }
+
+    fn update(&mut self, chip8: &mut Chip8) {
+        // minifbはupdate_with_bufferでイベントを処理するため，描画しないフレームでも呼び出す
+        let (width, height) = self.size;
+        if let Err(e) = self.window.update_with_buffer(&self.buffer, width, height) {
+            log::warn!("failed to update window: {}", e);
+        }
+        if !self.window.is_open() {
+            if let Some(running) = &self.running {
+                running.store(false, Ordering::Relaxed);
+            }
+            return;
+        }
+
+        if self.window.is_key_pressed(OVERLAY_KEY, KeyRepeat::No) {
+            self.renderer.toggle_overlay();
+            self.render(chip8.display());
+        }
+        if self.renderer.overlay() {
+            self.renderer
+                .set_overlay_info(OverlayInfo::from_chip8(chip8));
+        }
+
+        for (c, key) in *chip8.keymap() {
+            if let Some(window_key) = key_for_char(c) {
+                chip8.set_key(key, self.window.is_key_down(window_key));
+            }
+        }
+    }
+}
+
+// キー配置の文字に対応するキーボードのキー（英数字以外はNone）
+fn key_for_char(c: char) -> Option<Key> {
+    let c = c.to_ascii_lowercase();
+    match c {
+        '0'..='9' => Some(DIGIT_KEYS[c as usize - '0' as usize]),
+        'a'..='z' => Some(LETTER_KEYS[c as usize - 'a' as usize]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_keymap_maps_to_window_keys() {
+        assert_eq!(key_for_char('1'), Some(Key::Key1));
+        assert_eq!(key_for_char('q'), Some(Key::Q));
+        assert_eq!(key_for_char('V'), Some(Key::V));
+        assert_eq!(key_for_char('-'), None);
+        assert!(crate::DEFAULT_KEYMAP
+            .iter()
+            .all(|&(c, _)| key_for_char(c).is_some()));
+    }
+}
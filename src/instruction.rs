@@ -0,0 +1,430 @@
+use std::fmt;
+
+use crate::{Addr, Opcode, Symbols, Variant, Vreg};
+
+// デコード済みの命令
+//
+// x, yはレジスタの番号，nは4ビット，nnnは12ビット，kkは8ビットの即値を表す
+// 一部の命令はレジスタとアドレスをVreg，Addrで持つ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Sys(Addr),                // 0NNN - SYS addr（機械語のサブルーチンの呼び出し）
+    Cls,                      // 00E0 - CLS
+    Ret,                      // 00EE - RET
+    ScrollDown(u8),           // 00CN - SCD nibble（SUPER-CHIP）
+    ScrollUp(u8),             // 00DN - SCU nibble（XO-CHIP）
+    ScrollRight,              // 00FB - SCR（SUPER-CHIP）
+    ScrollLeft,               // 00FC - SCL（SUPER-CHIP）
+    Exit,                     // 00FD - EXIT（SUPER-CHIP，インタプリタを終了する）
+    Low,                      // 00FE - LOW（SUPER-CHIP，低解像度に切り替える）
+    High,                     // 00FF - HIGH（SUPER-CHIP，高解像度に切り替える）
+    Jp(Addr),                 // 1NNN - JP addr
+    Call(Addr),               // 2NNN - CALL addr
+    Se(usize, u8),            // 3XKK - SE Vx, byte
+    Sne(usize, u8),           // 4XKK - SNE Vx, byte
+    SeVxVy(usize, usize),     // 5XY0 - SE Vx, Vy
+    SaveRange(usize, usize),  // 5XY2 - SAVE Vx - Vy（XO-CHIP）
+    LoadRange(usize, usize),  // 5XY3 - LOAD Vx - Vy（XO-CHIP）
+    Ld(Vreg, u8),             // 6XKK - LD Vx, byte
+    Add(Vreg, u8),            // 7XKK - ADD Vx, byte
+    LdVxVy(usize, usize),     // 8XY0 - LD Vx, Vy
+    Or(usize, usize),         // 8XY1 - OR Vx, Vy
+    And(usize, usize),        // 8XY2 - AND Vx, Vy
+    Xor(usize, usize),        // 8XY3 - XOR Vx, Vy
+    AddVxVy(usize, usize),    // 8XY4 - ADD Vx, Vy
+    Sub(usize, usize),        // 8XY5 - SUB Vx, Vy
+    Shr(usize, usize),        // 8XY6 - SHR Vx {, Vy}
+    Subn(usize, usize),       // 8XY7 - SUBN Vx, Vy
+    Shl(usize, usize),        // 8XYE - SHL Vx {, Vy}
+    SneVxVy(usize, usize),    // 9XY0 - SNE Vx, Vy
+    LdI(Addr),                // ANNN - LD I, addr
+    LdILong,                  // F000 NNNN - LD I, long addr（XO-CHIP，アドレスは次の2バイト）
+    JpV0(u16),                // BNNN - JP V0, addr
+    Rnd(usize, u8),           // CXKK - RND Vx, byte
+    Drw(usize, usize, usize), // DXYN - DRW Vx, Vy, nibble
+    Skp(Vreg),                // EX9E - SKP Vx
+    Sknp(Vreg),               // EXA1 - SKNP Vx
+    LdVxDt(usize),            // FX07 - LD Vx, DT
+    Plane(u8),                // FN01 - PLANE n（XO-CHIP）
+    LdVxK(usize),             // FX0A - LD Vx, K
+    LdDtVx(usize),            // FX15 - LD DT, Vx
+    LdStVx(usize),            // FX18 - LD ST, Vx
+    AddIVx(usize),            // FX1E - ADD I, Vx
+    LdFVx(usize),             // FX29 - LD F, Vx
+    LdBVx(usize),             // FX33 - LD B, Vx
+    LdIVx(usize),             // FX55 - LD [I], Vx
+    LdVxI(usize),             // FX65 - LD Vx, [I]
+}
+
+impl Instruction {
+    // 命令をデコードする（未知の命令や，その系統で使えない命令の場合はNone）
+    pub fn decode(opcode: u16, variant: Variant) -> Option<Instruction> {
+        let xo_chip = variant == Variant::XoChip;
+        let super_chip = variant != Variant::Chip8;
+
+        let op = Opcode(opcode);
+        let (x, y, n, nnn, kk) = (op.x(), op.y(), op.n(), op.nnn(), op.kk());
+
+        let instruction = match op.high_nibble() {
+            0x0 => match opcode {
+                0x00E0 => Instruction::Cls,
+                0x00EE => Instruction::Ret,
+                0x00FB if super_chip => Instruction::ScrollRight,
+                0x00FC if super_chip => Instruction::ScrollLeft,
+                0x00FD if super_chip => Instruction::Exit,
+                0x00FE if super_chip => Instruction::Low,
+                0x00FF if super_chip => Instruction::High,
+                _ if opcode & 0xFFF0 == 0x00C0 && super_chip => Instruction::ScrollDown(n as u8),
+                _ if opcode & 0xFFF0 == 0x00D0 && xo_chip => Instruction::ScrollUp(n as u8),
+                _ => Instruction::Sys(op.addr()),
+            },
+            0x1 => Instruction::Jp(op.addr()),
+            0x2 => Instruction::Call(op.addr()),
+            0x3 => Instruction::Se(x, kk),
+            0x4 => Instruction::Sne(x, kk),
+            // 5XY0/9XY0は下位4ビットが0のもののみ有効（XO-CHIPでは5XY2/5XY3も使える）
+            0x5 => match n {
+                0x0 => Instruction::SeVxVy(x, y),
+                0x2 if xo_chip => Instruction::SaveRange(x, y),
+                0x3 if xo_chip => Instruction::LoadRange(x, y),
+                _ => return None,
+            },
+            0x6 => Instruction::Ld(op.vx(), kk),
+            0x7 => Instruction::Add(op.vx(), kk),
+            0x8 => match n {
+                0x0 => Instruction::LdVxVy(x, y),
+                0x1 => Instruction::Or(x, y),
+                0x2 => Instruction::And(x, y),
+                0x3 => Instruction::Xor(x, y),
+                0x4 => Instruction::AddVxVy(x, y),
+                0x5 => Instruction::Sub(x, y),
+                0x6 => Instruction::Shr(x, y),
+                0x7 => Instruction::Subn(x, y),
+                0xE => Instruction::Shl(x, y),
+                _ => return None,
+            },
+            0x9 if n == 0 => Instruction::SneVxVy(x, y),
+            0xA => Instruction::LdI(op.addr()),
+            0xB => Instruction::JpV0(nnn),
+            0xC => Instruction::Rnd(x, kk),
+            0xD => Instruction::Drw(x, y, n),
+            0xE => match kk {
+                0x9E => Instruction::Skp(op.vx()),
+                0xA1 => Instruction::Sknp(op.vx()),
+                _ => return None,
+            },
+            0xF if opcode == 0xF000 && xo_chip => Instruction::LdILong,
+            0xF => match kk {
+                0x01 if xo_chip => Instruction::Plane(x as u8),
+                0x07 => Instruction::LdVxDt(x),
+                0x0A => Instruction::LdVxK(x),
+                0x15 => Instruction::LdDtVx(x),
+                0x18 => Instruction::LdStVx(x),
+                0x1E => Instruction::AddIVx(x),
+                0x29 => Instruction::LdFVx(x),
+                0x33 => Instruction::LdBVx(x),
+                0x55 => Instruction::LdIVx(x),
+                0x65 => Instruction::LdVxI(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    // 画面を変更する命令（CLS，DRW，スクロール，解像度の切り替え）か
+    pub fn modifies_display(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Cls
+                | Instruction::Drw(..)
+                | Instruction::ScrollDown(_)
+                | Instruction::ScrollUp(_)
+                | Instruction::ScrollRight
+                | Instruction::ScrollLeft
+                | Instruction::Low
+                | Instruction::High
+        )
+    }
+
+    // 命令の形式（"DXYN"のように，オペランドの部分をX, Y, N, NNN, KKで表したもの）
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            Instruction::Sys(_) => "0NNN",
+            Instruction::Cls => "00E0",
+            Instruction::Ret => "00EE",
+            Instruction::ScrollDown(_) => "00CN",
+            Instruction::ScrollUp(_) => "00DN",
+            Instruction::ScrollRight => "00FB",
+            Instruction::ScrollLeft => "00FC",
+            Instruction::Exit => "00FD",
+            Instruction::Low => "00FE",
+            Instruction::High => "00FF",
+            Instruction::Jp(_) => "1NNN",
+            Instruction::Call(_) => "2NNN",
+            Instruction::Se(..) => "3XKK",
+            Instruction::Sne(..) => "4XKK",
+            Instruction::SeVxVy(..) => "5XY0",
+            Instruction::SaveRange(..) => "5XY2",
+            Instruction::LoadRange(..) => "5XY3",
+            Instruction::Ld(..) => "6XKK",
+            Instruction::Add(..) => "7XKK",
+            Instruction::LdVxVy(..) => "8XY0",
+            Instruction::Or(..) => "8XY1",
+            Instruction::And(..) => "8XY2",
+            Instruction::Xor(..) => "8XY3",
+            Instruction::AddVxVy(..) => "8XY4",
+            Instruction::Sub(..) => "8XY5",
+            Instruction::Shr(..) => "8XY6",
+            Instruction::Subn(..) => "8XY7",
+            Instruction::Shl(..) => "8XYE",
+            Instruction::SneVxVy(..) => "9XY0",
+            Instruction::LdI(_) => "ANNN",
+            Instruction::LdILong => "F000",
+            Instruction::JpV0(_) => "BNNN",
+            Instruction::Rnd(..) => "CXKK",
+            Instruction::Drw(..) => "DXYN",
+            Instruction::Skp(_) => "EX9E",
+            Instruction::Sknp(_) => "EXA1",
+            Instruction::LdVxDt(_) => "FX07",
+            Instruction::Plane(_) => "FN01",
+            Instruction::LdVxK(_) => "FX0A",
+            Instruction::LdDtVx(_) => "FX15",
+            Instruction::LdStVx(_) => "FX18",
+            Instruction::AddIVx(_) => "FX1E",
+            Instruction::LdFVx(_) => "FX29",
+            Instruction::LdBVx(_) => "FX33",
+            Instruction::LdIVx(_) => "FX55",
+            Instruction::LdVxI(_) => "FX65",
+        }
+    }
+
+    // 命令のニーモニック
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Sys(_) => "SYS",
+            Instruction::Cls => "CLS",
+            Instruction::Ret => "RET",
+            Instruction::ScrollDown(_) => "SCD",
+            Instruction::ScrollUp(_) => "SCU",
+            Instruction::ScrollRight => "SCR",
+            Instruction::ScrollLeft => "SCL",
+            Instruction::Exit => "EXIT",
+            Instruction::Low => "LOW",
+            Instruction::High => "HIGH",
+            Instruction::Jp(_) | Instruction::JpV0(_) => "JP",
+            Instruction::Call(_) => "CALL",
+            Instruction::Se(..) | Instruction::SeVxVy(..) => "SE",
+            Instruction::Sne(..) | Instruction::SneVxVy(..) => "SNE",
+            Instruction::SaveRange(..) => "SAVE",
+            Instruction::LoadRange(..) => "LOAD",
+            Instruction::Ld(..)
+            | Instruction::LdVxVy(..)
+            | Instruction::LdI(_)
+            | Instruction::LdILong
+            | Instruction::LdVxDt(_)
+            | Instruction::LdVxK(_)
+            | Instruction::LdDtVx(_)
+            | Instruction::LdStVx(_)
+            | Instruction::LdFVx(_)
+            | Instruction::LdBVx(_)
+            | Instruction::LdIVx(_)
+            | Instruction::LdVxI(_) => "LD",
+            Instruction::Add(..) | Instruction::AddVxVy(..) | Instruction::AddIVx(_) => "ADD",
+            Instruction::Or(..) => "OR",
+            Instruction::And(..) => "AND",
+            Instruction::Xor(..) => "XOR",
+            Instruction::Sub(..) => "SUB",
+            Instruction::Shr(..) => "SHR",
+            Instruction::Subn(..) => "SUBN",
+            Instruction::Shl(..) => "SHL",
+            Instruction::Rnd(..) => "RND",
+            Instruction::Drw(..) => "DRW",
+            Instruction::Skp(_) => "SKP",
+            Instruction::Sknp(_) => "SKNP",
+            Instruction::Plane(_) => "PLANE",
+        }
+    }
+
+    // アセンブリの表記（名前が登録されているアドレスは名前で表記する）
+    pub fn to_string_with_symbols(&self, symbols: &Symbols) -> String {
+        match *self {
+            Instruction::Sys(addr) | Instruction::Jp(addr) | Instruction::Call(addr) => {
+                match symbols.get(addr.get()) {
+                    Some(name) => format!("{} {}", self.mnemonic(), name),
+                    None => self.to_string(),
+                }
+            }
+            Instruction::LdI(addr) => match symbols.get(addr.get()) {
+                Some(name) => format!("LD I, {}", name),
+                None => self.to_string(),
+            },
+            Instruction::JpV0(nnn) => match symbols.get(nnn) {
+                Some(name) => format!("JP V0, {}", name),
+                None => self.to_string(),
+            },
+            _ => self.to_string(),
+        }
+    }
+}
+
+// アセンブリ言語の表記（例: "LD V0, 0x05"，"DRW V0, V1, 5"）
+//
+// LD I, long addrのアドレスは次の2バイトにあるため，"LD I, long"と表記する．
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mnemonic = self.mnemonic();
+        match *self {
+            Instruction::Cls
+            | Instruction::Ret
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::Low
+            | Instruction::High => write!(f, "{}", mnemonic),
+            Instruction::Sys(nnn) | Instruction::Jp(nnn) | Instruction::Call(nnn) => {
+                write!(f, "{} {:#05X}", mnemonic, nnn)
+            }
+            Instruction::ScrollDown(n) | Instruction::ScrollUp(n) => {
+                write!(f, "{} {}", mnemonic, n)
+            }
+            Instruction::Plane(n) => write!(f, "{} {}", mnemonic, n),
+            Instruction::Se(x, kk) | Instruction::Sne(x, kk) | Instruction::Rnd(x, kk) => {
+                write!(f, "{} V{:X}, {:#04X}", mnemonic, x, kk)
+            }
+            Instruction::Ld(x, kk) | Instruction::Add(x, kk) => {
+                write!(f, "{} V{:X}, {:#04X}", mnemonic, x, kk)
+            }
+            Instruction::SeVxVy(x, y)
+            | Instruction::LdVxVy(x, y)
+            | Instruction::Or(x, y)
+            | Instruction::And(x, y)
+            | Instruction::Xor(x, y)
+            | Instruction::AddVxVy(x, y)
+            | Instruction::Sub(x, y)
+            | Instruction::Shr(x, y)
+            | Instruction::Subn(x, y)
+            | Instruction::Shl(x, y)
+            | Instruction::SneVxVy(x, y) => write!(f, "{} V{:X}, V{:X}", mnemonic, x, y),
+            Instruction::SaveRange(x, y) | Instruction::LoadRange(x, y) => {
+                write!(f, "{} V{:X} - V{:X}", mnemonic, x, y)
+            }
+            Instruction::LdI(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            Instruction::LdILong => write!(f, "LD I, long"),
+            Instruction::JpV0(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            Instruction::Drw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::Skp(x) | Instruction::Sknp(x) => write!(f, "{} V{:X}", mnemonic, x),
+            Instruction::LdVxDt(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::LdVxK(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::LdDtVx(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::LdStVx(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIVx(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LdFVx(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LdBVx(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::LdIVx(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LdVxI(x) => write!(f, "LD V{:X}, [I]", x),
+        }
+    }
+}
+
+// 全ての命令のニーモニック
+pub const MNEMONICS: [&str; 30] = [
+    "SYS", "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE",
+    "SNE", "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND",
+    "DRW", "SKP", "SKNP", "PLANE",
+];
+
+// 対応している命令の形式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeSpec {
+    pub pattern: &'static str,  // 命令の形式（"DXYN"など）
+    pub mnemonic: &'static str, // ニーモニック
+    pub variants: Vec<Variant>, // その命令を使える系統
+}
+
+// 対応している全ての命令の形式を，最も小さいopcodeの順に返す
+//
+// 全ての系統で全てのopcodeを実際にデコードして求めるため，命令の実行と食い違うことはない．
+pub fn supported_opcodes() -> Vec<OpcodeSpec> {
+    let mut specs: Vec<OpcodeSpec> = Vec::new();
+    for opcode in 0..=u16::MAX {
+        for variant in Variant::ALL {
+            let Some(instruction) = Instruction::decode(opcode, variant) else {
+                continue;
+            };
+            let pattern = instruction.pattern();
+            match specs.iter_mut().find(|spec| spec.pattern == pattern) {
+                Some(spec) if !spec.variants.contains(&variant) => spec.variants.push(variant),
+                Some(_) => {}
+                None => specs.push(OpcodeSpec {
+                    pattern,
+                    mnemonic: instruction.mnemonic(),
+                    variants: vec![variant],
+                }),
+            }
+        }
+    }
+    specs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 命令の形式に対応する系統
+    fn variants_of(specs: &[OpcodeSpec], pattern: &str) -> Option<Vec<Variant>> {
+        specs
+            .iter()
+            .find(|spec| spec.pattern == pattern)
+            .map(|spec| spec.variants.clone())
+    }
+
+    #[test]
+    fn supported_opcodes_lists_each_pattern_with_its_variants() {
+        let specs = supported_opcodes();
+        assert_eq!(variants_of(&specs, "DXYN"), Some(Variant::ALL.to_vec()));
+        let scroll = Some(vec![Variant::SuperChip, Variant::XoChip]);
+        for pattern in ["00CN", "00FB", "00FC"] {
+            assert_eq!(variants_of(&specs, pattern), scroll, "{}", pattern);
+        }
+        let dxyn = specs.iter().find(|spec| spec.pattern == "DXYN").unwrap();
+        assert_eq!(dxyn.mnemonic, "DRW");
+        // 形式は重複しない
+        assert!((1..specs.len()).all(|i| specs[..i].iter().all(|s| s.pattern != specs[i].pattern)));
+    }
+
+    #[test]
+    fn resolution_switches_decode_only_on_super_chip() {
+        assert_eq!(
+            Instruction::decode(0x00FE, Variant::SuperChip),
+            Some(Instruction::Low)
+        );
+        assert_eq!(
+            Instruction::decode(0x00FF, Variant::XoChip),
+            Some(Instruction::High)
+        );
+        assert_eq!(
+            Instruction::decode(0x00FF, Variant::Chip8),
+            Some(Instruction::Sys(Addr::new(0x0FF).unwrap()))
+        );
+        assert_eq!(Instruction::High.to_string(), "HIGH");
+        assert!(Instruction::Low.modifies_display());
+    }
+
+    #[test]
+    fn exit_decodes_only_on_super_chip() {
+        for variant in [Variant::SuperChip, Variant::XoChip] {
+            assert_eq!(
+                Instruction::decode(0x00FD, variant),
+                Some(Instruction::Exit)
+            );
+        }
+        assert_eq!(
+            Instruction::decode(0x00FD, Variant::Chip8),
+            Some(Instruction::Sys(Addr::new(0x0FD).unwrap()))
+        );
+        assert_eq!(Instruction::Exit.to_string(), "EXIT");
+        assert!(MNEMONICS.contains(&"EXIT"));
+    }
+}
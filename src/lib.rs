@@ -0,0 +1,1977 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::{debug, error, trace, warn};
+use rand::rngs::StdRng;
+use rand::{random, SeedableRng};
+
+pub mod audio;
+mod builder;
+mod bus;
+mod clock;
+mod config;
+pub mod debugger;
+mod display;
+mod error;
+pub mod frontend;
+mod instruction;
+mod opcode;
+mod patch;
+mod profile;
+mod quirks;
+mod rand_source;
+mod recording;
+pub mod reference;
+mod rom;
+mod save_state;
+mod snapshot;
+mod state;
+mod symbols;
+mod variant;
+
+pub use builder::Chip8Builder;
+pub use bus::{Bus, UnmappedReadPolicy};
+pub use clock::{Clock, MockClock, MonotonicClock};
+pub use config::{Config, CONFIG_FILE_NAME};
+pub use display::{
+    DisplayChange, Framebuffer, LogicalDisplay, SharedDisplay, Sprite, HIRES_HEIGHT, HIRES_WIDTH,
+    LORES_HEIGHT, LORES_WIDTH, MAX_DISPLAY_HEIGHT, MAX_DISPLAY_WIDTH,
+};
+use display::{DisplayLog, RegionWatch};
+pub use error::{Chip8Error, ErrorKind};
+pub use instruction::{supported_opcodes, Instruction, OpcodeSpec, MNEMONICS};
+pub use opcode::{Addr, Opcode, Vreg};
+pub use patch::{apply_patch, Patch, PatchRecord};
+pub use profile::{
+    diff_profiles, recommend_profile, rom_sha256, QuirkProfile, QuirkValue, SHIFT_VY_TEST_ROM,
+};
+pub use quirks::{
+    DisplayWaitQuirk, EdgeQuirk, JumpQuirk, KeyWaitQuirk, LoadStoreQuirk, LogicQuirk,
+    LoresScrollQuirk, Quirks, ScrollQuirk, ShiftQuirk, SysQuirk,
+};
+pub use rand_source::RandSource;
+pub use rom::{stress_rom, OddLengthPolicy, RomInfo};
+pub use save_state::SAVE_STATE_VERSION;
+pub use symbols::Symbols;
+pub use variant::Variant;
+
+use frontend::Frontend;
+use recording::Recording;
+use snapshot::{Snapshot, HISTORY_LIMIT};
+
+#[cfg(test)]
+mod tests;
+
+const FONTSET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+// 標準のキー配置（キーボードの文字と対応するキー）
+//
+// COSMAC VIPのキーパッドの並び（123C/456D/789E/A0BF）を，キーボードの1234/QWER/ASDF/ZXCVに割り当てる
+pub const DEFAULT_KEYMAP: [(char, u8); 16] = [
+    ('1', 0x1),
+    ('2', 0x2),
+    ('3', 0x3),
+    ('4', 0xC),
+    ('q', 0x4),
+    ('w', 0x5),
+    ('e', 0x6),
+    ('r', 0xD),
+    ('a', 0x7),
+    ('s', 0x8),
+    ('d', 0x9),
+    ('f', 0xE),
+    ('z', 0xA),
+    ('x', 0x0),
+    ('c', 0xB),
+    ('v', 0xF),
+];
+
+// 1秒あたりのフレーム数
+const FRAMES_PER_SECOND: u32 = 60;
+// 1フレーム（60Hz）あたりに実行する命令数の標準値
+const CYCLES_PER_FRAME: usize = 10;
+const FRAME_DURATION: Duration = Duration::from_micros(16_667);
+
+// スタックの段数（サブルーチンの呼び出しを入れ子にできる深さ）の標準値
+//
+// CALLはstack[sp]に戻り先を書き込んでからspを1つ進め，RETはspを1つ戻してからstack[sp]を読む．
+// spは常に積まれているアドレスの数と等しく，空のスタックでのRETや段数を超えるCALLはエラーとなる．
+// SUPER-CHIPと同じ16段を標準とし，COSMAC VIP（12段）に合わせる場合はset_stack_sizeで変更する．
+pub const STACK_SIZE: usize = 16;
+
+// プログラムを読み込む先頭のアドレス（それより前はインタプリタとフォントの領域）
+const PROGRAM_START: usize = 0x200;
+
+// memory_mapでメモリ全体を表す図の文字数
+const MEMORY_MAP_WIDTH: usize = 64;
+
+// fpsで平均をとる直近のフレーム数
+const FPS_WINDOW: usize = 60;
+
+// DRWでスプライトの画素を画面に重ねる方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DrawMode {
+    #[default]
+    Xor, // 通常の描画（XOR）
+    Or, // デバッグ用: 画素を消さずに重ねる（衝突は通常通り報告する）
+}
+
+// tickで1フレームを実行した結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TickResult {
+    pub redraw: bool, // ディスプレイが変化したため描画が必要か
+    pub beep: bool,   // ブザーを鳴らすべきか（サウンドタイマが0でない）
+    pub halted: bool, // プログラムが停止したか
+}
+
+// フレームごとに呼び出されるフック
+pub type TickHook = Box<dyn FnMut(&mut Chip8) + Send>;
+
+// 命令を実行する直前に呼び出されるフック（pc，opcode，デコードした命令が渡される）
+pub type TraceHook = Box<dyn FnMut(u16, u16, &Instruction) + Send>;
+
+// 命令を実行する前に呼び出されるフック（実行中のマシンとpcのopcodeが渡され，falseを返すと実行しない）
+pub type PreStepHook = Box<dyn FnMut(&mut Chip8, u16) -> bool + Send>;
+
+// 命令を実行した後に呼び出されるフック（実行中のマシンと実行したopcodeが渡される）
+pub type PostStepHook = Box<dyn FnMut(&mut Chip8, u16) + Send>;
+
+// CXKK（RND）の1回分の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RndRecord {
+    pub pc: u16,    // RNDのアドレス
+    pub raw: u8,    // 乱数の生成元から得た値
+    pub kk: u8,     // ANDする値
+    pub result: u8, // Vxに代入した値（raw & kk）
+}
+
+// RNDを実行するたびに呼び出されるフック（乱数の再現性の確認用）
+pub type RndHook = Box<dyn FnMut(RndRecord) + Send>;
+
+pub struct Chip8 {
+    bus: Bus,                               // メモリ空間
+    registers: [u8; 16],                    // レジスタ
+    stack: Vec<u16>,                        // スタック
+    i: u16,                                 // インデックスレジスタ
+    pc: u16,                                // プログラムカウンタ
+    sp: usize,                // スタックポインタ（スタックに積まれているアドレスの数）
+    delay: u8,                // ディレイタイマ
+    sound: u8,                // サウンドタイマ
+    beep_started: bool,       // サウンドタイマが0から設定され，ブザーを鳴らし始めたか
+    keyboard: [bool; 16], // キー入力状態（フレームの開始時に確定し，フレームの途中では変わらない）
+    key_input: [bool; 16], // set_keyで設定された最新のキー入力状態
+    keymap: [(char, u8); 16], // キーボードの文字と対応するキー
+    key_events: VecDeque<(u8, bool)>, // 未反映のキーの押下・解放のイベント
+    recording: Option<Recording>, // 記録中のキー入力
+    replay: Option<VecDeque<u16>>, // 再生するキー入力（フレームごとのキーの状態）
+    key_latch: Option<u8>, // 現在のフレームで最初に押されたキー
+    key_release_latch: Option<u8>, // 現在のフレームで最初に離されたキー
+    key_wait: Option<u8>, // FX0Aで離されるのを待っているキー
+    key_wait_parked: bool, // このフレームでFX0Aがキー入力を待ったか
+    key_wait_frames: usize, // FX0Aがキー入力を待ち続けているフレーム数
+    key_wait_timeout: Option<usize>, // FX0Aがキー入力を待てる最大のフレーム数（Noneは無制限）
+    display: LogicalDisplay, // ディスプレイ
+    display_updated: bool, // 前回の描画からディスプレイが変化したか
+    display_waited: bool, // 現在のフレームでDRWが垂直帰線期間を待ち終えたか
+    display_wait_pending: bool, // DRWが垂直帰線期間を待っているか
+    region_watch: Option<RegionWatch>, // 変化を監視するディスプレイの領域
+    display_log: Option<DisplayLog>, // ディスプレイの変化の記録
+    cycle_count: u64,     // 実行した命令数
+    shared_display: Option<SharedDisplay>, // 別のスレッドと共有するディスプレイ
+    quirks: Quirks,       // 互換性に関わる挙動の設定
+    variant: Variant,     // エミュレートするCHIP-8の系統
+    odd_length_policy: OddLengthPolicy, // 奇数長のROMの扱い
+    rom_hash: u64,        // 読み込んだROMのハッシュ値
+    rom_len: usize,       // 読み込んだROMのバイト数（メモリに収まった分）
+    seed: u64,            // 乱数のシード
+    rng: Box<dyn RandSource + Send>, // RNDで使う乱数の生成元
+    debug: bool,          // デバッグモード（1命令ごとに状態を保存する）
+    draw_mode: DrawMode,  // スプライトの描画方法
+    planes: u8,           // 描画対象のプレーン（ビットマスク，XO-CHIP以外は常にプレーン1）
+    halt_on_idle: bool,   // 自分自身へのジャンプを検出したら停止するか
+    protect_interpreter_region: bool, // 0x200より前の領域への書き込みをエラーにするか
+    flip_sprites: bool,   // スプライトの各行を左右反転して描画するか（デバッグ用）
+    halted: bool,         // プログラムが停止したか
+    running: Arc<AtomicBool>, // runを続けるか（falseにすると次のフレームの前に終了する）
+    frame_cycle: usize,   // 現在のフレームで実行した命令数
+    cycles_per_frame: usize, // 1フレームあたりに実行する命令数
+    advance_remainder: u128, // advanceで命令数に換算しきれなかった時間（ナノ秒×命令の周波数）
+    clock: Box<dyn Clock + Send>, // 実行の速度の調整に使う時計
+    last_advance: Option<Instant>, // advance_with_clockを前回呼び出した時刻
+    frames: u64,          // 終えたフレーム数
+    frame_times: VecDeque<Instant>, // 直近のフレームを終えた時刻（fpsの計算に使う）
+    tick_hook: Option<TickHook>, // フレームごとに呼び出されるフック
+    trace_hook: Option<TraceHook>, // 命令ごとに呼び出されるフック
+    pre_step_hook: Option<PreStepHook>, // 命令を実行する前に呼び出されるフック
+    post_step_hook: Option<PostStepHook>, // 命令を実行した後に呼び出されるフック
+    rnd_hook: Option<RndHook>, // RNDを実行するたびに呼び出されるフック
+    symbols: Symbols,     // 逆アセンブルで表示するアドレスの名前
+    history: VecDeque<Snapshot>, // 各命令を実行する直前の状態
+    rewound_rnd: Vec<u8>, // 巻き戻したRNDが得ていた値（次のRNDから新しいものの順に使う）
+    decode_cache: Vec<Option<Instruction>>, // アドレスごとのデコード結果（無効時は空）
+}
+
+impl Chip8 {
+    // 初期化
+    pub fn new() -> Chip8 {
+        Chip8::with_quirks(Quirks::default())
+    }
+
+    // ディスプレイの大きさを指定して初期化
+    pub fn with_display_size(width: usize, height: usize) -> Result<Chip8, Chip8Error> {
+        let mut chip8 = Chip8::new();
+        chip8.display = LogicalDisplay::new(width, height)?;
+        Ok(chip8)
+    }
+
+    // 互換性に関わる挙動を指定して初期化
+    pub fn with_quirks(quirks: Quirks) -> Chip8 {
+        // メモリとレジスタを初期化
+        let mut bus = Bus::new(Variant::default().memory_size());
+        let registers = [0; 16];
+        let stack = vec![0; STACK_SIZE];
+
+        // メモリの先頭から順に，フォントセットをロード
+        bus.load(0, &FONTSET);
+
+        // 乱数のシードは毎回変える（再現したい場合はset_seedで指定する）
+        let seed = random();
+
+        Chip8 {
+            bus,
+            registers,
+            stack,
+            i: 0,
+            pc: 0x200,
+            sp: 0,
+            delay: 0,
+            sound: 0,
+            beep_started: false,
+            keyboard: [false; 16],
+            key_input: [false; 16],
+            keymap: DEFAULT_KEYMAP,
+            key_events: VecDeque::new(),
+            recording: None,
+            replay: None,
+            key_latch: None,
+            key_release_latch: None,
+            key_wait: None,
+            key_wait_parked: false,
+            key_wait_frames: 0,
+            key_wait_timeout: None,
+            display: LogicalDisplay::default(),
+            display_updated: false,
+            display_waited: false,
+            display_wait_pending: false,
+            region_watch: None,
+            display_log: None,
+            cycle_count: 0,
+            shared_display: None,
+            quirks,
+            variant: Variant::default(),
+            odd_length_policy: OddLengthPolicy::default(),
+            rom_hash: fnv1a(&[]),
+            rom_len: 0,
+            seed,
+            rng: Box::new(StdRng::seed_from_u64(seed)),
+            debug: false,
+            draw_mode: DrawMode::default(),
+            planes: 0x01,
+            halt_on_idle: false,
+            protect_interpreter_region: false,
+            flip_sprites: false,
+            halted: false,
+            running: Arc::new(AtomicBool::new(true)),
+            frame_cycle: 0,
+            cycles_per_frame: CYCLES_PER_FRAME,
+            advance_remainder: 0,
+            clock: Box::new(MonotonicClock),
+            last_advance: None,
+            frames: 0,
+            frame_times: VecDeque::with_capacity(FPS_WINDOW),
+            tick_hook: None,
+            trace_hook: None,
+            pre_step_hook: None,
+            post_step_hook: None,
+            rnd_hook: None,
+            symbols: Symbols::default(),
+            history: VecDeque::new(),
+            rewound_rnd: Vec::new(),
+            decode_cache: Vec::new(),
+        }
+    }
+
+    // ゲームプログラムの実行
+    pub fn run(&mut self, program: &[u8], frontend: &mut dyn Frontend) -> Result<(), Chip8Error> {
+        self.load(program);
+        frontend.attach_running_flag(self.running_flag());
+
+        // メインループ
+        loop {
+            // 停止が要求された場合は終了する
+            if !self.running.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+
+            let frame_start = self.clock.now();
+
+            // 1フレーム分の命令を実行
+            self.run_frame()?;
+
+            // ディスプレイが変化した場合のみ描画する
+            if self.take_display_updated() {
+                frontend.render(&self.display);
+            }
+            frontend.update(self);
+
+            // プログラムが停止した場合は終了する
+            if self.halted {
+                return Ok(());
+            }
+
+            // 60Hzになるように待機
+            let elapsed = self.clock.now().saturating_duration_since(frame_start);
+            if let Some(rest) = FRAME_DURATION.checked_sub(elapsed) {
+                self.clock.sleep(rest);
+            }
+        }
+    }
+
+    // runの停止を要求する（他のスレッドからはrunning_flagで取得したフラグを使う）
+    pub fn request_stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    // runを続けるかを表すフラグ（falseにするとrunは次のフレームの前に終了する）
+    pub fn running_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.running)
+    }
+
+    // 1フレーム分の処理を実行し，すぐに制御を返す（内部で待機しない）
+    // 非同期のフロントエンドは，自身のイベントループやタイマーから60Hzで呼び出す
+    pub fn tick(&mut self) -> Result<TickResult, Chip8Error> {
+        self.run_frame()?;
+        Ok(TickResult {
+            redraw: self.take_display_updated(),
+            beep: self.is_beeping(),
+            halted: self.halted,
+        })
+    }
+
+    // 1フレーム分（60Hz）の処理を実行
+    // run_cyclesなどで途中まで進んだフレームがあれば，その残りを実行する
+    // キー入力はフレームの開始時（命令を実行する前）に1度だけ読み取る
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        loop {
+            // 停止した後も，タイマーなどのフレームの処理は進める
+            if self.halted {
+                if self.frame_cycle == 0 {
+                    self.begin_frame();
+                }
+                self.end_frame();
+                self.frame_cycle = 0;
+                return Ok(());
+            }
+
+            self.cycle()?;
+            if self.frame_cycle == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // 指定された数の命令を実行し，実際に実行した命令数を返す（停止した場合はそこで終わる）
+    pub fn run_cycles(&mut self, cycles: usize) -> Result<usize, Chip8Error> {
+        for cycle in 0..cycles {
+            if self.halted {
+                return Ok(cycle);
+            }
+            self.cycle()?;
+        }
+        Ok(cycles)
+    }
+
+    // 描画せずにできるだけ速く命令を実行し，実時間でlimitが経過したら止めて実行した命令数を返す
+    //
+    // 時刻はset_clockで設定した時計で測り，1フレーム分の命令ごとに確認する．停止した場合はそこで終わる．
+    pub fn run_with_timeout(&mut self, limit: Duration) -> Result<usize, Chip8Error> {
+        self.run_cycles_with_timeout(usize::MAX, limit)
+    }
+
+    // run_with_timeoutと同じく実時間の制限付きで，最大でcycles個の命令を実行する
+    pub fn run_cycles_with_timeout(
+        &mut self,
+        cycles: usize,
+        limit: Duration,
+    ) -> Result<usize, Chip8Error> {
+        let start = self.clock.now();
+        let mut executed = 0;
+        while executed < cycles && !self.halted {
+            if self.clock.now().duration_since(start) >= limit {
+                break;
+            }
+            let chunk = self.cycles_per_frame.min(cycles - executed);
+            executed += self.run_cycles(chunk)?;
+        }
+        Ok(executed)
+    }
+
+    // 経過した実時間の分だけ命令を実行し，実行した命令数を返す
+    //
+    // 命令の周波数（cpu_hz）で換算した命令数を実行し，タイマーは1フレーム分の命令ごとに60Hzで更新する．
+    // 換算しきれなかった端数は次の呼び出しに持ち越すため，どのような間隔で呼び出しても累計は実時間に一致する．
+    // 停止した後も命令を実行しない点を除いて同じように時間を進める．
+    pub fn advance(&mut self, elapsed: Duration) -> Result<usize, Chip8Error> {
+        const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+        self.advance_remainder += elapsed.as_nanos() * self.cpu_hz() as u128;
+        let cycles = (self.advance_remainder / NANOS_PER_SEC) as usize;
+        self.advance_remainder %= NANOS_PER_SEC;
+
+        let mut executed = 0;
+        for _ in 0..cycles {
+            if self.halted {
+                self.idle_cycle();
+            } else {
+                self.cycle()?;
+                executed += 1;
+            }
+        }
+        Ok(executed)
+    }
+
+    // 別のスレッドで描画するために共有するディスプレイ（各フレームの終了時に更新される）
+    //
+    // Chip8をCPUのスレッドに移し，描画するスレッドでは返した値のreadでフレームを取得する．
+    // 何度呼び出しても同じものを共有する．
+    pub fn shared_display(&mut self) -> SharedDisplay {
+        self.shared_display
+            .get_or_insert_with(|| SharedDisplay::new(&self.display))
+            .clone()
+    }
+
+    // ディスプレイの変化を記録するかを設定する（既定では記録しない）
+    // 画面を変更する命令を実行するたびに確認し，変化した場合だけ命令数と画素数を記録する
+    pub fn set_display_log(&mut self, enabled: bool) {
+        self.display_log = enabled.then(|| DisplayLog::new(&self.display));
+    }
+
+    // 記録したディスプレイの変化を取り出す（記録していない場合は空）
+    pub fn take_display_log(&mut self) -> Vec<DisplayChange> {
+        self.display_log
+            .as_mut()
+            .map(DisplayLog::take)
+            .unwrap_or_default()
+    }
+
+    // これまでに実行した命令数
+    pub fn cycle_count(&self) -> u64 {
+        self.cycle_count
+    }
+
+    // ディスプレイの矩形領域の変化を監視する（スコアなどの表示のデバッグ用）
+    // 各フレームの終了時に確認し，変化があればtake_display_region_changedがtrueを返す
+    pub fn watch_display_region(&mut self, x: u8, y: u8, w: u8, h: u8) {
+        let (x, y, w, h) = (x as usize, y as usize, w as usize, h as usize);
+        self.region_watch = Some(RegionWatch::new(&self.display, x, y, w, h));
+    }
+
+    // ディスプレイの領域の監視をやめる
+    pub fn unwatch_display_region(&mut self) {
+        self.region_watch = None;
+    }
+
+    // 監視している領域が前回の呼び出しから変化したか（監視していない場合はfalse）
+    pub fn take_display_region_changed(&mut self) -> bool {
+        self.region_watch
+            .as_mut()
+            .is_some_and(|watch| watch.take_changed())
+    }
+
+    // 前回の呼び出しから時計で経過した時間の分だけ命令を実行し，実行した命令数を返す（初回は何もしない）
+    pub fn advance_with_clock(&mut self) -> Result<usize, Chip8Error> {
+        let now = self.clock.now();
+        let elapsed = self
+            .last_advance
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        self.last_advance = Some(now);
+        self.advance(elapsed)
+    }
+
+    // 実行の速度の調整に使う時計を設定する（テストではMockClockを使うと実際に待たずに済む）
+    pub fn set_clock(&mut self, clock: Box<dyn Clock + Send>) {
+        self.clock = clock;
+        self.last_advance = None;
+        self.frame_times.clear();
+    }
+
+    // ディスプレイが変化したかを取得し，変化していない状態に戻す
+    // フロントエンドは任意の間隔でこれを確認し，変化していればdisplay()を描画する
+    pub fn take_display_updated(&mut self) -> bool {
+        std::mem::take(&mut self.display_updated)
+    }
+
+    // 1命令を実行し，1フレーム分の命令ごとに入力とタイマーを更新する
+    fn cycle(&mut self) -> Result<(), Chip8Error> {
+        if self.frame_cycle == 0 {
+            self.begin_frame();
+        }
+        // FX0Aがキー入力を待っている間は，キー入力が反映される次のフレームまで命令を実行しない
+        // （時間とタイマーは通常通り進む）
+        if !self.key_wait_parked {
+            self.step()?;
+        }
+        self.finish_cycle();
+        Ok(())
+    }
+
+    // 停止中の1命令分の時間を進める（命令は実行せず，フレームの処理のみ行う）
+    fn idle_cycle(&mut self) {
+        if self.frame_cycle == 0 {
+            self.begin_frame();
+        }
+        self.finish_cycle();
+    }
+
+    // 1命令分の時間を数え，1フレーム分に達したらフレームを終える
+    fn finish_cycle(&mut self) {
+        self.frame_cycle += 1;
+        if self.frame_cycle >= self.cycles_per_frame {
+            self.end_frame();
+            self.frame_cycle = 0;
+        }
+    }
+
+    // フレームの開始時の処理: キー入力のイベントを反映する
+    //
+    // キー入力はフレームの開始時に1度だけ読み取る．set_keyによる状態の変更やpush_key_eventの
+    // イベントは，フレームの途中で行われても次のフレームの開始時にまとめて反映される．
+    // これにより，記録したキー入力を再生したときに同じ命令が同じキーの状態を見る．
+    fn begin_frame(&mut self) {
+        // フレーム内で押されて離されたキーも取りこぼさないように，押されたキーを記録しておく
+        self.key_latch = None;
+        self.key_release_latch = None;
+        // 前のフレームで待っていたDRWがあれば，このフレームの待ちはそれで済ませる
+        self.display_waited = std::mem::take(&mut self.display_wait_pending);
+
+        // set_keyで設定された状態との差分をイベントとして反映する
+        for key in 0..16u8 {
+            let pressed = self.key_input[key as usize];
+            if self.keyboard[key as usize] != pressed {
+                self.key_events.push_back((key, pressed));
+            }
+        }
+
+        // 再生中は記録したキーの状態との差分をイベントとして反映する
+        if let Some(replay) = &mut self.replay {
+            match replay.pop_front() {
+                Some(mask) => {
+                    for key in 0..16u8 {
+                        let pressed = mask & (1 << key) != 0;
+                        if self.keyboard[key as usize] != pressed {
+                            self.key_events.push_back((key, pressed));
+                        }
+                    }
+                }
+                None => self.replay = None,
+            }
+        }
+        while let Some((key, pressed)) = self.key_events.pop_front() {
+            let was_pressed = self.keyboard[key as usize];
+            self.keyboard[key as usize] = pressed;
+            if pressed && self.key_latch.is_none() {
+                self.key_latch = Some(key);
+            }
+            if !pressed && was_pressed && self.key_release_latch.is_none() {
+                self.key_release_latch = Some(key);
+            }
+        }
+        self.key_input = self.keyboard;
+
+        if let Some(recording) = &mut self.recording {
+            let mask = (0..16).fold(0u16, |mask, key| mask | (self.keyboard[key] as u16) << key);
+            recording.push(mask);
+        }
+    }
+
+    // フレームの終了時の処理: タイマーを更新し，フックを呼び出す
+    fn end_frame(&mut self) {
+        self.update_timers();
+        self.frames += 1;
+        if self.frame_times.len() == FPS_WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(self.clock.now());
+        if let Some(watch) = &mut self.region_watch {
+            watch.update(&self.display);
+        }
+        if let Some(shared) = &self.shared_display {
+            shared.publish(&self.display);
+        }
+
+        // FX0Aがキー入力を待ち続けているフレーム数を数える
+        if self.key_wait_parked {
+            self.key_wait_frames += 1;
+        } else {
+            self.key_wait_frames = 0;
+        }
+        self.key_wait_parked = false;
+
+        // フックの実行中は取り外しておくため，フックの中からフレームを進めてもフックは再度呼ばれない
+        if let Some(mut hook) = self.tick_hook.take() {
+            hook(self);
+            // フックの中で新しいフックが設定されていなければ元に戻す
+            if self.tick_hook.is_none() {
+                self.tick_hook = Some(hook);
+            }
+        }
+    }
+
+    // ゲームプログラムをロードしてから終えたフレーム数
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    // 直近のフレームの間隔から求めた1秒あたりのフレーム数（set_clockで設定した時計で測る）
+    // 2フレーム以上を終えていない場合は0を返す
+    pub fn fps(&self) -> f64 {
+        let (Some(first), Some(last)) = (self.frame_times.front(), self.frame_times.back()) else {
+            return 0.0;
+        };
+        let elapsed = last.saturating_duration_since(*first).as_secs_f64();
+        if elapsed == 0.0 {
+            return 0.0;
+        }
+        (self.frame_times.len() - 1) as f64 / elapsed
+    }
+
+    // FX0Aがキー入力を待てる最大のフレーム数を設定する（Noneは無制限で，既定値）
+    // 超えた場合はKeyWaitTimeoutエラーとなるため，キー入力のない自動実行でも終了できる
+    pub fn set_key_wait_timeout(&mut self, frames: Option<usize>) {
+        self.key_wait_timeout = frames;
+    }
+
+    // 60Hzのフレームごと（タイマーの更新後）に呼び出されるフックを設定する
+    // フックには実行中のマシンが渡され，キー入力の追加や状態の読み取りができる
+    pub fn set_tick_hook(&mut self, hook: TickHook) {
+        self.tick_hook = Some(hook);
+    }
+
+    // RNDを実行するたびに，乱数の生の値とANDした結果を渡して呼び出されるフックを設定する（Noneで解除）
+    pub fn set_rnd_hook(&mut self, hook: Option<RndHook>) {
+        self.rnd_hook = hook;
+    }
+
+    // 命令を実行する直前に呼び出されるフックを設定する（Noneで解除）
+    pub fn set_trace_hook(&mut self, hook: Option<TraceHook>) {
+        self.trace_hook = hook;
+    }
+
+    // 命令を実行する前に呼び出されるフックを設定する（Noneで解除）
+    // フックがfalseを返した場合はその命令を実行せず，pcも進めない
+    pub fn set_pre_step_hook(&mut self, hook: Option<PreStepHook>) {
+        self.pre_step_hook = hook;
+    }
+
+    // 命令を実行した後に呼び出されるフックを設定する（Noneで解除）
+    // 実行に失敗した命令では呼び出されない
+    pub fn set_post_step_hook(&mut self, hook: Option<PostStepHook>) {
+        self.post_step_hook = hook;
+    }
+
+    // 画面を変更する命令（CLS，DRW，スクロール）を1つ実行するまで命令を実行し，実行した命令数を返す
+    // 停止した場合はそこで終わり，max_cycles命令を実行しても画面を変更しなかった場合はエラーとなる
+    pub fn step_to_next_draw(&mut self, max_cycles: usize) -> Result<usize, Chip8Error> {
+        for cycle in 0..max_cycles {
+            if self.halted {
+                return Ok(cycle);
+            }
+
+            let pc = self.pc;
+            let draws = self
+                .opcode_at(pc)
+                .and_then(|opcode| Instruction::decode(opcode, self.variant))
+                .is_some_and(|instruction| instruction.modifies_display());
+            self.cycle()?;
+            // 垂直帰線期間を待っているDRWはpcが進まず，まだ描画していない
+            if draws && self.pc != pc {
+                return Ok(cycle + 1);
+            }
+        }
+        Err(ErrorKind::CycleLimitReached(max_cycles).into())
+    }
+
+    // 条件を満たすまで命令を実行し，それまでに実行した命令数を返す
+    // max_cycles命令を実行しても条件を満たさない場合はエラーとなる
+    pub fn run_until<F: Fn(&Chip8) -> bool>(
+        &mut self,
+        predicate: F,
+        max_cycles: usize,
+    ) -> Result<usize, Chip8Error> {
+        for cycle in 0..max_cycles {
+            if predicate(self) {
+                return Ok(cycle);
+            }
+
+            self.cycle()?;
+        }
+
+        if predicate(self) {
+            Ok(max_cycles)
+        } else {
+            Err(ErrorKind::CycleLimitReached(max_cycles).into())
+        }
+    }
+
+    // キーの状態を設定する（次のフレームの開始時に反映する）
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.key_input[key as usize & 0xF] = pressed;
+    }
+
+    // キーボードの文字と対応するキー（フロントエンドがヘルプの表示や入力の変換に使う）
+    pub fn keymap(&self) -> &[(char, u8); 16] {
+        &self.keymap
+    }
+
+    // キー配置を設定する（既定ではDEFAULT_KEYMAP）
+    pub fn set_keymap(&mut self, keymap: [(char, u8); 16]) {
+        self.keymap = keymap;
+    }
+
+    // キーボードの文字に対応するキー（大文字と小文字は区別しない）
+    pub fn key_for_char(&self, c: char) -> Option<u8> {
+        self.keymap
+            .iter()
+            .find(|(key_char, _)| key_char.eq_ignore_ascii_case(&c))
+            .map(|&(_, key)| key)
+    }
+
+    // キーの押下・解放のイベントを追加する（次のフレームの開始時にまとめて反映する）
+    pub fn push_key_event(&mut self, key: u8, pressed: bool) {
+        self.key_events.push_back((key & 0xF, pressed));
+    }
+
+    // ゲームプログラムのロード
+    pub fn load(&mut self, program: &[u8]) {
+        // メモリの0x200から順に，ゲームプログラムをロード
+        let loaded = self.bus.load(PROGRAM_START, program);
+        self.rom_hash = fnv1a(program);
+        self.rom_len = loaded;
+        self.frames = 0;
+        self.frame_times.clear();
+        self.clear_decode_cache();
+        if loaded < program.len() {
+            warn!(
+                "ROM is too large ({} bytes); only the first {} bytes were loaded",
+                program.len(),
+                loaded
+            );
+        }
+
+        // 命令は2バイト単位なので，奇数長のROMは設定に応じて補うか警告する
+        if !program.len().is_multiple_of(2) {
+            let end = PROGRAM_START + program.len();
+            match self.odd_length_policy {
+                // メモリの末尾まで読み込んだ場合は補えない
+                OddLengthPolicy::Pad => {
+                    let _ = self.write_memory(end, 0x00);
+                }
+                OddLengthPolicy::Warn => warn!(
+                    "ROM has an odd length ({} bytes); the last opcode at {:#05X} is incomplete",
+                    program.len(),
+                    end - 1
+                ),
+            }
+        }
+    }
+
+    // マシンを電源投入時の状態に戻してからゲームプログラムをロードする（ROMの再読み込み用）
+    //
+    // メモリ，レジスタ，スタック，タイマー，ディスプレイ，キー入力の状態，実行の履歴を初期化する．
+    // 設定（互換性の設定，系統，フック，時計，乱数の生成元など）はそのまま引き継ぐ．
+    pub fn load_and_reset(&mut self, program: &[u8]) {
+        self.bus.clear();
+        self.bus.load(0, &FONTSET);
+        self.registers = [0; 16];
+        self.stack.fill(0);
+        self.i = 0;
+        self.pc = PROGRAM_START as u16;
+        self.sp = 0;
+        self.delay = 0;
+        self.sound = 0;
+        self.beep_started = false;
+        self.keyboard = [false; 16];
+        self.key_input = [false; 16];
+        self.key_events.clear();
+        self.key_latch = None;
+        self.key_release_latch = None;
+        self.key_wait = None;
+        self.key_wait_parked = false;
+        self.key_wait_frames = 0;
+        self.display.clear();
+        self.display_updated = true;
+        self.display_waited = false;
+        self.display_wait_pending = false;
+        self.planes = 0x01;
+        self.halted = false;
+        self.frame_cycle = 0;
+        self.advance_remainder = 0;
+        self.cycle_count = 0;
+        self.history.clear();
+        self.rewound_rnd.clear();
+        self.load(program);
+    }
+
+    // 16進数の文字列（"00E0 A22A 600C"など）をゲームプログラムとしてロードする
+    // 空白は無視し，2桁ずつ1バイトとして読む．16進数でない文字や奇数の桁数はエラーとなる
+    pub fn load_hex(&mut self, hex: &str) -> Result<(), Chip8Error> {
+        let digits = hex
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| {
+                c.to_digit(16)
+                    .map(|d| d as u8)
+                    .ok_or(ErrorKind::InvalidHexDigit(c))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if !digits.len().is_multiple_of(2) {
+            return Err(ErrorKind::OddHexLength(digits.len()).into());
+        }
+        let program: Vec<u8> = digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect();
+        self.load(&program);
+        Ok(())
+    }
+
+    // 奇数長のROMの扱いを設定
+    pub fn set_odd_length_policy(&mut self, policy: OddLengthPolicy) {
+        self.odd_length_policy = policy;
+    }
+
+    // メモリの範囲外からデータを読み込んだ場合の扱いを設定（既定値は0を返す）
+    //
+    // FX65，5XY3，DRWがメモリの末尾を超えて読み込んだ場合もこの設定に従う（以前のように先頭から読むにはWrapを指定する）．
+    pub fn set_unmapped_read_policy(&mut self, policy: UnmappedReadPolicy) {
+        self.bus.set_unmapped_read(policy);
+    }
+
+    // 1命令を取得し，実行（命令はpcから始まるビッグエンディアンの2バイト）
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        let pc = self.pc;
+        let opcode = self.opcode_at(pc);
+
+        // フックの実行中は取り外しておくため，フックの中から命令を実行してもフックは再度呼ばれない
+        if let (Some(opcode), Some(mut hook)) = (opcode, self.pre_step_hook.take()) {
+            let proceed = hook(self, opcode);
+            if self.pre_step_hook.is_none() {
+                self.pre_step_hook = Some(hook);
+            }
+            if !proceed {
+                return Ok(());
+            }
+        }
+        // フックがメモリを書き換えた場合に備えて読み直す
+        let opcode = self.opcode_at(pc);
+
+        // デバッグモードでは，1命令ずつ巻き戻せるように実行前の状態を保存する
+        // （フックが実行を取りやめた命令やエラーになった命令は履歴に残さない）
+        if self.debug {
+            if self.history.len() == HISTORY_LIMIT {
+                self.history.pop_front();
+            }
+            self.history.push_back(self.snapshot());
+        }
+
+        let mut draws = false;
+        let result = self.fetch().and_then(|instruction| {
+            trace!("{:#05X}: {:?}", pc, instruction);
+            if let Some(hook) = &mut self.trace_hook {
+                hook(pc, opcode.unwrap_or_default(), &instruction);
+            }
+            draws = instruction.modifies_display();
+            self.advance_pc(2)?;
+            self.execute(instruction)
+        });
+        // エラーには発生した命令の位置を記録する
+        let result = result.map_err(|e| e.at(pc, opcode));
+        match &result {
+            Ok(()) => {
+                let cycle = self.cycle_count;
+                self.cycle_count += 1;
+                if let (true, Some(log)) = (draws, &mut self.display_log) {
+                    log.update(&self.display, cycle);
+                }
+                if let (Some(opcode), Some(mut hook)) = (opcode, self.post_step_hook.take()) {
+                    hook(self, opcode);
+                    if self.post_step_hook.is_none() {
+                        self.post_step_hook = Some(hook);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("{}", e);
+                if self.debug {
+                    self.history.pop_back();
+                }
+            }
+        }
+        result
+    }
+
+    // pcの命令を取得し，デコードする
+    fn fetch(&mut self) -> Result<Instruction, Chip8Error> {
+        match self.decode_cache.get(self.pc as usize).copied().flatten() {
+            Some(instruction) => Ok(instruction),
+            None => {
+                let opcode = self.read_opcode(self.pc as usize)?;
+                let instruction = Instruction::decode(opcode, self.variant)
+                    .ok_or(ErrorKind::UnknownOpcode(opcode))?;
+                if let Some(entry) = self.decode_cache.get_mut(self.pc as usize) {
+                    *entry = Some(instruction);
+                }
+                Ok(instruction)
+            }
+        }
+    }
+
+    // デコード結果のキャッシュを設定する
+    // 有効にすると，同じアドレスの命令はメモリへ書き込まれるまで再デコードしない
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache = if enabled {
+            vec![None; self.bus.len()]
+        } else {
+            Vec::new()
+        };
+    }
+
+    // デコード結果のキャッシュを全て破棄する
+    fn clear_decode_cache(&mut self) {
+        self.decode_cache.fill(None);
+    }
+
+    // 直前に実行した1命令を取り消す（取り消せる履歴がなければfalse）
+    pub fn step_back(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(snapshot) => {
+                self.restore(&snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // デバッグモードを設定する（無効にすると履歴を破棄する）
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+        if !debug {
+            self.history.clear();
+            self.rewound_rnd.clear();
+        }
+    }
+
+    // スプライトの描画方法を設定する（ゲームの動作に影響するのはXorのみで，Orはデバッグ用）
+    pub fn set_draw_mode(&mut self, draw_mode: DrawMode) {
+        self.draw_mode = draw_mode;
+    }
+
+    // 1秒あたりに実行する命令数を設定する（1フレームあたりの命令数に丸め，最低でも1命令は実行する）
+    pub fn set_cpu_hz(&mut self, cpu_hz: u32) {
+        let cycles = (cpu_hz + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+        self.cycles_per_frame = cycles.max(1) as usize;
+    }
+
+    // 1秒あたりに実行する命令数
+    pub fn cpu_hz(&self) -> u32 {
+        self.cycles_per_frame as u32 * FRAMES_PER_SECOND
+    }
+
+    // 自分自身へのジャンプ（1NNN）を検出したときに停止するかを設定する
+    pub fn set_halt_on_idle(&mut self, halt_on_idle: bool) {
+        self.halt_on_idle = halt_on_idle;
+    }
+
+    // ブザーを鳴らしているか（サウンドタイマが0でない）
+    pub fn is_beeping(&self) -> bool {
+        self.sound > 0
+    }
+
+    // 前回呼び出してからブザーを鳴らし始めたか（フロントエンドが音の再生を開始するために使う）
+    pub fn take_beep_started(&mut self) -> bool {
+        std::mem::take(&mut self.beep_started)
+    }
+
+    // ディレイタイマとサウンドタイマを0にし，ブザーを止める（他の状態は変更しない）
+    pub fn reset_timers(&mut self) {
+        self.delay = 0;
+        self.sound = 0;
+        self.beep_started = false;
+    }
+
+    // インタプリタの領域（0x000〜0x1FF）への命令による書き込みをエラーにするかを設定する（既定では無効）
+    pub fn set_protect_interpreter_region(&mut self, protect: bool) {
+        self.protect_interpreter_region = protect;
+    }
+
+    // スプライトの各行のビットの並びを反転して（左右反転して）描画するかを設定する（既定では無効）
+    //
+    // フォントやスプライトのビットの向きを確かめるためのデバッグ用の設定で，衝突の判定も反転した画素で行う．
+    pub fn set_flip_sprites(&mut self, flip: bool) {
+        self.flip_sprites = flip;
+    }
+
+    // FX0Aでキー入力を待っているか（キーが入力されてFX0Aを終えるとfalseに戻る）
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait_parked || self.key_wait_frames > 0
+    }
+
+    // プログラムが停止したか
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // スタックの段数を設定する（標準は16段で，それを超える入れ子の呼び出しはエラーとなる）
+    pub fn set_stack_size(&mut self, size: usize) {
+        self.stack.resize(size, 0);
+        self.sp = self.sp.min(size);
+    }
+
+    // エミュレートするCHIP-8の系統を設定する
+    // メモリの大きさは系統に合わせて変わる（小さくなる場合は末尾の内容が失われる）
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+        self.bus.resize(variant.memory_size());
+        if !self.decode_cache.is_empty() {
+            self.decode_cache.resize(self.bus.len(), None);
+        }
+        self.clear_decode_cache();
+    }
+
+    // タイマーの更新（60Hzで呼び出す）
+    fn update_timers(&mut self) {
+        if self.delay > 0 {
+            self.delay -= 1;
+        }
+        if self.sound > 0 {
+            self.sound -= 1;
+        }
+    }
+
+    // ディスプレイの内容
+    pub fn display(&self) -> &LogicalDisplay {
+        &self.display
+    }
+
+    // ディスプレイの内容をASCIIアートにしたもの（点灯している画素は'#'，消灯している画素は'.'）
+    pub fn display_ascii(&self) -> String {
+        let mut out = String::with_capacity((self.display.width() + 1) * self.display.height());
+        for row in self.display.rows() {
+            out.extend(row.iter().map(|&pixel| if pixel != 0 { '#' } else { '.' }));
+            out.push('\n');
+        }
+        out
+    }
+
+    // ディスプレイの内容を1画素1ビットに詰めたもの（各行の左端の画素が先頭バイトの最上位ビット）
+    // 64x32のディスプレイでは256バイトになる
+    pub fn packed_display(&self) -> Vec<u8> {
+        let mut packed = vec![0; self.display.pixels().len().div_ceil(8)];
+        for (i, &pixel) in self.display.pixels().iter().enumerate() {
+            packed[i / 8] |= ((pixel != 0) as u8) << (7 - i % 8);
+        }
+        packed
+    }
+
+    // ディスプレイの内容のハッシュ値
+    // 詰めたディスプレイをFNV-1a（64ビット）でハッシュするため，プラットフォームによらず同じ値になる
+    pub fn frame_hash(&self) -> u64 {
+        fnv1a(&self.packed_display())
+    }
+
+    // メモリの使われ方の概要（フォント，ROM，スタック，I，pc）
+    //
+    // 1行目はメモリ全体をMEMORY_MAP_WIDTH文字に縮めた図で，各文字は次のものを含む範囲を表す
+    // （複数ある場合は左のものを優先する）．
+    // P: pc，I: インデックスレジスタ，R: ROM，F: フォント，.: それ以外
+    pub fn memory_map(&self) -> String {
+        let len = self.bus.len();
+        let rom = PROGRAM_START..PROGRAM_START + self.rom_len;
+        let font = 0..FONTSET.len();
+        let chunk = len.div_ceil(MEMORY_MAP_WIDTH);
+        let overlaps = |range: &std::ops::Range<usize>, start: usize| {
+            range.start < start + chunk && start < range.end
+        };
+        let bar: String = (0..len)
+            .step_by(chunk)
+            .map(|start| {
+                let contains = |addr: usize| (start..start + chunk).contains(&addr);
+                if contains(self.pc as usize) {
+                    'P'
+                } else if contains(self.i as usize) {
+                    'I'
+                } else if overlaps(&rom, start) {
+                    'R'
+                } else if overlaps(&font, start) {
+                    'F'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+
+        let mut lines = vec![format!("[{}]", bar)];
+        lines.push(format!(
+            "font   {:#06X}-{:#06X} ({} bytes)",
+            font.start,
+            font.end - 1,
+            font.len()
+        ));
+        if rom.is_empty() {
+            lines.push("rom    (not loaded)".to_string());
+        } else {
+            lines.push(format!(
+                "rom    {:#06X}-{:#06X} ({} bytes)",
+                rom.start,
+                rom.end - 1,
+                rom.len()
+            ));
+        }
+        lines.push(format!("stack  {}/{}", self.sp, self.stack.len()));
+        lines.push(format!("I      {:#06X}", self.i));
+        lines.push(format!("pc     {:#06X}", self.pc));
+        lines.push(format!("memory {:#06X} bytes", len));
+        lines.join("\n")
+    }
+
+    // 読み込んだROMのハッシュ値（FNV-1a，64ビット）
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    // 乱数のシードを設定し，乱数生成器を初期化する（set_rand_sourceで設定した生成元は置き換えられる）
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Box::new(StdRng::seed_from_u64(seed));
+        self.rewound_rnd.clear();
+    }
+
+    // RNDで使う乱数の生成元を設定する
+    pub fn set_rand_source(&mut self, source: Box<dyn RandSource + Send>) {
+        self.rng = source;
+        self.rewound_rnd.clear();
+    }
+
+    // 乱数のシード
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // プログラムカウンタ
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    // レジスタV0〜VF
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    // インデックスレジスタ
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    // ディレイタイマ
+    pub fn delay_timer(&self) -> u8 {
+        self.delay
+    }
+
+    // サウンドタイマ
+    pub fn sound_timer(&self) -> u8 {
+        self.sound
+    }
+
+    // スタックに積まれているアドレスの数
+    pub fn stack_depth(&self) -> usize {
+        self.sp
+    }
+
+    // メモリの内容
+    pub fn memory(&self) -> &[u8] {
+        self.bus.as_slice()
+    }
+
+    // 指定されたアドレスの命令（メモリの範囲外の場合はNone）
+    pub fn opcode_at(&self, addr: u16) -> Option<u16> {
+        self.read_opcode(addr as usize).ok()
+    }
+
+    // 指定されたアドレスから命令を読み込む
+    // メモリの最後のバイトから始まる命令は，wraps_atの条件を満たせば下位バイトを0番地から読む
+    fn read_opcode(&self, addr: usize) -> Result<u16, Chip8Error> {
+        if self.wraps_at(addr) {
+            let high = self.bus.read(addr)?;
+            let low = self.bus.read(0)?;
+            return Ok((high as u16) << 8 | low as u16);
+        }
+        self.bus.read_u16(addr)
+    }
+
+    // 逆アセンブルで表示するアドレスの名前を設定する
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    // 逆アセンブルで表示するアドレスの名前
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    // pcから最大count個の命令を逆アセンブルし，アドレス，opcode，表記の組を返す
+    // メモリの末尾に達した場合はそこで終わる．解釈できない命令は"(unknown)"と表記する
+    // 名前が登録されているアドレスを参照する命令は，アドレスの代わりに名前で表記する
+    pub fn disassemble_at(&self, pc: u16, count: usize) -> Vec<(u16, u16, String)> {
+        let mut lines = Vec::new();
+        let mut addr = pc as usize;
+        while lines.len() < count {
+            let Ok(opcode) = self.bus.read_u16(addr) else {
+                break;
+            };
+            let text = match Instruction::decode(opcode, self.variant) {
+                // アドレスは次の2バイトにある
+                Some(Instruction::LdILong) => match self.bus.read_u16(addr + 2) {
+                    Ok(long) => {
+                        let text = match self.symbols.get(long) {
+                            Some(name) => format!("LD I, {}", name),
+                            None => format!("LD I, {:#06X}", long),
+                        };
+                        lines.push((addr as u16, opcode, text));
+                        addr += 4;
+                        continue;
+                    }
+                    Err(_) => Instruction::LdILong.to_string(),
+                },
+                Some(instruction) => instruction.to_string_with_symbols(&self.symbols),
+                None => "(unknown)".to_string(),
+            };
+            lines.push((addr as u16, opcode, text));
+            addr += 2;
+        }
+        lines
+    }
+
+    // 指定されたアドレスの値（メモリの範囲外の場合はNone）
+    pub fn peek(&self, addr: u16) -> Option<u8> {
+        self.bus.get(addr as usize)
+    }
+
+    // 指定されたアドレスに値を書き込む（チートやテスト用．フレームの間に呼び出す）
+    pub fn poke(&mut self, addr: u16, value: u8) -> Result<(), Chip8Error> {
+        self.write_memory(addr as usize, value)
+    }
+
+    // Vxの値（レジスタの番号が範囲外の場合はNone）
+    pub fn peek_register(&self, x: usize) -> Option<u8> {
+        self.registers.get(x).copied()
+    }
+
+    // Vxに値を代入する
+    pub fn poke_register(&mut self, x: usize, value: u8) -> Result<(), Chip8Error> {
+        let register = self
+            .registers
+            .get_mut(x)
+            .ok_or(ErrorKind::InvalidRegister(x))?;
+        *register = value;
+        Ok(())
+    }
+
+    // メモリ中でバイト列が現れる先頭アドレスの一覧（重なる一致も含む．空のバイト列は一致しない）
+    pub fn find_in_memory(&self, needle: &[u8]) -> Vec<u16> {
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        self.bus
+            .as_slice()
+            .windows(needle.len())
+            .enumerate()
+            .filter(|(_, window)| *window == needle)
+            .map(|(addr, _)| addr as u16)
+            .collect()
+    }
+
+    // 描画対象のプレーン（ビットマスク）
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    // エミュレートするCHIP-8の系統
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    // デコード済みの命令の実行
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        // 各命令に応じた処理
+        match instruction {
+            Instruction::Sys(nnn) => self.sys(nnn)?,
+            Instruction::Cls => self.cls(),
+            Instruction::Ret => self.ret()?,
+            Instruction::ScrollDown(n) => self.scroll(0, n as isize),
+            Instruction::ScrollUp(n) => self.scroll(0, -(n as isize)),
+            Instruction::ScrollRight => self.scroll(4, 0),
+            Instruction::ScrollLeft => self.scroll(-4, 0),
+            Instruction::Exit => self.exit(),
+            Instruction::Low => self.set_resolution(LORES_WIDTH, LORES_HEIGHT)?,
+            Instruction::High => self.set_resolution(HIRES_WIDTH, HIRES_HEIGHT)?,
+            Instruction::Jp(nnn) => self.jp(nnn)?,
+            Instruction::Call(nnn) => self.call(nnn)?,
+            Instruction::Se(x, kk) => self.se(x, kk)?,
+            Instruction::Sne(x, kk) => self.sne(x, kk)?,
+            Instruction::SeVxVy(x, y) => self.se_vx_vy(x, y)?,
+            Instruction::SaveRange(x, y) => self.save_range(x, y)?,
+            Instruction::LoadRange(x, y) => self.load_range(x, y)?,
+            Instruction::Ld(x, kk) => self.ld(x, kk),
+            Instruction::Add(x, kk) => self.add(x, kk),
+            Instruction::LdVxVy(x, y) => self.ld_vx_vy(x, y),
+            Instruction::Or(x, y) => self.or(x, y),
+            Instruction::And(x, y) => self.and(x, y),
+            Instruction::Xor(x, y) => self.xor(x, y),
+            Instruction::AddVxVy(x, y) => self.add_vx_vy(x, y),
+            Instruction::Sub(x, y) => self.sub(x, y),
+            Instruction::Shr(x, y) => self.shr(x, y),
+            Instruction::Subn(x, y) => self.subn(x, y),
+            Instruction::Shl(x, y) => self.shl(x, y),
+            Instruction::SneVxVy(x, y) => self.sne_vx_vy(x, y)?,
+            Instruction::LdI(nnn) => self.ld_i(nnn),
+            Instruction::LdILong => self.ld_i_long()?,
+            Instruction::JpV0(nnn) => self.jp_v0(nnn)?,
+            Instruction::Rnd(x, kk) => self.rnd(x, kk),
+            Instruction::Drw(x, y, n) => self.drw(x, y, n)?,
+            Instruction::Skp(x) => self.skp(x)?,
+            Instruction::Sknp(x) => self.sknp(x)?,
+            Instruction::LdVxDt(x) => self.ld_vx_dt(x),
+            Instruction::Plane(n) => self.plane(n),
+            Instruction::LdVxK(x) => self.ld_vx_k(x)?,
+            Instruction::LdDtVx(x) => self.ld_dt_vx(x),
+            Instruction::LdStVx(x) => self.ld_st_vx(x),
+            Instruction::AddIVx(x) => self.add_i_vx(x),
+            Instruction::LdFVx(x) => self.ld_f_vx(x),
+            Instruction::LdBVx(x) => self.ld_b_vx(x)?,
+            Instruction::LdIVx(x) => self.ld_i_vx(x)?,
+            Instruction::LdVxI(x) => self.ld_vx_i(x)?,
+        }
+
+        Ok(())
+    }
+
+    // メモリへの書き込み（書き込んだアドレスを含む命令のデコード結果を破棄する）
+    //
+    // 0番地は，メモリの最後のバイトから始まり0番地に折り返す命令の下位バイトにもなる．
+    fn write_memory(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        self.bus.write(addr, value)?;
+        if !self.decode_cache.is_empty() {
+            let len = self.decode_cache.len();
+            self.decode_cache[addr] = None;
+            self.decode_cache[(addr + len - 1) % len] = None;
+        }
+        Ok(())
+    }
+
+    // 命令によるメモリへの書き込み（設定に応じてインタプリタの領域への書き込みをエラーにする）
+    fn store(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        if self.protect_interpreter_region && addr < PROGRAM_START {
+            return Err(ErrorKind::ProtectedWrite(addr).into());
+        }
+        self.write_memory(addr, value)
+    }
+
+    // 飛び先のアドレスを設定に応じて検証する
+    //
+    // 命令を読み込める最後のアドレス（メモリの末尾の2バイト前）より後ろは範囲外とする．
+    fn jump_target(&self, addr: u16) -> Result<u16, Chip8Error> {
+        let last_opcode_addr = (self.bus.len() - 2) as u16;
+        if addr <= last_opcode_addr {
+            return Ok(addr);
+        }
+        match self.quirks.jump {
+            JumpQuirk::Wrap => {
+                let wrapped = self.wrap_addr(addr as usize) as u16;
+                warn!("jump target {:#05X} wrapped to {:#05X}", addr, wrapped);
+                Ok(wrapped)
+            }
+            JumpQuirk::Clamp => {
+                warn!(
+                    "jump target {:#05X} clamped to {:#05X}",
+                    addr, last_opcode_addr
+                );
+                Ok(last_opcode_addr)
+            }
+            JumpQuirk::Error => Err(ErrorKind::JumpOutOfRange(addr).into()),
+        }
+    }
+
+    // 00E0 - CLS: 画面を消去
+    // XO-CHIPでは選択しているプレーンのみを消す
+    fn cls(&mut self) {
+        self.display.clear_planes(self.planes);
+        self.display_updated = true;
+    }
+
+    // 0NNN - SYS addr: 機械語のサブルーチンは実行できないため，設定に応じて無視するかエラーにする
+    fn sys(&mut self, addr: Addr) -> Result<(), Chip8Error> {
+        match self.quirks.sys {
+            SysQuirk::Ignore => {
+                debug!("ignored SYS {:#05X}", addr);
+                Ok(())
+            }
+            SysQuirk::Error => Err(ErrorKind::UnknownOpcode(addr.get()).into()),
+        }
+    }
+
+    // 00CN/00DN/00FB/00FC - SCD/SCU/SCR/SCL: 選択されたプレーンを(dx, dy)だけスクロールする
+    // 低解像度（幅が64画素以下）では設定に応じて移動量を半分にし，移動量が0になる場合は何もしない
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let hires = self.display.width() > LORES_WIDTH;
+        let (dx, dy) = match self.quirks.lores_scroll {
+            LoresScrollQuirk::Half if !hires => (dx / 2, dy / 2),
+            _ => (dx, dy),
+        };
+        if dx == 0 && dy == 0 {
+            return;
+        }
+        let wrap = self.quirks.scroll == ScrollQuirk::Wrap;
+        self.display.scroll(dx, dy, self.planes, wrap);
+        self.display_updated = true;
+    }
+
+    // 00FE/00FF - LOW/HIGH: ディスプレイを低解像度（64x32）または高解像度（128x64）に切り替える
+    // 切り替えた後の画面は全てのプレーンを消去した状態になる（XO-CHIPと同じ）
+    fn set_resolution(&mut self, width: usize, height: usize) -> Result<(), Chip8Error> {
+        self.display = LogicalDisplay::new(width, height)?;
+        self.display_updated = true;
+        Ok(())
+    }
+
+    // 00EE - RET: サブルーチンから復帰
+    fn ret(&mut self) -> Result<(), Chip8Error> {
+        // スタックからアドレスをポップし，プログラムカウンタをセットする
+        if self.sp == 0 {
+            return Err(ErrorKind::StackUnderflow.into());
+        }
+        self.sp -= 1;
+        self.pc = self.stack[self.sp];
+        debug!("return to {:#05X}", self.pc);
+        Ok(())
+    }
+
+    // 00FD - EXIT: プログラムを終了する（run，run_cyclesなどはそこで終わる）
+    fn exit(&mut self) {
+        self.halted = true;
+        debug!("exit at {:#05X}", self.instruction_pc());
+    }
+
+    // 1NNN - JP addr: プログラムカウンタを指定されたアドレスへ移動
+    fn jp(&mut self, addr: Addr) -> Result<(), Chip8Error> {
+        let nnn = addr.get();
+        // 自分自身へのジャンプ（無限ループ）はプログラムの終了とみなせる
+        if self.halt_on_idle && nnn == self.instruction_pc() {
+            self.halted = true;
+        }
+        self.pc = self.jump_target(nnn)?;
+        debug!("jump to {:#05X}", self.pc);
+        Ok(())
+    }
+
+    // 2NNN - CALL addr: サブルーチンを呼び出す
+    fn call(&mut self, addr: Addr) -> Result<(), Chip8Error> {
+        let target = self.jump_target(addr.get())?;
+        debug!("call {:#05X} from {:#05X}", target, self.instruction_pc());
+        // 現在のプログラムカウンタをスタックにプッシュ
+        if self.sp == self.stack.len() {
+            return Err(ErrorKind::StackOverflow.into());
+        }
+        self.stack[self.sp] = self.pc;
+        self.sp += 1;
+        // プログラムカウンタを指定されたアドレスへ移動
+        self.pc = target;
+        Ok(())
+    }
+
+    // 3XKK - SE Vx, byte: Vxと指定された値が等しい場合，プログラムカウンタを2つ進める
+    fn se(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
+        if self.registers[x] == kk {
+            self.skip()?;
+        }
+        Ok(())
+    }
+
+    // 4XKK - SNE Vx, byte: Vxと指定された値が等しくない場合，プログラムカウンタを2つ進める
+    fn sne(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
+        if self.registers[x] != kk {
+            self.skip()?;
+        }
+        Ok(())
+    }
+
+    // 5XY0 - SE Vx, Vy: VxとVyが等しい場合，プログラムカウンタを2つ進める
+    fn se_vx_vy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        if self.registers[x] == self.registers[y] {
+            self.skip()?;
+        }
+        Ok(())
+    }
+
+    // 5XY2 - SAVE Vx - Vy: インデックスレジスタからVxからVyまでのレジスタの値を順番に保存する（x > yの場合は逆順）
+    // メモリの末尾を超えたアドレスは先頭に折り返す
+    fn save_range(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        for (offset, reg) in register_range(x, y).enumerate() {
+            self.store(
+                self.wrap_addr(self.i as usize + offset),
+                self.registers[reg],
+            )?;
+        }
+        Ok(())
+    }
+
+    // 5XY3 - LOAD Vx - Vy: インデックスレジスタからVxからVyまでのレジスタに順番に値を代入する（x > yの場合は逆順）
+    // メモリの末尾を超えたアドレスからの読み込みはUnmappedReadPolicyに従う
+    fn load_range(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        for (offset, reg) in register_range(x, y).enumerate() {
+            self.registers[reg] = self.bus.read(self.i as usize + offset)?;
+        }
+        Ok(())
+    }
+
+    // アドレスをメモリの大きさで折り返す（4KBなら0x1000，XO-CHIPの64KBなら0x10000で折り返す）
+    fn wrap_addr(&self, addr: usize) -> usize {
+        addr % self.bus.len()
+    }
+
+    // 条件付きスキップ命令で次の命令を飛ばす
+    // XO-CHIPでは4バイトの命令（F000 NNNN）を丸ごと飛ばす
+    fn skip(&mut self) -> Result<(), Chip8Error> {
+        if self.variant == Variant::XoChip && self.opcode_at(self.pc) == Some(0xF000) {
+            self.advance_pc(4)
+        } else {
+            self.advance_pc(2)
+        }
+    }
+
+    // メモリの最後のバイトから始まる命令を，0番地に折り返して読むか
+    //
+    // pc_wrapが有効な場合に加えて，JumpQuirk::Wrapでも折り返す．Wrapは12ビットのアドレスで
+    // 折り返すCOSMAC VIPの挙動なので，0xFFFへのジャンプでは0xFFFと0x000を1つの命令として読み，
+    // 次の命令は0x001から読む（範囲外の読み込みでエラーにはならない）．
+    fn wraps_at(&self, addr: usize) -> bool {
+        addr + 1 == self.bus.len() && (self.quirks.pc_wrap || self.quirks.jump == JumpQuirk::Wrap)
+    }
+
+    // プログラムカウンタをbytesだけ進める
+    // pc_wrapが有効な場合と，メモリの最後のバイトから始まる命令を折り返して読んだ場合は0に折り返す
+    // それ以外でpcが16ビットを超える場合（XO-CHIPの64KBの末尾）はMemoryOutOfRangeエラーとなる
+    fn advance_pc(&mut self, bytes: u16) -> Result<(), Chip8Error> {
+        if self.quirks.pc_wrap || self.wraps_at(self.pc as usize) {
+            self.pc = self.wrap_addr(self.pc as usize + bytes as usize) as u16;
+        } else {
+            self.pc = self
+                .pc
+                .checked_add(bytes)
+                .ok_or(ErrorKind::MemoryOutOfRange(
+                    self.pc as usize + bytes as usize,
+                ))?;
+        }
+        Ok(())
+    }
+
+    // 実行中の命令のアドレス（命令の実行中はpcが既に次の命令を指している）
+    // pcが進めた際に0へ折り返していても，メモリの大きさで折り返して戻す
+    fn instruction_pc(&self) -> u16 {
+        self.wrap_addr(self.pc as usize + self.bus.len() - 2) as u16
+    }
+
+    // 実行中の命令を次の命令でもう一度実行するように，プログラムカウンタを戻す
+    fn repeat_instruction(&mut self) {
+        self.pc = self.instruction_pc();
+    }
+
+    // 6XKK - LD Vx, byte: Vxに指定された値を代入する
+    fn ld(&mut self, x: Vreg, kk: u8) {
+        self.registers[x.index()] = kk;
+    }
+
+    // 7XKK - ADD Vx, byte: Vxに指定された値を加える
+    fn add(&mut self, x: Vreg, kk: u8) {
+        let x = x.index();
+        self.registers[x] = self.registers[x].wrapping_add(kk);
+    }
+
+    // 8XY0 - LD Vx, Vy: VxにVyを代入する
+    fn ld_vx_vy(&mut self, x: usize, y: usize) {
+        self.registers[x] = self.registers[y];
+    }
+
+    // 8XY1 - OR Vx, Vy: VxにVx OR Vyを代入する
+    fn or(&mut self, x: usize, y: usize) {
+        self.registers[x] |= self.registers[y];
+        self.reset_vf_after_logic();
+    }
+
+    // 8XY2 - AND Vx, Vy: VxにVx AND Vyを代入する
+    fn and(&mut self, x: usize, y: usize) {
+        self.registers[x] &= self.registers[y];
+        self.reset_vf_after_logic();
+    }
+
+    // 8XY3 - XOR Vx, Vy: VxにVx XOR Vyを代入する
+    fn xor(&mut self, x: usize, y: usize) {
+        self.registers[x] ^= self.registers[y];
+        self.reset_vf_after_logic();
+    }
+
+    // 8XY1/8XY2/8XY3の後処理: 設定に応じてVFを0にリセットする
+    fn reset_vf_after_logic(&mut self) {
+        if self.quirks.logic == LogicQuirk::ResetVf {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    // 8XY4〜8XYEの算術命令は，演算前の値から結果とフラグを求めてからVx，VFの順に書き込む．
+    // そのため，xが0xFの場合は演算の結果ではなくフラグがVFの最終的な値になる．
+
+    // 8XY4 - ADD Vx, Vy: VxにVx + Vyを代入する
+    fn add_vx_vy(&mut self, x: usize, y: usize) {
+        let (result, overflow) = self.registers[x].overflowing_add(self.registers[y]);
+        self.registers[x] = result;
+        self.registers[0xF] = if overflow { 1 } else { 0 };
+    }
+
+    // 8XY5 - SUB Vx, Vy: VxからVyを引いた値をVxに代入する
+    fn sub(&mut self, x: usize, y: usize) {
+        let (vx, vy) = (self.registers[x], self.registers[y]);
+        self.registers[x] = vx.wrapping_sub(vy);
+        self.registers[0xF] = if vx > vy { 1 } else { 0 };
+    }
+
+    // 8XY6 - SHR Vx {, Vy}: Vx（設定によってはVy）を右シフトした値をVxに代入し，VFにシフトで溢れたビットを代入する
+    fn shr(&mut self, x: usize, y: usize) {
+        let value = self.shift_source(x, y);
+        let flag = value & 0x01;
+        self.registers[x] = value >> 1;
+        self.registers[0xF] = flag;
+    }
+
+    // 8XY7 - SUBN Vx, Vy: VyからVxを引いた値をVxに代入する
+    fn subn(&mut self, x: usize, y: usize) {
+        let (vx, vy) = (self.registers[x], self.registers[y]);
+        self.registers[x] = vy.wrapping_sub(vx);
+        self.registers[0xF] = if vy > vx { 1 } else { 0 };
+    }
+
+    // 8XYE - SHL Vx {, Vy}: Vx（設定によってはVy）を左シフトした値をVxに代入し，VFにシフトで溢れたビットを代入する
+    fn shl(&mut self, x: usize, y: usize) {
+        let value = self.shift_source(x, y);
+        let flag = (value & 0x80) >> 7;
+        self.registers[x] = value << 1;
+        self.registers[0xF] = flag;
+    }
+
+    // シフト命令で使う値（設定に応じてVxまたはVy）
+    //
+    // 溢れるビットはVxに代入する前のこの値から求め，VFへの代入は最後に行う．
+    // そのため，x == yではどちらの設定でも同じ結果になり，x == 0xFではVFに溢れたビットが残る．
+    fn shift_source(&self, x: usize, y: usize) -> u8 {
+        match self.quirks.shift {
+            ShiftQuirk::VxOnly => self.registers[x],
+            ShiftQuirk::Vy => self.registers[y],
+        }
+    }
+
+    // 9XY0 - SNE Vx, Vy: VxとVyが等しくない場合，プログラムカウンタを2つ進める
+    fn sne_vx_vy(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        if self.registers[x] != self.registers[y] {
+            self.skip()?;
+        }
+        Ok(())
+    }
+
+    // ANNN - LD I, addr: インデックスレジスタに指定された値を代入する
+    fn ld_i(&mut self, addr: Addr) {
+        self.i = addr.get();
+    }
+
+    // F000 NNNN - LD I, long addr: インデックスレジスタに次の2バイトの値を代入する（XO-CHIP）
+    fn ld_i_long(&mut self) -> Result<(), Chip8Error> {
+        let addr = self
+            .opcode_at(self.pc)
+            .ok_or(ErrorKind::MemoryOutOfRange(self.pc as usize + 1))?;
+        self.i = self.wrap_addr(addr as usize) as u16;
+        self.advance_pc(2)
+    }
+
+    // BNNN - JP V0, addr: V0と指定された値を加えた値をプログラムカウンタに代入する
+    fn jp_v0(&mut self, nnn: u16) -> Result<(), Chip8Error> {
+        self.pc = self.jump_target(self.registers[0] as u16 + nnn)?;
+        debug!("jump to {:#05X}", self.pc);
+        Ok(())
+    }
+
+    // CXKK - RND Vx, byte: 0から255までのランダムな値と指定された値をANDし，Vxに代入する
+    fn rnd(&mut self, x: usize, kk: u8) {
+        let raw = self.next_rnd();
+        let result = raw & kk;
+        self.registers[x] = result;
+        let pc = self.instruction_pc();
+        if let Some(hook) = &mut self.rnd_hook {
+            hook(RndRecord {
+                pc,
+                raw,
+                kk,
+                result,
+            });
+        }
+    }
+
+    // DXYN - DRW Vx, Vy, nibble: Vx, Vyからインデックスレジスタに保持されたアドレスからnibble個分のデータを取得し，画面上に描画する
+    //
+    // 処理は次の順序で行う．
+    // 1. Vx, Vyを読み，開始座標を画面の大きさで折り返す（VFを座標に使った場合も描画前の値を使う）
+    // 2. 全ての行を描画し，衝突の有無をまとめて記録する
+    // 3. 最後に一度だけVFに衝突の結果を代入する（描画の途中でVFは変化しない）
+    //
+    // n = 0の場合の挙動は系統によって異なる
+    // - CHIP-8: 何もしない（VFも変更しない）
+    // - SUPER-CHIP，XO-CHIP: 16x16のスプライトを描画する（1行2バイトで32バイト）
+    fn drw(&mut self, x: usize, y: usize, n: usize) -> Result<(), Chip8Error> {
+        if n == 0 && self.variant == Variant::Chip8 {
+            return Ok(());
+        }
+        let (rows, row_bytes) = if n == 0 { (16, 2) } else { (n, 1) };
+        let sprite_width = row_bytes * 8;
+
+        // 垂直帰線期間を待つ場合，フレームで最初のDRWは次のフレームの開始まで同じ命令を繰り返す
+        // 待ち終えたDRWとそのフレームの残りのDRWはすぐに描画する
+        if self.quirks.display_wait == DisplayWaitQuirk::Vblank && !self.display_waited {
+            self.display_wait_pending = true;
+            self.repeat_instruction();
+            return Ok(());
+        }
+
+        // Vx, Vyの座標にスプライトを描画する（座標の折り返しや切り捨てはFramebuffer::draw_sprite）
+        // 複数のプレーンを選択している場合は，プレーン1，プレーン2の順にスプライトを読み込む
+        let (x, y) = (self.registers[x] as usize, self.registers[y] as usize);
+        let edges = (self.quirks.h_edge, self.quirks.v_edge);
+        let mut collision = false;
+        let mut sprite_addr = self.i as usize;
+        for plane in [0x01, 0x02] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+
+            let mut lines = [0u16; 16];
+            for (i, line) in lines.iter_mut().take(rows).enumerate() {
+                for b in 0..row_bytes {
+                    let addr = sprite_addr + i * row_bytes + b;
+                    *line = *line << 8 | self.bus.read(addr)? as u16;
+                }
+                if self.flip_sprites {
+                    *line = line.reverse_bits() >> (16 - sprite_width);
+                }
+            }
+            let sprite = Sprite {
+                rows: &lines[..rows],
+                width: sprite_width,
+            };
+            collision |= self
+                .display
+                .draw_sprite(x, y, sprite, plane, edges, self.draw_mode);
+            sprite_addr += rows * row_bytes;
+        }
+
+        self.display_updated = true;
+
+        // 衝突が発生したかどうかをVFに代入する（VFへの書き込みはここだけ）
+        self.registers[0xF] = if collision { 1 } else { 0 };
+        Ok(())
+    }
+
+    // FN01 - PLANE n: 描画対象のプレーンを選択する（XO-CHIP）
+    fn plane(&mut self, n: u8) {
+        self.planes = n & 0x03;
+    }
+
+    // EX9E - SKP Vx: キーボードのVx番目のキーが押されている場合，プログラムカウンタを2つ進める
+    // キーは16個しかないため，Vxの下位4ビットだけを使う
+    fn skp(&mut self, x: Vreg) -> Result<(), Chip8Error> {
+        if self.keyboard[self.registers[x.index()] as usize & 0xF] {
+            self.skip()?;
+        }
+        Ok(())
+    }
+
+    // EXA1 - SKNP Vx: キーボードのVx番目のキーが押されていない場合，プログラムカウンタを2つ進める
+    fn sknp(&mut self, x: Vreg) -> Result<(), Chip8Error> {
+        if !self.keyboard[self.registers[x.index()] as usize & 0xF] {
+            self.skip()?;
+        }
+        Ok(())
+    }
+
+    // FX07 - LD Vx, DT: Vxにデルタタイムを代入する
+    fn ld_vx_dt(&mut self, x: usize) {
+        self.registers[x] = self.delay;
+    }
+
+    // FX0A - LD Vx, K: キー入力を待つ
+    fn ld_vx_k(&mut self, x: usize) -> Result<(), Chip8Error> {
+        let button_pressed = match self.quirks.key_wait {
+            KeyWaitQuirk::OnPress => self.pressed_key(),
+            KeyWaitQuirk::OnRelease => self.released_key(),
+        };
+
+        match button_pressed {
+            Some(i) => {
+                self.registers[x] = i;
+                self.key_wait_frames = 0;
+            }
+            // ボタンが押されるまで，同じ命令を繰り返し実行して待つ
+            None => {
+                if let Some(timeout) = self.key_wait_timeout {
+                    if self.key_wait_frames >= timeout {
+                        return Err(ErrorKind::KeyWaitTimeout(timeout).into());
+                    }
+                }
+                self.key_wait_parked = true;
+                self.repeat_instruction();
+            }
+        }
+        Ok(())
+    }
+
+    // このフレームで押されたキー（既に離されていてもよい），または押されているキーを取得する
+    fn pressed_key(&mut self) -> Option<u8> {
+        self.key_latch
+            .take()
+            .or_else(|| self.keyboard.iter().position(|&b| b).map(|i| i as u8))
+    }
+
+    // 押されていたキーが離された場合に，そのキーを取得する
+    fn released_key(&mut self) -> Option<u8> {
+        // このフレームで押されて離されたキー
+        if let Some(key) = self.key_release_latch.take() {
+            self.key_wait = None;
+            return Some(key);
+        }
+
+        match self.key_wait {
+            // 待っていたキーが離された
+            Some(key) if !self.keyboard[key as usize] => {
+                self.key_wait = None;
+                Some(key)
+            }
+            Some(_) => None,
+            // 押されているキーがあれば，それが離されるのを待つ
+            None => {
+                self.key_wait = self.pressed_key();
+                None
+            }
+        }
+    }
+
+    // FX15 - LD DT, Vx: デルタタイムにVxを代入する
+    // 0を代入した場合，直後のFX07は同じフレーム内でも0を読む
+    fn ld_dt_vx(&mut self, x: usize) {
+        self.delay = self.registers[x];
+    }
+
+    // FX18 - LD ST, Vx: サウンドタイマにVxを代入する
+    // 0を代入した場合はブザーを鳴らさない（鳴っていた場合はすぐに止まる）
+    fn ld_st_vx(&mut self, x: usize) {
+        if self.sound == 0 && self.registers[x] > 0 {
+            self.beep_started = true;
+        }
+        self.sound = self.registers[x];
+    }
+
+    // FX1E - ADD I, Vx: インデックスレジスタにVxを加える
+    fn add_i_vx(&mut self, x: usize) {
+        self.i = self.wrap_addr(self.i as usize + self.registers[x] as usize) as u16;
+    }
+
+    // FX29 - LD F, Vx: インデックスレジスタにVx番目のフォントを代入する
+    fn ld_f_vx(&mut self, x: usize) {
+        self.i = (self.registers[x] as usize * 5) as u16;
+    }
+
+    // FX33 - LD B, Vx: Vxの百の位，十の位，一の位をI，I+1，I+2に書き込む
+    // メモリの末尾を超えたアドレスは先頭に折り返す（I = 0xFFEなら0xFFE，0xFFF，0x000）
+    fn ld_b_vx(&mut self, x: usize) -> Result<(), Chip8Error> {
+        let value = self.registers[x];
+        let i = self.i as usize;
+        self.store(self.wrap_addr(i), value / 100)?;
+        self.store(self.wrap_addr(i + 1), (value / 10) % 10)?;
+        self.store(self.wrap_addr(i + 2), value % 10)
+    }
+
+    // FX55 - LD [I], Vx: インデックスレジスタからV0からVxまでのレジスタの値を順番に保存する
+    fn ld_i_vx(&mut self, x: usize) -> Result<(), Chip8Error> {
+        for i in 0..=x {
+            self.store(self.wrap_addr(self.i as usize + i), self.registers[i])?;
+        }
+        self.advance_i_after_load_store(x);
+        Ok(())
+    }
+
+    // FX65 - LD Vx, [I]: インデックスレジスタからV0からVxまでのレジスタに順番に値を代入する
+    fn ld_vx_i(&mut self, x: usize) -> Result<(), Chip8Error> {
+        for i in 0..=x {
+            self.registers[i] = self.bus.read(self.i as usize + i)?;
+        }
+        self.advance_i_after_load_store(x);
+        Ok(())
+    }
+
+    // FX55/FX65の後処理: 設定に応じてIを転送したレジスタの数だけ進める（メモリの大きさで折り返す）
+    fn advance_i_after_load_store(&mut self, x: usize) {
+        if self.quirks.load_store == LoadStoreQuirk::IncrementI {
+            self.i = self.wrap_addr(self.i as usize + x + 1) as u16;
+        }
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Chip8 {
+        Chip8::new()
+    }
+}
+
+// VxからVyまでのレジスタの番号（x > yの場合は降順）
+fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+    (0..=x.abs_diff(y)).map(move |k| if x <= y { x + k } else { x - k })
+}
+
+// FNV-1a（64ビット）によるハッシュ値（プラットフォームによらず同じ値になる）
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use crate::{Chip8Error, ErrorKind};
+
+// 2バイトの命令
+//
+// 命令に含まれるレジスタの番号や即値を取り出す．
+// x, yはレジスタの番号，nは4ビット，nnnは12ビット，kkは8ビットの即値を表す
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Opcode(pub u16);
+
+impl Opcode {
+    // 上位4ビット（命令の種類）
+    pub fn high_nibble(self) -> u8 {
+        (self.0 >> 12) as u8
+    }
+
+    // 上位から2番目の4ビット（レジスタVxの番号）
+    pub fn x(self) -> usize {
+        ((self.0 & 0x0F00) >> 8) as usize
+    }
+
+    // 上位から3番目の4ビット（レジスタVyの番号）
+    pub fn y(self) -> usize {
+        ((self.0 & 0x00F0) >> 4) as usize
+    }
+
+    // 下位4ビット
+    pub fn n(self) -> usize {
+        (self.0 & 0x000F) as usize
+    }
+
+    // 下位12ビット（アドレス）
+    pub fn nnn(self) -> u16 {
+        self.0 & 0x0FFF
+    }
+
+    // 下位8ビット
+    pub fn kk(self) -> u8 {
+        (self.0 & 0x00FF) as u8
+    }
+
+    // 上位から2番目の4ビットをレジスタとして取り出す
+    pub fn vx(self) -> Vreg {
+        Vreg(((self.0 & 0x0F00) >> 8) as u8)
+    }
+
+    // 上位から3番目の4ビットをレジスタとして取り出す
+    pub fn vy(self) -> Vreg {
+        Vreg(((self.0 & 0x00F0) >> 4) as u8)
+    }
+
+    // 下位12ビットをアドレスとして取り出す
+    pub fn addr(self) -> Addr {
+        Addr(self.0 & 0x0FFF)
+    }
+}
+
+impl From<u16> for Opcode {
+    fn from(opcode: u16) -> Opcode {
+        Opcode(opcode)
+    }
+}
+
+// レジスタV0〜VFの番号
+//
+// 範囲外の番号は作成時にエラーとなるため，レジスタの配列の添字として安全に使える．
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Vreg(u8);
+
+impl Vreg {
+    // 番号が0〜15であればレジスタを作成する
+    pub fn new(index: usize) -> Result<Vreg, Chip8Error> {
+        if index < 16 {
+            Ok(Vreg(index as u8))
+        } else {
+            Err(ErrorKind::InvalidRegister(index).into())
+        }
+    }
+
+    // レジスタの番号
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// デバッグ表示では整数と同じく値だけを表示する（Instructionの表示を変えないため）
+impl fmt::Debug for Vreg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+// "V{:X}"のように番号を16進数で書けるようにする
+impl fmt::UpperHex for Vreg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+// 命令に含まれる12ビットのアドレス
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Addr(u16);
+
+impl Addr {
+    // 12ビットに収まればアドレスを作成する
+    pub fn new(addr: u16) -> Result<Addr, Chip8Error> {
+        if addr <= 0x0FFF {
+            Ok(Addr(addr))
+        } else {
+            Err(ErrorKind::MemoryOutOfRange(addr as usize).into())
+        }
+    }
+
+    // アドレスの値
+    pub fn get(self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Debug for Addr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for Addr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_extracted_from_the_opcode() {
+        let opcode = Opcode(0xABCD);
+        assert_eq!(opcode.high_nibble(), 0xA);
+        assert_eq!(opcode.x(), 0xB);
+        assert_eq!(opcode.y(), 0xC);
+        assert_eq!(opcode.n(), 0xD);
+        assert_eq!(opcode.nnn(), 0xBCD);
+        assert_eq!(opcode.kk(), 0xCD);
+        assert_eq!(opcode.vx().index(), 0xB);
+        assert_eq!(opcode.vy().index(), 0xC);
+        assert_eq!(opcode.addr().get(), 0xBCD);
+    }
+
+    #[test]
+    fn vreg_rejects_out_of_range_indices() {
+        assert_eq!(Vreg::new(0xF).unwrap().index(), 0xF);
+        let err = Vreg::new(16).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidRegister(16));
+    }
+
+    #[test]
+    fn addr_rejects_values_over_12_bits() {
+        assert_eq!(Addr::new(0x0FFF).unwrap().get(), 0x0FFF);
+        let err = Addr::new(0x1000).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::MemoryOutOfRange(0x1000));
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// パッチファイルの先頭と末尾の印
+const HEADER: &[u8] = b"PATCH";
+const FOOTER: &[u8] = b"EOF";
+
+// ROMの一部を置き換えるパッチ（IPS形式）
+//
+// パッチファイルは"PATCH"で始まり，"EOF"で終わる．その間に次のレコードを並べる（数値はビッグエンディアン）．
+// - オフセット（3バイト），長さ（2バイト），置き換えるバイト列（長さ分）
+// - 長さが0の場合は，繰り返す回数（2バイト）と繰り返すバイト（1バイト）
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Patch {
+    pub records: Vec<PatchRecord>, // ファイルに書かれた順のレコード
+}
+
+// ROMのoffsetからをbytesで置き換えるレコード
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchRecord {
+    pub offset: usize,  // ROMの先頭からのオフセット
+    pub bytes: Vec<u8>, // 置き換えるバイト列
+}
+
+impl Patch {
+    // パッチファイルの内容を解析する
+    pub fn parse(data: &[u8]) -> Result<Patch, String> {
+        let mut rest = data
+            .strip_prefix(HEADER)
+            .ok_or("missing \"PATCH\" header")?;
+        let mut records = Vec::new();
+        loop {
+            if rest.starts_with(FOOTER) {
+                return Ok(Patch { records });
+            }
+            let offset = take(&mut rest, 3).ok_or("truncated record offset")?;
+            let offset =
+                (offset[0] as usize) << 16 | (offset[1] as usize) << 8 | offset[2] as usize;
+            let bytes = match take_u16(&mut rest).ok_or("truncated record length")? {
+                // 長さが0のレコードは同じバイトの繰り返し
+                0 => {
+                    let count = take_u16(&mut rest).ok_or("truncated run length")?;
+                    let value = take(&mut rest, 1).ok_or("truncated run value")?[0];
+                    vec![value; count as usize]
+                }
+                len => take(&mut rest, len as usize)
+                    .ok_or_else(|| format!("truncated record at offset {:#06X}", offset))?
+                    .to_vec(),
+            };
+            records.push(PatchRecord { offset, bytes });
+        }
+    }
+
+    // パッチファイルを読み込む
+    pub fn load(path: &Path) -> io::Result<Patch> {
+        Patch::parse(&fs::read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+// ROMにパッチを適用する
+//
+// レコードは順に適用し，ROMの末尾を超える部分はROMを延長する．
+// ただし，ROMの末尾より後ろから始まるレコード（間が空いてしまうもの）はエラーとし，その場合ROMは変更しない．
+pub fn apply_patch(rom: &mut Vec<u8>, patch: &Patch) -> Result<(), String> {
+    let mut patched = rom.clone();
+    for record in &patch.records {
+        if record.offset > patched.len() {
+            return Err(format!(
+                "record at offset {:#06X} starts beyond the end of the ROM ({} bytes)",
+                record.offset,
+                patched.len()
+            ));
+        }
+        let end = record.offset + record.bytes.len();
+        if end > patched.len() {
+            patched.resize(end, 0);
+        }
+        patched[record.offset..end].copy_from_slice(&record.bytes);
+    }
+    *rom = patched;
+    Ok(())
+}
+
+// 先頭のlenバイトを取り出す（足りなければNone）
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if data.len() < len {
+        return None;
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Some(head)
+}
+
+fn take_u16(data: &mut &[u8]) -> Option<u16> {
+    take(data, 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn patch_changes_one_opcode() {
+        // 2番目の命令（オフセット2）を6042から6099に置き換える
+        let mut data = b"PATCH".to_vec();
+        data.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x02, 0x60, 0x99]);
+        data.extend_from_slice(b"EOF");
+        let patch = Patch::parse(&data).unwrap();
+
+        let mut rom = vec![0x00, 0xE0, 0x60, 0x42, 0x12, 0x04];
+        apply_patch(&mut rom, &patch).unwrap();
+        assert_eq!(rom, [0x00, 0xE0, 0x60, 0x99, 0x12, 0x04]);
+
+        let mut chip8 = Chip8::new();
+        chip8.load(&rom);
+        assert_eq!(chip8.peek(0x202), Some(0x60));
+        assert_eq!(chip8.peek(0x203), Some(0x99));
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.registers()[0], 0x99);
+    }
+
+    #[test]
+    fn record_beyond_the_end_is_rejected() {
+        let patch = Patch {
+            records: vec![PatchRecord {
+                offset: 8,
+                bytes: vec![0xFF],
+            }],
+        };
+        let mut rom = vec![0x00; 4];
+        assert!(apply_patch(&mut rom, &patch).is_err());
+        assert_eq!(rom, [0x00; 4]);
+    }
+}
@@ -0,0 +1,219 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    DisplayWaitQuirk, EdgeQuirk, JumpQuirk, KeyWaitQuirk, LoadStoreQuirk, LogicQuirk,
+    LoresScrollQuirk, Quirks, ScrollQuirk, ShiftQuirk, SysQuirk, Variant,
+};
+
+// 対象とするインタプリタごとの推奨設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuirkProfile {
+    CosmacVip, // COSMAC VIPのインタプリタ
+    SuperChip, // SUPER-CHIP 1.1
+    XoChip,    // XO-CHIP
+}
+
+impl QuirkProfile {
+    // エミュレートするCHIP-8の系統
+    pub fn variant(self) -> Variant {
+        match self {
+            QuirkProfile::CosmacVip => Variant::Chip8,
+            QuirkProfile::SuperChip => Variant::SuperChip,
+            QuirkProfile::XoChip => Variant::XoChip,
+        }
+    }
+
+    // 互換性に関わる挙動の設定
+    pub fn quirks(self) -> Quirks {
+        match self {
+            QuirkProfile::CosmacVip => Quirks {
+                logic: LogicQuirk::ResetVf,
+                jump: JumpQuirk::Wrap,
+                h_edge: EdgeQuirk::Clip,
+                v_edge: EdgeQuirk::Clip,
+                key_wait: KeyWaitQuirk::OnRelease,
+                shift: ShiftQuirk::Vy,
+                load_store: LoadStoreQuirk::IncrementI,
+                scroll: ScrollQuirk::Blank,
+                lores_scroll: LoresScrollQuirk::Full,
+                display_wait: DisplayWaitQuirk::Vblank,
+                sys: SysQuirk::Ignore,
+                pc_wrap: false,
+            },
+            QuirkProfile::SuperChip => Quirks {
+                logic: LogicQuirk::LeaveVf,
+                jump: JumpQuirk::Wrap,
+                h_edge: EdgeQuirk::Clip,
+                v_edge: EdgeQuirk::Clip,
+                key_wait: KeyWaitQuirk::OnPress,
+                shift: ShiftQuirk::VxOnly,
+                load_store: LoadStoreQuirk::KeepI,
+                scroll: ScrollQuirk::Blank,
+                lores_scroll: LoresScrollQuirk::Half,
+                display_wait: DisplayWaitQuirk::Immediate,
+                sys: SysQuirk::Ignore,
+                pc_wrap: false,
+            },
+            QuirkProfile::XoChip => Quirks {
+                logic: LogicQuirk::LeaveVf,
+                jump: JumpQuirk::Wrap,
+                h_edge: EdgeQuirk::Wrap,
+                v_edge: EdgeQuirk::Wrap,
+                key_wait: KeyWaitQuirk::OnPress,
+                shift: ShiftQuirk::Vy,
+                load_store: LoadStoreQuirk::IncrementI,
+                scroll: ScrollQuirk::Blank,
+                lores_scroll: LoresScrollQuirk::Full,
+                display_wait: DisplayWaitQuirk::Immediate,
+                sys: SysQuirk::Ignore,
+                pc_wrap: false,
+            },
+        }
+    }
+}
+
+// 互換性に関わる挙動の設定の値（diff_profilesで使う）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuirkValue {
+    Logic(LogicQuirk),
+    Jump(JumpQuirk),
+    Edge(EdgeQuirk),
+    KeyWait(KeyWaitQuirk),
+    Shift(ShiftQuirk),
+    LoadStore(LoadStoreQuirk),
+    Scroll(ScrollQuirk),
+    LoresScroll(LoresScrollQuirk),
+    DisplayWait(DisplayWaitQuirk),
+    Sys(SysQuirk),
+    Bool(bool),
+}
+
+// 2つの推奨設定で異なる項目を，項目名（設定ファイルのキー）とそれぞれの値の組で返す
+pub fn diff_profiles(
+    a: QuirkProfile,
+    b: QuirkProfile,
+) -> Vec<(&'static str, QuirkValue, QuirkValue)> {
+    quirk_values(a.quirks())
+        .into_iter()
+        .zip(quirk_values(b.quirks()))
+        .filter(|((_, a), (_, b))| a != b)
+        .map(|((name, a), (_, b))| (name, a, b))
+        .collect()
+}
+
+// 設定の各項目の名前と値
+fn quirk_values(quirks: Quirks) -> [(&'static str, QuirkValue); 12] {
+    [
+        ("logic", QuirkValue::Logic(quirks.logic)),
+        ("jump", QuirkValue::Jump(quirks.jump)),
+        ("h_edge", QuirkValue::Edge(quirks.h_edge)),
+        ("v_edge", QuirkValue::Edge(quirks.v_edge)),
+        ("key_wait", QuirkValue::KeyWait(quirks.key_wait)),
+        ("shift", QuirkValue::Shift(quirks.shift)),
+        ("load_store", QuirkValue::LoadStore(quirks.load_store)),
+        ("scroll", QuirkValue::Scroll(quirks.scroll)),
+        ("lores_scroll", QuirkValue::LoresScroll(quirks.lores_scroll)),
+        ("display_wait", QuirkValue::DisplayWait(quirks.display_wait)),
+        ("sys", QuirkValue::Sys(quirks.sys)),
+        ("pc_wrap", QuirkValue::Bool(quirks.pc_wrap)),
+    ]
+}
+
+// 8XY6がVyをシフトするかを確認するROM（Vyをシフトする場合は"1"，しない場合は"0"を左上に描画する）
+//
+//     0x200: LD V1, 0x81; LD V0, 0; SHR V0, V1
+//     0x206: LD V2, 0; SE V0, 0x40; JP 0x20E; LD V2, 1
+//     0x20E: LD F, V2; LD V3, 0; DRW V3, V3, 5; JP 0x214
+pub const SHIFT_VY_TEST_ROM: [u8; 22] = [
+    0x61, 0x81, 0x60, 0x00, 0x80, 0x16, 0x62, 0x00, 0x30, 0x40, 0x12, 0x0E, 0x62, 0x01, 0xF2, 0x29,
+    0x63, 0x00, 0xD3, 0x35, 0x12, 0x14,
+];
+
+// 既知のROMのSHA-256（小文字の16進数）と推奨設定
+//
+// 動作を確認したROMのみを登録する．
+const KNOWN_ROMS: &[(&str, QuirkProfile)] = &[
+    // SHIFT_VY_TEST_ROM（COSMAC VIPの挙動を確認するためのもの）
+    (
+        "3cab7486038003273afa20305bff2c1b75782baef3240264764991127c2a76ed",
+        QuirkProfile::CosmacVip,
+    ),
+];
+
+// ROMのSHA-256を小文字の16進数で返す
+pub fn rom_sha256(rom: &[u8]) -> String {
+    Sha256::digest(rom)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// 既知のROMであれば推奨設定を返す
+pub fn recommend_profile(rom: &[u8]) -> Option<QuirkProfile> {
+    let hash = rom_sha256(rom);
+    KNOWN_ROMS
+        .iter()
+        .find(|(known, _)| *known == hash)
+        .map(|&(_, profile)| profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8Builder;
+
+    #[test]
+    fn known_rom_recommends_its_profile() {
+        assert_eq!(
+            recommend_profile(&SHIFT_VY_TEST_ROM),
+            Some(QuirkProfile::CosmacVip)
+        );
+        assert_eq!(recommend_profile(&[0x12, 0x00]), None);
+    }
+
+    #[test]
+    fn known_rom_hashes_match_sha256() {
+        assert_eq!(
+            rom_sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(rom_sha256(&SHIFT_VY_TEST_ROM), KNOWN_ROMS[0].0);
+    }
+
+    #[test]
+    fn shift_test_rom_draws_one_under_its_profile() {
+        // 推奨設定では"1"，SUPER-CHIPの設定では"0"を描画する
+        for (profile, digit) in [(QuirkProfile::CosmacVip, 1), (QuirkProfile::SuperChip, 0)] {
+            let mut chip8 = Chip8Builder::new()
+                .variant(profile.variant())
+                .quirks(profile.quirks())
+                .build()
+                .unwrap();
+            chip8.load(&SHIFT_VY_TEST_ROM);
+            for _ in 0..10 {
+                chip8.step().unwrap();
+            }
+            assert_eq!(chip8.registers()[0x2], digit, "{:?}", profile);
+        }
+    }
+
+    #[test]
+    fn vip_and_super_chip_differ_in_shift_and_load_store() {
+        let diff = diff_profiles(QuirkProfile::CosmacVip, QuirkProfile::SuperChip);
+        assert!(diff.contains(&(
+            "shift",
+            QuirkValue::Shift(ShiftQuirk::Vy),
+            QuirkValue::Shift(ShiftQuirk::VxOnly)
+        )));
+        assert!(diff.contains(&(
+            "load_store",
+            QuirkValue::LoadStore(LoadStoreQuirk::IncrementI),
+            QuirkValue::LoadStore(LoadStoreQuirk::KeepI)
+        )));
+        // 同じ値の項目は含まない
+        assert!(diff.iter().all(|(name, _, _)| *name != "jump"));
+        assert!(diff_profiles(QuirkProfile::XoChip, QuirkProfile::XoChip).is_empty());
+    }
+}
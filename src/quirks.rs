@@ -0,0 +1,146 @@
+use serde::Deserialize;
+
+// 8XY1/8XY2/8XY3（OR/AND/XOR）がVFに与える副作用
+//
+// COSMAC VIPのインタプリタでは論理演算の後にVFが0にリセットされる．
+// 一方，SUPER-CHIP以降の多くのインタプリタではVFは変更されず，
+// 現在流通しているROMの大半はこちらを前提としているため，既定値はLeaveVfとする．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogicQuirk {
+    ResetVf, // 論理演算の後にVFを0にする（COSMAC VIP）
+    #[default]
+    LeaveVf, // VFを変更しない（SUPER-CHIP以降）
+}
+
+// JP/CALL/BNNNの飛び先がメモリの範囲（命令を読める最後のアドレス0xFFE）を超えた場合の扱い
+//
+// 12ビットのアドレスを指定するJP/CALLでは0xFFFのみが該当し，
+// 主に問題となるのはV0を加算するBNNNである．
+// Wrapでは命令の読み込みも12ビットのアドレスで折り返すため，0xFFFに飛んだ場合は
+// 0xFFFと0x000を1つの命令として実行し，次の命令は0x001から読む．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JumpQuirk {
+    #[default]
+    Wrap, // 下位12ビットに切り詰める（COSMAC VIP）
+    Clamp, // 最後の有効なアドレスに丸める
+    Error, // エラーとして実行を停止する
+}
+
+// DRWでスプライトが画面の端からはみ出した場合の扱い
+//
+// どちらの場合も描画の開始座標（Vx, Vy）は画面の大きさで折り返す．
+// Clipでははみ出した画素は描画せず，衝突判定（VF）にも含めない．
+// Wrapでははみ出した画素を反対側の端に描画し，そこで既存の画素と重なれば衝突として扱う．
+// 横方向（h_edge）と縦方向（v_edge）で別々に設定でき，どちらも既定値はClipとする．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeQuirk {
+    #[default]
+    Clip, // はみ出した画素を切り捨てる（COSMAC VIP，SUPER-CHIP）
+    Wrap, // はみ出した画素を反対側に折り返す
+}
+
+// FX0Aがキー入力を受け付けるタイミング
+//
+// COSMAC VIPではキーが離されたときに命令が完了する．連打の扱いに影響するため選択できるようにしている．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyWaitQuirk {
+    #[default]
+    OnPress, // キーが押された時点で完了する
+    OnRelease, // 押されていたキーが離された時点で完了し，そのキーを格納する（COSMAC VIP）
+}
+
+// 8XY6/8XYE（SHR/SHL）でシフトする値
+//
+// COSMAC VIPではVyをシフトした値をVxに代入する．SUPER-CHIP以降はVxをその場でシフトし，Yは無視する．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShiftQuirk {
+    #[default]
+    VxOnly, // Vxをシフトする（SUPER-CHIP以降）
+    Vy, // Vyをシフトした値をVxに代入する（COSMAC VIP）
+}
+
+// FX55/FX65（LD [I], Vx / LD Vx, [I]）の後のインデックスレジスタ
+//
+// COSMAC VIPとXO-CHIPでは転送したレジスタの数だけIが進む．SUPER-CHIPではIを変更しない．
+// 既定値はこの設定を追加する前と同じKeepIとし，QuirkProfileのCosmacVipとXoChipはIncrementIを使う．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadStoreQuirk {
+    #[default]
+    KeepI, // Iを変更しない（SUPER-CHIP）
+    IncrementI, // IにX + 1を加える（COSMAC VIP，XO-CHIP）
+}
+
+// スクロール命令（00CN/00DN/00FB/00FC）で空いた行や列を埋める内容
+//
+// 標準の挙動（SUPER-CHIP，XO-CHIP）では消灯した画素で埋める．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollQuirk {
+    #[default]
+    Blank, // 消灯した画素で埋める
+    Wrap, // 反対側の端からはみ出した画素で埋める
+}
+
+// 低解像度（64x32）でのスクロール命令（00CN/00DN/00FB/00FC）の移動量
+//
+// 高解像度（128x64）では常に指定された画素数だけスクロールする．
+// SUPER-CHIP 1.1は低解像度でも高解像度の画素単位でスクロールするため，画面上では半分の移動量になる．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoresScrollQuirk {
+    #[default]
+    Full, // 指定された画素数だけスクロールする（XO-CHIP）
+    Half, // 半分の画素数だけスクロールする（SUPER-CHIP 1.1，端数は切り捨てる）
+}
+
+// DRWが表示の垂直帰線期間（vblank）を待つか
+//
+// COSMAC VIPではDRWが次の垂直帰線期間まで待つ．Vblankでは次のように扱う．
+// - フレームで最初のDRWは，そのフレームの残りの命令を実行せずに次のフレームの開始まで待ち，
+//   次のフレームの最初に描画する
+// - 待ち終えたフレームでは，その後のDRWは待たずにすぐに描画し，残りの命令も続けて実行する
+// そのため，描画してから計算するROMは，描画の後の計算を同じフレームの中で進められる．
+// Immediateではフレーム内の全てのDRWがすぐに実行される．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayWaitQuirk {
+    #[default]
+    Immediate, // DRWをすぐに実行する
+    Vblank, // フレームで最初のDRWだけが次のフレームの開始まで待つ（COSMAC VIP）
+}
+
+// 0NNN（SYS）の扱い
+//
+// COSMAC VIPでは機械語のサブルーチンを呼び出す命令だが，エミュレータでは実行できない．
+// 現在のインタプリタの多くは何もせずに次の命令へ進むため，既定値はIgnoreとする．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SysQuirk {
+    #[default]
+    Ignore, // 何もせずに次の命令へ進む
+    Error, // 未知の命令としてエラーにする
+}
+
+// 互換性に関わる挙動の設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quirks {
+    pub logic: LogicQuirk,              // 論理演算命令のVFの扱い
+    pub jump: JumpQuirk,                // 範囲外へのジャンプの扱い
+    pub h_edge: EdgeQuirk,              // 画面の左右の端からはみ出したスプライトの扱い
+    pub v_edge: EdgeQuirk,              // 画面の上下の端からはみ出したスプライトの扱い
+    pub key_wait: KeyWaitQuirk,         // FX0Aがキー入力を受け付けるタイミング
+    pub shift: ShiftQuirk,              // SHR/SHLでシフトする値
+    pub load_store: LoadStoreQuirk,     // FX55/FX65の後にIを進めるか
+    pub scroll: ScrollQuirk,            // スクロール命令で空いた行や列を埋める内容
+    pub lores_scroll: LoresScrollQuirk, // 低解像度でのスクロール命令の移動量
+    pub display_wait: DisplayWaitQuirk, // DRWが垂直帰線期間を待つか
+    pub sys: SysQuirk,                  // 0NNN（SYS）の扱い
+    pub pc_wrap: bool,                  // プログラムカウンタがメモリの末尾を超えたら0に折り返すか
+}
@@ -0,0 +1,17 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+// RNDで使う乱数の生成元
+//
+// 既定ではシードから初期化したStdRngを使う．決まった値の列を返す生成元に差し替えると，
+// RNDの結果を固定してテストやファジングができる．
+pub trait RandSource {
+    // 次の乱数
+    fn next_u8(&mut self) -> u8;
+}
+
+impl RandSource for StdRng {
+    fn next_u8(&mut self) -> u8 {
+        self.gen()
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::Chip8;
+
+// 記録ファイルの先頭に置く識別子と形式のバージョン
+const MAGIC: &[u8; 4] = b"C8RC";
+const VERSION: u16 = 1;
+
+// ヘッダーの大きさ（識別子，バージョン，ROMのハッシュ値，乱数のシード，フレーム数）
+const HEADER_LEN: usize = 4 + 2 + 8 + 8 + 4;
+
+// キー入力の記録
+//
+// 記録ファイルの形式（数値は全てリトルエンディアン）
+//
+//     "C8RC"           識別子（4バイト）
+//     version: u16     形式のバージョン（1）
+//     rom_hash: u64    記録したROMのハッシュ値（FNV-1a）
+//     seed: u64        記録したときの乱数のシード
+//     frames: u32      フレーム数
+//     masks: [u16]     フレームごとのキーの状態（ビットnがキーnに対応する）
+//     crc: u32         ここまでの全てのバイトのCRC-32
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Recording {
+    rom_hash: u64,   // 記録したROMのハッシュ値
+    seed: u64,       // 記録したときの乱数のシード
+    masks: Vec<u16>, // フレームごとのキーの状態
+}
+
+impl Recording {
+    pub(crate) fn new(rom_hash: u64, seed: u64) -> Recording {
+        Recording {
+            rom_hash,
+            seed,
+            masks: Vec::new(),
+        }
+    }
+
+    // 1フレーム分のキーの状態を追加する
+    pub(crate) fn push(&mut self, mask: u16) {
+        self.masks.push(mask);
+    }
+
+    // 記録ファイルの形式に変換する
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.masks.len() * 2 + 4);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.rom_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.masks.len() as u32).to_le_bytes());
+        for mask in &self.masks {
+            bytes.extend_from_slice(&mask.to_le_bytes());
+        }
+        let crc = crc32(&bytes);
+        bytes.extend_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    // 記録ファイルの形式から変換する
+    fn decode(bytes: &[u8]) -> io::Result<Recording> {
+        if bytes.len() < HEADER_LEN + 4 || &bytes[..4] != MAGIC {
+            return Err(invalid_data("not a CHIP-8 recording"));
+        }
+        let (body, crc) = bytes.split_at(bytes.len() - 4);
+        if crc32(body).to_le_bytes() != crc {
+            return Err(invalid_data("recording checksum mismatch"));
+        }
+
+        let version = u16::from_le_bytes([body[4], body[5]]);
+        if version != VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported recording version {}",
+                version
+            )));
+        }
+        let rom_hash = u64::from_le_bytes(body[6..14].try_into().unwrap_or_default());
+        let seed = u64::from_le_bytes(body[14..22].try_into().unwrap_or_default());
+        let frames = u32::from_le_bytes(body[22..26].try_into().unwrap_or_default()) as usize;
+
+        let masks = &body[HEADER_LEN..];
+        if masks.len() != frames * 2 {
+            return Err(invalid_data(
+                "recording frame count does not match its length",
+            ));
+        }
+        Ok(Recording {
+            rom_hash,
+            seed,
+            masks: masks
+                .chunks_exact(2)
+                .map(|mask| u16::from_le_bytes([mask[0], mask[1]]))
+                .collect(),
+        })
+    }
+}
+
+impl Chip8 {
+    // キー入力の記録を始める（それまでの記録は破棄する）
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::new(self.rom_hash, self.seed));
+    }
+
+    // 記録したキー入力をファイルに保存する（記録していない場合はエラー）
+    pub fn save_recording(&self, path: &Path) -> io::Result<()> {
+        let recording = self
+            .recording
+            .as_ref()
+            .ok_or_else(|| io::Error::other("not recording"))?;
+        fs::write(path, recording.encode())
+    }
+
+    // 記録ファイルを読み込み，次のフレームから記録したキー入力を再生する
+    // 読み込んでいるROMや乱数のシードが記録したときと異なる場合はエラー
+    pub fn load_recording(&mut self, path: &Path) -> io::Result<()> {
+        let recording = Recording::decode(&fs::read(path)?)?;
+        if recording.rom_hash != self.rom_hash {
+            return Err(invalid_data("recording was made with a different ROM"));
+        }
+        if recording.seed != self.seed {
+            return Err(invalid_data(&format!(
+                "recording was made with seed {} (current seed is {})",
+                recording.seed, self.seed
+            )));
+        }
+        self.replay = Some(VecDeque::from(recording.masks));
+        Ok(())
+    }
+
+    // フレームごとのキーの状態（ビットnがキーn）を，次のフレームから順に再生する
+    // 再生中の記録があれば置き換える．記録と違い，ROMや乱数のシードは確認しない
+    pub fn replay_key_masks(&mut self, masks: &[u16]) {
+        self.replay = Some(masks.iter().copied().collect());
+    }
+
+    // 記録したキー入力を再生しているか
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// CRC-32（IEEE 802.3）
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // キーを待ってV0に読み込み，乱数を加えて繰り返すプログラム（F00A; RND V1, FF; ADD V0, V1; JP 0x200）
+    const KEY_AND_RND: [u8; 8] = [0xF0, 0x0A, 0xC1, 0xFF, 0x80, 0x14, 0x12, 0x00];
+
+    // テストごとの一時ファイルのパス
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chip8-rust-{}-{}.c8r", name, std::process::id()))
+    }
+
+    fn machine(seed: u64, rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.set_seed(seed);
+        chip8.load(rom);
+        chip8
+    }
+
+    // キーを押したり離したりしながら記録し，ファイルに保存する
+    fn record(path: &Path) -> Chip8 {
+        let mut chip8 = machine(7, &KEY_AND_RND);
+        chip8.start_recording();
+        for frame in 0..20 {
+            chip8.set_key((frame % 16) as u8, frame % 3 == 0);
+            chip8.run_frame().unwrap();
+        }
+        chip8.save_recording(path).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn recording_round_trips_and_replays() {
+        let path = temp_path("round-trip");
+        let recorded = record(&path);
+
+        let mut replayed = machine(7, &KEY_AND_RND);
+        replayed.load_recording(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(replayed.is_replaying());
+        for _ in 0..20 {
+            replayed.run_frame().unwrap();
+        }
+        assert_eq!(replayed.registers(), recorded.registers());
+        assert_eq!(replayed.pc(), recorded.pc());
+    }
+
+    #[test]
+    fn mismatched_recording_is_rejected() {
+        let path = temp_path("mismatch");
+        record(&path);
+
+        let err = machine(8, &KEY_AND_RND).load_recording(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("seed 7"), "{}", err);
+
+        let err = machine(7, &[0x12, 0x00]).load_recording(&path).unwrap_err();
+        assert!(err.to_string().contains("different ROM"), "{}", err);
+
+        // 壊れたファイルはチェックサムで検出する
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[HEADER_LEN] ^= 0x01;
+        std::fs::write(&path, bytes).unwrap();
+        let err = machine(7, &KEY_AND_RND).load_recording(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("checksum"), "{}", err);
+    }
+}
@@ -0,0 +1,142 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::{Chip8, Chip8Error};
+
+// 参照トレースの1命令分の状態（命令を実行する直前のもの）
+//
+// 1行に1命令で，"pc opcode V0 V1 ... VF I"を空白区切りの16進数で書く．
+// 空行と"#"で始まる行は無視する．
+// 例: "0200 6005 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 0000"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceStep {
+    pub pc: u16,             // プログラムカウンタ
+    pub opcode: u16,         // pcの命令
+    pub registers: [u8; 16], // レジスタ
+    pub i: u16,              // インデックスレジスタ
+}
+
+impl TraceStep {
+    // 現在のマシンの状態
+    pub fn capture(chip8: &Chip8) -> TraceStep {
+        TraceStep {
+            pc: chip8.pc(),
+            opcode: chip8.opcode_at(chip8.pc()).unwrap_or_default(),
+            registers: *chip8.registers(),
+            i: chip8.i(),
+        }
+    }
+
+    // 1行を解析する
+    pub fn parse(line: &str) -> Result<TraceStep, String> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        if words.len() != 19 {
+            return Err(format!("expected 19 fields, found {}", words.len()));
+        }
+        let hex16 =
+            |s: &str| u16::from_str_radix(s, 16).map_err(|_| format!("invalid hex value: {}", s));
+        let mut registers = [0; 16];
+        for (register, word) in registers.iter_mut().zip(&words[2..18]) {
+            *register =
+                u8::from_str_radix(word, 16).map_err(|_| format!("invalid register: {}", word))?;
+        }
+        Ok(TraceStep {
+            pc: hex16(words[0])?,
+            opcode: hex16(words[1])?,
+            registers,
+            i: hex16(words[18])?,
+        })
+    }
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04X} {:04X}", self.pc, self.opcode)?;
+        for register in &self.registers {
+            write!(f, " {:02X}", register)?;
+        }
+        write!(f, " {:04X}", self.i)
+    }
+}
+
+// 参照トレースとの比較に失敗した理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceError {
+    Parse(usize, String), // 参照トレースの行番号（1始まり）と解析できなかった理由
+    Diverged {
+        step: usize,         // 何命令目で異なったか（0始まり）
+        line: usize,         // 参照トレースの行番号（1始まり）
+        expected: TraceStep, // 参照トレースの状態
+        actual: TraceStep,   // 実際の状態
+    },
+    Execution(usize, Chip8Error), // 何命令目で実行に失敗したかとそのエラー
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceError::Parse(line, message) => write!(f, "line {}: {}", line, message),
+            TraceError::Diverged {
+                step,
+                line,
+                expected,
+                actual,
+            } => {
+                writeln!(f, "diverged at step {} (line {})", step, line)?;
+                writeln!(f, "  expected: {}", expected)?;
+                write!(f, "  actual:   {}", actual)?;
+                // 異なる項目を列挙する
+                if expected.pc != actual.pc {
+                    write!(f, "\n  PC: {:04X} != {:04X}", expected.pc, actual.pc)?;
+                }
+                if expected.opcode != actual.opcode {
+                    write!(
+                        f,
+                        "\n  opcode: {:04X} != {:04X}",
+                        expected.opcode, actual.opcode
+                    )?;
+                }
+                for (x, (e, a)) in expected.registers.iter().zip(&actual.registers).enumerate() {
+                    if e != a {
+                        write!(f, "\n  V{:X}: {:02X} != {:02X}", x, e, a)?;
+                    }
+                }
+                if expected.i != actual.i {
+                    write!(f, "\n  I: {:04X} != {:04X}", expected.i, actual.i)?;
+                }
+                Ok(())
+            }
+            TraceError::Execution(step, e) => write!(f, "step {}: {}", step, e),
+        }
+    }
+}
+
+impl Error for TraceError {}
+
+// 参照トレースと1命令ずつ状態を比べながら実行し，一致した命令数を返す
+//
+// ROMは読み込んでおく．最初に異なった時点でTraceError::Divergedを返す．
+pub fn compare_trace(chip8: &mut Chip8, trace: &str) -> Result<usize, TraceError> {
+    let mut step = 0;
+    for (index, line) in trace.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let expected = TraceStep::parse(line).map_err(|e| TraceError::Parse(index + 1, e))?;
+        let actual = TraceStep::capture(chip8);
+        if expected != actual {
+            return Err(TraceError::Diverged {
+                step,
+                line: index + 1,
+                expected,
+                actual,
+            });
+        }
+        chip8
+            .run_cycles(1)
+            .map_err(|e| TraceError::Execution(step, e))?;
+        step += 1;
+    }
+    Ok(step)
+}
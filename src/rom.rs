@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{recommend_profile, rom_sha256, Instruction, QuirkProfile, Variant, PROGRAM_START};
+
+// 奇数長のROMの扱い
+//
+// 命令は2バイト（ビッグエンディアン）単位で0x200から並んでいるため，
+// ROMの長さは本来偶数になる．奇数長の場合，最後の命令の下位バイトはROMの外側から読み込まれる．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OddLengthPolicy {
+    #[default]
+    Pad, // 末尾に0x00を補って最後の命令を完成させる
+    Warn, // 補わずに警告を表示する
+}
+
+// ROMを実行せずに調べた情報
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub size: usize,                              // ROMの大きさ（バイト）
+    pub sha256: String,                           // ROMのSHA-256（小文字の16進数）
+    pub profile: Option<QuirkProfile>,            // 既知のROMであれば推奨設定
+    pub header: Option<String>,                   // 先頭のJPで飛び越える領域に埋め込まれた文字列
+    pub variant: Variant,                         // 全ての命令を解釈できる最小の系統
+    pub histogram: BTreeMap<&'static str, usize>, // ニーモニックごとの出現回数
+    pub unknown: usize,                           // どの系統でも解釈できない語の数
+}
+
+impl RomInfo {
+    // ROMの先頭から2バイトずつ命令として解釈して集計する
+    //
+    // 静的な走査のため，スプライトなどのデータも命令として数えられる．
+    // 各語は最も多くの命令を持つXO-CHIPとしてデコードし，同じ命令になる最小の系統を求める．
+    // そのため，元のCHIP-8ではSYSになる00FEなども，SUPER-CHIPの命令として数える．
+    pub fn scan(rom: &[u8]) -> RomInfo {
+        let mut variant = Variant::Chip8;
+        let mut histogram = BTreeMap::new();
+        let mut unknown = 0;
+        for word in rom.chunks(2) {
+            let opcode = u16::from_be_bytes([word[0], word.get(1).copied().unwrap_or(0)]);
+            match minimal_variant(opcode) {
+                Some((instruction, required)) => {
+                    *histogram.entry(instruction.mnemonic()).or_insert(0) += 1;
+                    variant = variant.max(required);
+                }
+                None => unknown += 1,
+            }
+        }
+
+        RomInfo {
+            size: rom.len(),
+            sha256: rom_sha256(rom),
+            profile: recommend_profile(rom),
+            header: embedded_header(rom),
+            variant,
+            histogram,
+            unknown,
+        }
+    }
+}
+
+// 命令の解釈と，それを解釈できる最小の系統（どの系統でも解釈できない場合はNone）
+fn minimal_variant(opcode: u16) -> Option<(Instruction, Variant)> {
+    let instruction = Instruction::decode(opcode, Variant::XoChip)?;
+    let variant = Variant::ALL.into_iter().find(|&variant| {
+        // DXY0（16x16のスプライト）は元のCHIP-8では0行のスプライトになる
+        let sprite16 = matches!(instruction, Instruction::Drw(_, _, 0));
+        Instruction::decode(opcode, variant) == Some(instruction)
+            && !(sprite16 && variant == Variant::Chip8)
+    })?;
+    Some((instruction, variant))
+}
+
+// 先頭のJPで飛び越える領域に埋め込まれた文字列（タイトルや作者名など）
+//
+// 多くのROMは先頭の命令でデータを飛び越え，その部分にASCIIの文字列を置いている．
+// 飛び越える領域から4文字以上続く表示可能な文字の並びを取り出し，空白でつなげる．
+fn embedded_header(rom: &[u8]) -> Option<String> {
+    let [high, low] = *rom.first_chunk::<2>()?;
+    if high >> 4 != 0x1 {
+        return None;
+    }
+    let target = (u16::from_be_bytes([high, low]) & 0x0FFF) as usize;
+    let skipped = rom.get(2..target.checked_sub(PROGRAM_START)?)?;
+    let text: Vec<String> = skipped
+        .split(|byte| !(0x20..0x7F).contains(byte))
+        .map(|run| String::from_utf8_lossy(run).trim().to_string())
+        .filter(|run| run.len() >= 4)
+        .collect();
+    (!text.is_empty()).then(|| text.join(" "))
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "size:    {} bytes", self.size)?;
+        writeln!(f, "sha256:  {}", self.sha256)?;
+        match self.profile {
+            Some(profile) => writeln!(f, "profile: {:?}", profile)?,
+            None => writeln!(f, "profile: (unknown ROM)")?,
+        }
+        match &self.header {
+            Some(header) => writeln!(f, "header:  {:?}", header)?,
+            None => writeln!(f, "header:  (none)")?,
+        }
+        writeln!(f, "variant: {:?}", self.variant)?;
+        writeln!(f, "opcodes:")?;
+        for (mnemonic, count) in &self.histogram {
+            writeln!(f, "  {:<6} {}", mnemonic, count)?;
+        }
+        write!(f, "  {:<6} {}", "(data)", self.unknown)
+    }
+}
+
+// DRWの性能を測るためのROMを生成する
+//
+// 8x15のスプライトを画面全体に横8個，縦3段（最下段は画面の下端で切れる）並べて描画し，
+// 最初に戻って同じ位置に描画し続ける．XORで描画するため，1周ごとに画面全体が点灯と消灯を繰り返す．
+// 全ての画素が衝突判定の対象になり，最下段では画面の端の処理も通る．
+pub fn stress_rom() -> Vec<u8> {
+    const SPRITE_ADDR: u16 = 0x216;
+    let mut rom = Vec::new();
+    for opcode in [
+        0xA000 | SPRITE_ADDR, // 0x200: LD I, sprite
+        0x6100,               // 0x202: LD V1, 0
+        0x6000,               // 0x204: LD V0, 0
+        0xD01F,               // 0x206: DRW V0, V1, 15
+        0x7008,               // 0x208: ADD V0, 8
+        0x3040,               // 0x20A: SE V0, 64
+        0x1206,               // 0x20C: JP 0x206
+        0x710F,               // 0x20E: ADD V1, 15
+        0x312D,               // 0x210: SE V1, 45
+        0x1204,               // 0x212: JP 0x204
+        0x1200,               // 0x214: JP 0x200
+    ] {
+        rom.extend_from_slice(&u16::to_be_bytes(opcode));
+    }
+    rom.extend_from_slice(&[0xFF; 15]);
+    rom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 16進数の文字列をROMにする
+    fn rom(hex: &str) -> Vec<u8> {
+        let digits: Vec<u8> = hex
+            .chars()
+            .filter_map(|c| c.to_digit(16))
+            .map(|d| d as u8)
+            .collect();
+        digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect()
+    }
+
+    #[test]
+    fn scan_reports_the_smallest_variant() {
+        assert_eq!(
+            RomInfo::scan(&rom("00E0 A200 D015")).variant,
+            Variant::Chip8
+        );
+        // 00FF（HIGH），00C2（SCD 2），DXY0はSUPER-CHIPから
+        for program in ["6000 00FF", "00C2", "A200 D010"] {
+            let info = RomInfo::scan(&rom(program));
+            assert_eq!(info.variant, Variant::SuperChip, "{}", program);
+        }
+        let info = RomInfo::scan(&rom("00FF 5012"));
+        assert_eq!(info.variant, Variant::XoChip);
+        assert_eq!(info.histogram["HIGH"], 1);
+        assert_eq!(info.histogram["SAVE"], 1);
+    }
+
+    #[test]
+    fn header_is_the_text_skipped_by_the_first_jump() {
+        // JP 0x210; "PONG"; 0x00; " by Joe"; 0xFF, 0x00; CLS; JP 0x212
+        let mut program = rom("1210");
+        program.extend_from_slice(b"PONG\0 by Joe\xFF\0");
+        program.extend_from_slice(&rom("00E0 1212"));
+        let info = RomInfo::scan(&program);
+        assert_eq!(info.header.as_deref(), Some("PONG by Joe"));
+        assert!(info.to_string().contains("header:  \"PONG by Joe\""));
+
+        assert_eq!(RomInfo::scan(&rom("00E0 1200")).header, None);
+        // ROMの外への飛び先は無視する
+        assert_eq!(RomInfo::scan(&rom("1FFF")).header, None);
+    }
+}
@@ -0,0 +1,209 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Chip8, Chip8Error, ErrorKind, LogicalDisplay, Variant};
+
+// セーブデータの形式の版
+//
+// 形式を変更したら増やし，migrateに1つ前の版からの変換を追加する．
+// - 1: レジスタ，スタック，タイマー，メモリ，ディスプレイ
+// - 2: 描画対象のプレーン（planes）と系統（variant）を追加
+pub const SAVE_STATE_VERSION: u64 = 2;
+
+// セーブデータ（JSON）
+//
+// 数値はJSONの数値，メモリとディスプレイの画素はBase64で保存するため，環境のバイト順によらない．
+//
+//     {
+//       "version": 2,
+//       "variant": "chip8",            # 系統
+//       "registers": [0, ...],         # V0〜VF（16個）
+//       "i": 512,                      # インデックスレジスタ
+//       "pc": 512,                     # プログラムカウンタ
+//       "sp": 0,                       # スタックポインタ
+//       "stack": [0, ...],             # スタック（全ての段）
+//       "delay": 0,                    # ディレイタイマ
+//       "sound": 0,                    # サウンドタイマ
+//       "planes": 1,                   # 描画対象のプレーン
+//       "memory": "AAAA...",           # メモリ全体をBase64でエンコードしたもの
+//       "display": {
+//         "width": 64,
+//         "height": 32,
+//         "pixels": "AAAA..."          # 1画素1バイトの画素をBase64でエンコードしたもの
+//       }
+//     }
+#[derive(Serialize, Deserialize)]
+struct SaveState {
+    version: u64,
+    variant: Variant,
+    registers: [u8; 16],
+    i: u16,
+    pc: u16,
+    sp: usize,
+    stack: Vec<u16>,
+    delay: u8,
+    sound: u8,
+    planes: u8,
+    memory: String,
+    display: DisplayState,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DisplayState {
+    width: usize,
+    height: usize,
+    pixels: String,
+}
+
+impl Chip8 {
+    // 現在の状態をセーブデータ（JSON）にする
+    pub fn save_state(&self) -> String {
+        let state = SaveState {
+            version: SAVE_STATE_VERSION,
+            variant: self.variant,
+            registers: self.registers,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            stack: self.stack.clone(),
+            delay: self.delay,
+            sound: self.sound,
+            planes: self.planes,
+            memory: STANDARD.encode(self.bus.as_slice()),
+            display: DisplayState {
+                width: self.display.width(),
+                height: self.display.height(),
+                pixels: STANDARD.encode(self.display.pixels()),
+            },
+        };
+        // 文字列のキーと数値・文字列のみからなるため，シリアライズは失敗しない
+        serde_json::to_string(&state).unwrap_or_default()
+    }
+
+    // セーブデータを読み込み，その状態に戻す
+    //
+    // 古い版のセーブデータは現在の版に変換してから読み込む（追加された項目は既定値になる）．
+    // 新しい版のものや内容が正しくないものはエラーとし，その場合は状態を変更しない．
+    pub fn load_state(&mut self, json: &str) -> Result<(), Chip8Error> {
+        let value = serde_json::from_str(json).map_err(invalid)?;
+        let state: SaveState = serde_json::from_value(migrate(value)?).map_err(invalid)?;
+
+        let memory = STANDARD.decode(&state.memory).map_err(invalid)?;
+        if memory.len() != state.variant.memory_size() {
+            return Err(invalid(format!(
+                "memory size {} does not match the variant",
+                memory.len()
+            )));
+        }
+        if state.sp > state.stack.len() {
+            return Err(invalid("stack pointer is out of range"));
+        }
+        let mut display = LogicalDisplay::new(state.display.width, state.display.height)?;
+        let pixels = STANDARD.decode(&state.display.pixels).map_err(invalid)?;
+        if pixels.len() != display.pixels().len() {
+            return Err(invalid("display size does not match its pixels"));
+        }
+        for (i, &pixel) in pixels.iter().enumerate() {
+            display.set(i % display.width(), i / display.width(), pixel & 0x03);
+        }
+
+        self.set_variant(state.variant);
+        self.bus.load(0, &memory);
+        self.clear_decode_cache();
+        self.registers = state.registers;
+        self.i = state.i;
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.stack = state.stack;
+        self.delay = state.delay;
+        self.sound = state.sound;
+        self.planes = state.planes & 0x03;
+        self.display = display;
+        self.display_updated = true;
+        Ok(())
+    }
+}
+
+// 古い版のセーブデータを現在の版に変換する
+fn migrate(mut value: Value) -> Result<Value, Chip8Error> {
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| invalid("missing version"))?;
+    if version > SAVE_STATE_VERSION {
+        return Err(ErrorKind::UnsupportedSaveStateVersion(version).into());
+    }
+    let Some(object) = value.as_object_mut() else {
+        return Err(invalid("not an object"));
+    };
+
+    // 1 -> 2: プレーン1だけを描画する元のCHIP-8として扱う
+    if version < 2 {
+        object.insert("planes".to_string(), Value::from(0x01));
+        object.insert("variant".to_string(), Value::from("chip8"));
+    }
+
+    object.insert("version".to_string(), Value::from(SAVE_STATE_VERSION));
+    Ok(value)
+}
+
+// 内容が正しくないセーブデータのエラー
+fn invalid(reason: impl ToString) -> Chip8Error {
+    ErrorKind::InvalidSaveState(reason.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_round_trips() {
+        let mut chip8 = Chip8::new();
+        chip8.load_hex("6042 A300 2206 0000 0000 0000").unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        let mut restored = Chip8::new();
+        restored.load_state(&chip8.save_state()).unwrap();
+        assert_eq!(restored.save_state(), chip8.save_state());
+        assert_eq!(restored.registers()[0], 0x42);
+        assert_eq!((restored.pc(), restored.i()), (0x206, 0x300));
+    }
+
+    #[test]
+    fn v1_state_is_migrated_with_defaults() {
+        // 現在のセーブデータから版2で追加した項目を除き，版1のものとする
+        let mut chip8 = Chip8::new();
+        chip8.load_hex("6042").unwrap();
+        chip8.step().unwrap();
+        let mut v1: Value = serde_json::from_str(&chip8.save_state()).unwrap();
+        let object = v1.as_object_mut().unwrap();
+        object.remove("planes");
+        object.remove("variant");
+        object.insert("version".to_string(), Value::from(1));
+
+        // XO-CHIPで両方のプレーンを選択しているマシンに読み込む
+        let mut restored = Chip8::new();
+        restored.set_variant(Variant::XoChip);
+        restored.planes = 0x03;
+        restored.load_state(&v1.to_string()).unwrap();
+        assert_eq!(restored.variant(), Variant::Chip8);
+        assert_eq!(restored.planes(), 0x01);
+        assert_eq!(restored.registers()[0], 0x42);
+        assert_eq!(restored.pc(), 0x202);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let mut state: Value = serde_json::from_str(&Chip8::new().save_state()).unwrap();
+        state["version"] = Value::from(SAVE_STATE_VERSION + 1);
+        let mut chip8 = Chip8::new();
+        let err = chip8.load_state(&state.to_string()).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::UnsupportedSaveStateVersion(SAVE_STATE_VERSION + 1)
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+
+use crate::{Bus, Chip8, LogicalDisplay};
+
+// 巻き戻しのために保持する履歴の最大数
+pub(crate) const HISTORY_LIMIT: usize = 256;
+
+// ある時点のマシンの状態
+//
+// 命令の実行によって変わる状態を全て保持する（設定やフック，乱数の生成元は含まない）．
+// 乱数の生成元は複製できないため，その命令のRNDが得た値を記録しておき，巻き戻した後のRNDで再び使う．
+#[derive(Clone)]
+pub(crate) struct Snapshot {
+    bus: Bus,
+    registers: [u8; 16],
+    stack: Vec<u16>,
+    i: u16,
+    pc: u16,
+    sp: usize,
+    delay: u8,
+    sound: u8,
+    beep_started: bool,
+    keyboard: [bool; 16],
+    key_latch: Option<u8>,
+    key_release_latch: Option<u8>,
+    key_wait: Option<u8>,
+    key_wait_parked: bool,
+    key_wait_frames: usize,
+    replay: Option<VecDeque<u16>>,
+    display: LogicalDisplay,
+    display_waited: bool,
+    display_wait_pending: bool,
+    planes: u8,
+    halted: bool,
+    cycle_count: u64,
+    frame_cycle: usize,
+    rnd: Option<u8>, // この状態から実行した命令のRNDが乱数の生成元から得た値
+}
+
+impl Chip8 {
+    // 現在の状態を保存する
+    pub(crate) fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            bus: self.bus.clone(),
+            registers: self.registers,
+            stack: self.stack.clone(),
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            delay: self.delay,
+            sound: self.sound,
+            beep_started: self.beep_started,
+            keyboard: self.keyboard,
+            key_latch: self.key_latch,
+            key_release_latch: self.key_release_latch,
+            key_wait: self.key_wait,
+            key_wait_parked: self.key_wait_parked,
+            key_wait_frames: self.key_wait_frames,
+            replay: self.replay.clone(),
+            display: self.display.clone(),
+            display_waited: self.display_waited,
+            display_wait_pending: self.display_wait_pending,
+            planes: self.planes,
+            halted: self.halted,
+            cycle_count: self.cycle_count,
+            frame_cycle: self.frame_cycle,
+            rnd: None,
+        }
+    }
+
+    // 保存した状態に戻す
+    pub(crate) fn restore(&mut self, snapshot: &Snapshot) {
+        self.bus = snapshot.bus.clone();
+        self.registers = snapshot.registers;
+        self.stack = snapshot.stack.clone();
+        self.i = snapshot.i;
+        self.pc = snapshot.pc;
+        self.sp = snapshot.sp;
+        self.delay = snapshot.delay;
+        self.sound = snapshot.sound;
+        self.beep_started = snapshot.beep_started;
+        self.keyboard = snapshot.keyboard;
+        self.key_latch = snapshot.key_latch;
+        self.key_release_latch = snapshot.key_release_latch;
+        self.key_wait = snapshot.key_wait;
+        self.key_wait_parked = snapshot.key_wait_parked;
+        self.key_wait_frames = snapshot.key_wait_frames;
+        self.replay = snapshot.replay.clone();
+        self.display = snapshot.display.clone();
+        self.display_waited = snapshot.display_waited;
+        self.display_wait_pending = snapshot.display_wait_pending;
+        self.planes = snapshot.planes;
+        self.halted = snapshot.halted;
+        self.cycle_count = snapshot.cycle_count;
+        self.frame_cycle = snapshot.frame_cycle;
+        if let Some(raw) = snapshot.rnd {
+            self.rewound_rnd.push(raw);
+        }
+        self.display_updated = true;
+        self.clear_decode_cache();
+    }
+
+    // RNDが得る乱数（巻き戻したRNDが得ていた値があれば，乱数の生成元の代わりにそれを使う）
+    pub(crate) fn next_rnd(&mut self) -> u8 {
+        let raw = self.rewound_rnd.pop().unwrap_or_else(|| self.rng.next_u8());
+        if let (true, Some(snapshot)) = (self.debug, self.history.back_mut()) {
+            snapshot.rnd = Some(raw);
+        }
+        raw
+    }
+}
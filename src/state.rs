@@ -0,0 +1,99 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+
+use crate::Chip8;
+
+// 外部のデバッガに渡すマシンの状態（読み取り専用で，セーブデータとは別のもの）
+//
+//     {
+//       "registers": [0, ...],        # V0〜VF（16個）
+//       "i": 512,                     # インデックスレジスタ
+//       "pc": 512,                    # プログラムカウンタ
+//       "sp": 0,                      # スタックポインタ
+//       "stack": [0, ...],            # スタック（全ての段）
+//       "delay": 0,                   # デルタタイム
+//       "sound": 0,                   # サウンドタイマ
+//       "keyboard": [false, ...],     # 各キーが押されているか（16個）
+//       "display": {
+//         "width": 64,
+//         "height": 32,
+//         "packed": "AAAA..."         # packed_displayをBase64でエンコードしたもの
+//       }
+//     }
+#[derive(Serialize)]
+struct State<'a> {
+    registers: &'a [u8; 16],
+    i: u16,
+    pc: u16,
+    sp: usize,
+    stack: &'a [u16],
+    delay: u8,
+    sound: u8,
+    keyboard: &'a [bool; 16],
+    display: DisplayState,
+}
+
+#[derive(Serialize)]
+struct DisplayState {
+    width: usize,
+    height: usize,
+    packed: String,
+}
+
+impl Chip8 {
+    // マシンの状態をJSONにしたもの
+    pub fn state_json(&self) -> String {
+        let state = State {
+            registers: &self.registers,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            stack: &self.stack,
+            delay: self.delay,
+            sound: self.sound,
+            keyboard: &self.keyboard,
+            display: DisplayState {
+                width: self.display.width(),
+                height: self.display.height(),
+                packed: STANDARD.encode(self.packed_display()),
+            },
+        };
+        // 文字列のキーと数値・真偽値のみからなるため，シリアライズは失敗しない
+        serde_json::to_string(&state).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn state_json_matches_the_live_state() {
+        // V1 = 0x42; I = 0; DT = 5; CALL 0x20A; DRW V1, V1, 5（以降は描画を繰り返す）
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_hex("6142 A000 6005 F015 220A D115 120A")
+            .unwrap();
+        // キー入力はフレームの開始時に反映される
+        chip8.set_key(0x5, true);
+        chip8.run_frame().unwrap();
+
+        let state: Value = serde_json::from_str(&chip8.state_json()).unwrap();
+        assert_eq!(state["registers"].as_array().unwrap().len(), 16);
+        assert_eq!(state["registers"][1], 0x42);
+        assert_eq!(state["i"], chip8.i());
+        assert_eq!(state["pc"], chip8.pc());
+        assert_eq!(state["sp"], chip8.stack_depth());
+        assert_eq!(state["stack"][0], 0x20A);
+        assert_eq!(state["delay"], chip8.delay_timer());
+        assert_eq!(state["keyboard"][5], true);
+        assert_eq!(state["display"]["width"], 64);
+        let packed = STANDARD
+            .decode(state["display"]["packed"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(packed, chip8.packed_display());
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// アドレスと名前の対応（逆アセンブルやトレースの表示に使う）
+//
+// シンボルファイルは1行に1つで，"アドレス 名前"の形式で書く（アドレスは16進数で"0x"は省略可能）．
+// 空行と"#"で始まる行は無視する．
+// 例: "0300 draw_player"
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Symbols {
+    names: BTreeMap<u16, String>, // アドレスごとの名前
+}
+
+impl Symbols {
+    // シンボルファイルの内容を解析する
+    pub fn parse(text: &str) -> Result<Symbols, String> {
+        let mut symbols = Symbols::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let (Some(addr), Some(name), None) = (words.next(), words.next(), words.next()) else {
+                return Err(format!("line {}: expected \"addr name\"", index + 1));
+            };
+            let digits = addr.trim_start_matches("0x").trim_start_matches("0X");
+            let addr = u16::from_str_radix(digits, 16)
+                .map_err(|_| format!("line {}: invalid address: {}", index + 1, addr))?;
+            symbols.insert(addr, name);
+        }
+        Ok(symbols)
+    }
+
+    // シンボルファイルを読み込む
+    pub fn load(path: &Path) -> io::Result<Symbols> {
+        Symbols::parse(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // 名前を登録する（同じアドレスに登録済みの場合は置き換える）
+    pub fn insert(&mut self, addr: u16, name: &str) {
+        self.names.insert(addr, name.to_string());
+    }
+
+    // アドレスの名前（登録されていなければNone）
+    pub fn get(&self, addr: u16) -> Option<&str> {
+        self.names.get(&addr).map(String::as_str)
+    }
+
+    // 登録されている名前の数
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbol_file_is_parsed() {
+        let symbols = Symbols::parse("# sprites\n0x300 draw_player\n\n 2A0  score \n").unwrap();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols.get(0x300), Some("draw_player"));
+        assert_eq!(symbols.get(0x2A0), Some("score"));
+        assert_eq!(symbols.get(0x200), None);
+
+        assert!(Symbols::parse("0300").is_err());
+        assert!(Symbols::parse("main 0300").is_err());
+    }
+}
@@ -0,0 +1,2090 @@
+use super::*;
+
+// 16進数の文字列のプログラムを読み込んだChip8を作成する
+fn chip8_with(hex: &str) -> Chip8 {
+    chip8_with_quirks(Quirks::default(), hex)
+}
+
+// 互換性の設定を指定して，16進数の文字列のプログラムを読み込んだChip8を作成する
+fn chip8_with_quirks(quirks: Quirks, hex: &str) -> Chip8 {
+    let mut chip8 = Chip8::with_quirks(quirks);
+    chip8.load_hex(hex).unwrap();
+    chip8
+}
+
+// XO-CHIPとして，16進数の文字列のプログラムを読み込んだChip8を作成する
+fn xo_chip_with(hex: &str) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.set_variant(Variant::XoChip);
+    chip8.load_hex(hex).unwrap();
+    chip8
+}
+
+// 指定された数の命令を実行する
+fn steps(chip8: &mut Chip8, count: usize) {
+    for _ in 0..count {
+        chip8.step().unwrap();
+    }
+}
+
+// 常に同じ値を返す乱数の生成元
+struct FixedRand(u8);
+
+impl RandSource for FixedRand {
+    fn next_u8(&mut self) -> u8 {
+        self.0
+    }
+}
+
+#[test]
+fn or_resets_vf_with_reset_vf_quirk() {
+    let quirks = Quirks {
+        logic: LogicQuirk::ResetVf,
+        ..Quirks::default()
+    };
+    // LD VF, 1; LD V0, 0x0C; LD V1, 0x03; OR V0, V1
+    let mut chip8 = chip8_with_quirks(quirks, "6F01 600C 6103 8011");
+    steps(&mut chip8, 4);
+    assert_eq!(chip8.registers[0], 0x0F);
+    assert_eq!(chip8.registers[0xF], 0);
+}
+
+#[test]
+fn or_leaves_vf_with_leave_vf_quirk() {
+    let quirks = Quirks {
+        logic: LogicQuirk::LeaveVf,
+        ..Quirks::default()
+    };
+    let mut chip8 = chip8_with_quirks(quirks, "6F01 600C 6103 8011");
+    steps(&mut chip8, 4);
+    assert_eq!(chip8.registers[0], 0x0F);
+    assert_eq!(chip8.registers[0xF], 1);
+}
+
+#[test]
+fn odd_length_rom_is_padded() {
+    let mut chip8 = Chip8::new();
+    // 以前の内容が残っていても，補った0x00で上書きされる
+    chip8.poke(0x203, 0xAA).unwrap();
+    chip8.load(&[0x60, 0x01, 0x61]);
+    assert_eq!(chip8.memory()[0x202..0x204], [0x61, 0x00]);
+}
+
+#[test]
+fn odd_length_rom_is_not_padded_with_warn_policy() {
+    let mut chip8 = Chip8::new();
+    chip8.set_odd_length_policy(OddLengthPolicy::Warn);
+    chip8.poke(0x203, 0xAA).unwrap();
+    chip8.load(&[0x60, 0x01, 0x61]);
+    assert_eq!(chip8.memory()[0x202..0x204], [0x61, 0xAA]);
+}
+
+// BNNNでV0を加えて0x1000に飛ぶプログラム（LD V0, 0xFF; JP V0, 0xF01）
+const OVERFLOWING_JUMP: &str = "60FF BF01";
+
+#[test]
+fn overflowing_jump_wraps() {
+    let quirks = Quirks {
+        jump: JumpQuirk::Wrap,
+        ..Quirks::default()
+    };
+    let mut chip8 = chip8_with_quirks(quirks, OVERFLOWING_JUMP);
+    steps(&mut chip8, 2);
+    assert_eq!(chip8.pc, 0x000);
+}
+
+#[test]
+fn overflowing_jump_clamps() {
+    let quirks = Quirks {
+        jump: JumpQuirk::Clamp,
+        ..Quirks::default()
+    };
+    let mut chip8 = chip8_with_quirks(quirks, OVERFLOWING_JUMP);
+    steps(&mut chip8, 2);
+    assert_eq!(chip8.pc, 0xFFE);
+}
+
+#[test]
+fn overflowing_jump_errors() {
+    let quirks = Quirks {
+        jump: JumpQuirk::Error,
+        ..Quirks::default()
+    };
+    let mut chip8 = chip8_with_quirks(quirks, OVERFLOWING_JUMP);
+    chip8.step().unwrap();
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::JumpOutOfRange(0x1000));
+    assert_eq!(chip8.pc, 0x204);
+}
+
+#[test]
+fn jumps_past_4k_are_in_range_on_a_64k_bus() {
+    // XO-CHIPのメモリは64KBのため，0x1000以降への飛び先もそのまま使う
+    for jump in [JumpQuirk::Wrap, JumpQuirk::Clamp, JumpQuirk::Error] {
+        let mut chip8 = xo_chip_with(OVERFLOWING_JUMP);
+        chip8.quirks = Quirks {
+            jump,
+            ..Quirks::default()
+        };
+        steps(&mut chip8, 2);
+        assert_eq!(chip8.pc(), 0x1000, "{:?}", jump);
+    }
+}
+
+#[test]
+fn wrapped_jump_to_last_byte_fetches_across_the_end() {
+    let quirks = Quirks {
+        jump: JumpQuirk::Wrap,
+        ..Quirks::default()
+    };
+    // JP 0xFFF: 0xFFFと0x000を合わせたLD V0, 0x2Aを実行し，0x001に進む
+    let mut chip8 = chip8_with_quirks(quirks, "1FFF");
+    chip8.poke(0xFFF, 0x60).unwrap();
+    chip8.poke(0x000, 0x2A).unwrap();
+    steps(&mut chip8, 2);
+    assert_eq!(chip8.registers[0], 0x2A);
+    assert_eq!(chip8.pc, 0x001);
+}
+
+#[test]
+fn step_back_restores_previous_state() {
+    // LD V0, 1; ADD V0, 2; LD V1, 3
+    let mut chip8 = chip8_with("6001 7002 6103");
+    chip8.set_debug(true);
+    chip8.step().unwrap();
+    let (pc, registers) = (chip8.pc(), *chip8.registers());
+
+    steps(&mut chip8, 2);
+    assert!(chip8.step_back());
+    assert!(chip8.step_back());
+    assert_eq!(chip8.pc(), pc);
+    assert_eq!(*chip8.registers(), registers);
+}
+
+#[test]
+fn step_back_without_debug_has_no_history() {
+    let mut chip8 = chip8_with("6001");
+    chip8.step().unwrap();
+    assert!(!chip8.step_back());
+    assert_eq!(chip8.pc(), 0x202);
+}
+
+#[test]
+fn step_back_undoes_an_idle_loop_halt() {
+    let mut chip8 = chip8_with("1200");
+    chip8.set_halt_on_idle(true);
+    chip8.set_debug(true);
+    chip8.step().unwrap();
+    assert!(chip8.is_halted());
+
+    assert!(chip8.step_back());
+    assert!(!chip8.is_halted());
+    assert_eq!((chip8.pc(), chip8.cycle_count()), (0x200, 0));
+}
+
+#[test]
+fn step_back_replays_the_same_random_value() {
+    // RND V0, 0xFF; RND V1, 0xFF
+    let mut chip8 = chip8_with("C0FF C1FF");
+    chip8.set_seed(1);
+    chip8.set_debug(true);
+    steps(&mut chip8, 2);
+    let values = (chip8.registers()[0], chip8.registers()[1]);
+
+    assert!(chip8.step_back());
+    assert!(chip8.step_back());
+    chip8.registers = [0; 16];
+    steps(&mut chip8, 2);
+    assert_eq!((chip8.registers()[0], chip8.registers()[1]), values);
+}
+
+#[test]
+fn step_back_unparks_a_key_wait() {
+    let mut chip8 = chip8_with("F00A");
+    chip8.set_debug(true);
+    chip8.step().unwrap();
+    assert!(chip8.is_waiting_for_key());
+    assert_eq!(chip8.pc(), 0x200);
+
+    assert!(chip8.step_back());
+    assert!(!chip8.is_waiting_for_key());
+    assert_eq!(chip8.cycle_count(), 0);
+}
+
+#[test]
+fn vetoed_and_failed_steps_leave_no_history() {
+    let mut chip8 = chip8_with("6001 00EE");
+    chip8.set_debug(true);
+    chip8.set_pre_step_hook(Some(Box::new(|_, _| false)));
+    chip8.step().unwrap();
+    assert!(!chip8.step_back());
+
+    chip8.set_pre_step_hook(None);
+    chip8.step().unwrap();
+    // 空のスタックからのRETはエラーになり，履歴には残らない
+    assert!(chip8.step().is_err());
+    assert!(chip8.step_back());
+    assert_eq!((chip8.pc(), chip8.registers()[0]), (0x200, 0));
+    assert!(!chip8.step_back());
+}
+
+// フォントの"0"を(0, 0)に2回重ねて描画するプログラム（LD I, 0; LD V0, 0; DRW V0, V0, 5を2回）
+const OVERLAPPING_DRAW: &str = "A000 6000 D005 D005";
+
+#[test]
+fn or_draw_mode_never_clears_pixels() {
+    let mut chip8 = chip8_with(OVERLAPPING_DRAW);
+    chip8.set_draw_mode(DrawMode::Or);
+    steps(&mut chip8, 3);
+    let first = chip8.display().pixels().to_vec();
+    chip8.step().unwrap();
+    assert_eq!(chip8.display().pixels(), first);
+    assert!(first.iter().any(|&pixel| pixel != 0));
+    // 衝突は通常通り報告する
+    assert_eq!(chip8.registers()[0xF], 1);
+}
+
+#[test]
+fn xor_draw_mode_clears_overlapping_pixels() {
+    let mut chip8 = chip8_with(OVERLAPPING_DRAW);
+    steps(&mut chip8, 4);
+    assert!(chip8.display().pixels().iter().all(|&pixel| pixel == 0));
+    assert_eq!(chip8.registers()[0xF], 1);
+}
+
+#[test]
+fn frame_hash_of_known_pattern() {
+    // フォントの"0"を(0, 0)に描画する
+    let mut chip8 = chip8_with("A000 6000 D005");
+    let blank = chip8.frame_hash();
+    steps(&mut chip8, 3);
+    assert_ne!(chip8.frame_hash(), blank);
+    assert_eq!(chip8.frame_hash(), 0x7B25_88E3_D7CE_C2B5);
+}
+
+#[test]
+fn run_until_returns_cycles_when_pixel_turns_on() {
+    // V0を5まで数えてから，フォントの"0"の1行目を(0, 0)に描画する
+    let mut chip8 = chip8_with("6000 7001 3005 1202 A000 D111");
+    let cycles = chip8
+        .run_until(|chip8| chip8.display().get(0, 0) != 0, 100)
+        .unwrap();
+    assert_eq!(cycles, 17);
+}
+
+#[test]
+fn run_until_errors_at_cycle_cap() {
+    let mut chip8 = chip8_with("1200");
+    let err = chip8
+        .run_until(|chip8| chip8.display().get(0, 0) != 0, 50)
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::CycleLimitReached(50));
+}
+
+// 0x206のADD V2, 1を1度実行した後に，FX55でADD V2, 0x10に書き換えてから再度実行するプログラム
+const SELF_MODIFYING: &str = "A206 6072 6110 7201 3301 120E 120C 6301 F155 1206";
+
+#[test]
+fn decode_cache_is_invalidated_by_writes() {
+    let mut chip8 = chip8_with(SELF_MODIFYING);
+    chip8.set_decode_cache(true);
+    steps(&mut chip8, 20);
+    assert_eq!(chip8.memory()[0x206..0x208], [0x72, 0x10]);
+    assert_eq!(chip8.registers()[2], 0x11);
+}
+
+#[test]
+fn decode_cache_matches_uncached_execution() {
+    let mut cached = chip8_with(SELF_MODIFYING);
+    cached.set_decode_cache(true);
+    let mut uncached = chip8_with(SELF_MODIFYING);
+    steps(&mut cached, 20);
+    steps(&mut uncached, 20);
+    assert_eq!(cached.registers(), uncached.registers());
+    assert_eq!(cached.pc(), uncached.pc());
+}
+
+#[test]
+fn writing_address_zero_invalidates_the_wrapped_instruction() {
+    let quirks = Quirks {
+        jump: JumpQuirk::Wrap,
+        ..Quirks::default()
+    };
+    // LD V0, 0x11; LD I, 0x000; LD [I], V0
+    let mut chip8 = chip8_with_quirks(quirks, "6011 A000 F055");
+    chip8.set_decode_cache(true);
+
+    // 0xFFFと0x000を合わせたLD V0, 0x2Aを実行し，デコード結果をキャッシュする
+    chip8.poke(0xFFF, 0x60).unwrap();
+    chip8.poke(0x000, 0x2A).unwrap();
+    chip8.pc = 0xFFF;
+    chip8.step().unwrap();
+    assert_eq!(chip8.registers()[0], 0x2A);
+
+    // FX55で0x000を書き換えると，0xFFFの命令もデコードし直す
+    chip8.pc = 0x200;
+    steps(&mut chip8, 3);
+    chip8.registers[0] = 0x00;
+    chip8.pc = 0xFFF;
+    chip8.step().unwrap();
+    assert_eq!(chip8.registers()[0], 0x11);
+}
+
+// (x, 0)に1画素を描画してから，(60, 0)に幅8画素の横線を描画するChip8（横線の右半分は画面の外）
+fn draw_over_right_edge(h_edge: EdgeQuirk, x: u8) -> Chip8 {
+    let quirks = Quirks {
+        h_edge,
+        ..Quirks::default()
+    };
+    let program = format!("A301 60{:02X} 6100 D011 A300 603C D011", x);
+    let mut chip8 = chip8_with_quirks(quirks, &program);
+    chip8.poke(0x300, 0xFF).unwrap();
+    chip8.poke(0x301, 0x80).unwrap();
+    steps(&mut chip8, 7);
+    chip8
+}
+
+#[test]
+fn clipped_pixels_do_not_collide() {
+    let chip8 = draw_over_right_edge(EdgeQuirk::Clip, 0);
+    assert_eq!(chip8.registers()[0xF], 0);
+    assert_eq!(chip8.display().get(0, 0), 1);
+}
+
+#[test]
+fn wrapped_pixels_collide() {
+    let chip8 = draw_over_right_edge(EdgeQuirk::Wrap, 0);
+    assert_eq!(chip8.registers()[0xF], 1);
+    assert_eq!(chip8.display().get(0, 0), 0);
+}
+
+#[test]
+fn on_screen_pixels_collide_in_both_modes() {
+    for edge in [EdgeQuirk::Clip, EdgeQuirk::Wrap] {
+        let chip8 = draw_over_right_edge(edge, 61);
+        assert_eq!(chip8.registers()[0xF], 1, "{:?}", edge);
+        assert_eq!(chip8.display().get(61, 0), 0, "{:?}", edge);
+    }
+}
+
+#[test]
+fn key_tapped_within_a_frame_is_latched_by_key_wait() {
+    // LD V0, K; JP 0x202
+    let mut chip8 = chip8_with("F00A 1202");
+    chip8.push_key_event(0x5, true);
+    chip8.push_key_event(0x5, false);
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.registers()[0], 0x5);
+    assert_eq!(chip8.pc(), 0x202);
+}
+
+#[test]
+fn exit_halts_the_program() {
+    // LD V0, 1; EXIT（SUPER-CHIP）
+    let mut chip8 = Chip8::new();
+    chip8.set_variant(Variant::SuperChip);
+    chip8.load_hex("6001 00FD").unwrap();
+    steps(&mut chip8, 2);
+    assert!(chip8.is_halted());
+    assert_eq!(chip8.registers()[0], 1);
+}
+
+#[test]
+fn se_vx_vy_with_nonzero_low_nibble_is_unknown() {
+    let mut chip8 = chip8_with("5123");
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::UnknownOpcode(0x5123));
+    assert_eq!(Instruction::decode(0x5123, Variant::Chip8), None);
+}
+
+#[test]
+fn se_vx_vy_skips_when_equal() {
+    let mut chip8 = chip8_with("5120");
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x204);
+}
+
+#[test]
+fn sne_vx_vy_with_nonzero_low_nibble_is_unknown() {
+    let mut chip8 = chip8_with("9121");
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::UnknownOpcode(0x9121));
+}
+
+// V1, V2, V3に1, 2, 3を代入し，Iを0x300にするプログラム
+const SET_V1_TO_V3: &str = "6101 6202 6303 A300";
+
+#[test]
+fn save_range_ascending() {
+    let mut chip8 = xo_chip_with(&format!("{} 5132", SET_V1_TO_V3));
+    steps(&mut chip8, 5);
+    assert_eq!(chip8.memory()[0x300..0x304], [1, 2, 3, 0]);
+}
+
+#[test]
+fn save_range_descending() {
+    let mut chip8 = xo_chip_with(&format!("{} 5312", SET_V1_TO_V3));
+    steps(&mut chip8, 5);
+    assert_eq!(chip8.memory()[0x300..0x304], [3, 2, 1, 0]);
+}
+
+#[test]
+fn load_range_ascending() {
+    let mut chip8 = xo_chip_with("A300 5133");
+    for (offset, value) in [0xA, 0xB, 0xC].into_iter().enumerate() {
+        chip8.poke(0x300 + offset as u16, value).unwrap();
+    }
+    steps(&mut chip8, 2);
+    assert_eq!(chip8.registers()[0..5], [0, 0xA, 0xB, 0xC, 0]);
+}
+
+#[test]
+fn load_range_descending() {
+    let mut chip8 = xo_chip_with("A300 5313");
+    for (offset, value) in [0xA, 0xB, 0xC].into_iter().enumerate() {
+        chip8.poke(0x300 + offset as u16, value).unwrap();
+    }
+    steps(&mut chip8, 2);
+    assert_eq!(chip8.registers()[0..5], [0, 0xC, 0xB, 0xA, 0]);
+}
+
+#[test]
+fn range_opcodes_are_unknown_outside_xo_chip() {
+    let mut chip8 = chip8_with("5132");
+    assert_eq!(
+        chip8.step().unwrap_err().kind,
+        ErrorKind::UnknownOpcode(0x5132)
+    );
+}
+
+#[test]
+fn seventeenth_nested_call_overflows_the_stack() {
+    // CALL 0x200（自分自身を呼び出し続ける）
+    let mut chip8 = chip8_with("2200");
+    steps(&mut chip8, STACK_SIZE);
+    assert_eq!(chip8.sp, 16);
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::StackOverflow);
+    assert_eq!(chip8.sp, 16);
+}
+
+#[test]
+fn stack_size_is_configurable() {
+    let mut chip8 = chip8_with("2200");
+    chip8.set_stack_size(12);
+    steps(&mut chip8, 12);
+    assert_eq!(chip8.step().unwrap_err().kind, ErrorKind::StackOverflow);
+}
+
+// テスト中に出力されたログを記録するロガー（traceは記録しない）
+struct CapturingLogger {
+    records: std::sync::Mutex<Vec<(log::Level, String)>>,
+}
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Debug
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let message = record.args().to_string();
+            self.records.lock().unwrap().push((record.level(), message));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger {
+    records: std::sync::Mutex::new(Vec::new()),
+};
+
+// 記録されたログのうち，messageを含むものの水準を返す
+// テストは並行して実行されるため，他のテストのログと区別できるmessageを指定する
+fn logged_levels(message: &str) -> Vec<log::Level> {
+    LOGGER
+        .records
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, m)| m.contains(message))
+        .map(|&(level, _)| level)
+        .collect()
+}
+
+// ロガーを設定する
+fn install_logger() {
+    // 他のテストで設定済みの場合は失敗するが，同じロガーなので問題ない
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(log::LevelFilter::Debug);
+}
+
+#[test]
+fn unknown_opcode_is_logged_as_error() {
+    install_logger();
+    let mut chip8 = chip8_with("E1FF");
+    assert!(chip8.step().is_err());
+    assert_eq!(logged_levels("unknown opcode 0xE1FF"), [log::Level::Error]);
+}
+
+#[test]
+fn jump_is_logged_as_debug() {
+    install_logger();
+    let mut chip8 = chip8_with("1A5A");
+    chip8.step().unwrap();
+    assert_eq!(logged_levels("jump to 0xA5A"), [log::Level::Debug]);
+}
+
+#[test]
+fn sprite_wraps_at_bottom_of_custom_display() {
+    let mut chip8 = Chip8::with_display_size(64, 48).unwrap();
+    chip8.quirks = Quirks {
+        v_edge: EdgeQuirk::Wrap,
+        ..Quirks::default()
+    };
+    // フォントの"0"（5行）を(0, 46)に描画する
+    chip8.load_hex("A000 6000 612E D015").unwrap();
+    steps(&mut chip8, 4);
+    let display = chip8.display();
+    assert_eq!((display.width(), display.height()), (64, 48));
+    let column: Vec<u8> = [46, 47, 0, 1, 2, 3]
+        .iter()
+        .map(|&y| display.get(0, y))
+        .collect();
+    assert_eq!(column, [1, 1, 1, 1, 1, 0]);
+    assert_eq!(display.get(1, 47), 0);
+    assert_eq!(display.get(1, 2), 1);
+}
+
+#[test]
+fn start_coordinate_wraps_by_custom_display_height() {
+    let mut chip8 = Chip8::with_display_size(64, 48).unwrap();
+    // フォントの"0"の1行目を(0, 50)に描画すると，(0, 2)に描画される
+    chip8.load_hex("A000 6000 6132 D011").unwrap();
+    steps(&mut chip8, 4);
+    assert_eq!(chip8.display().get(0, 2), 1);
+}
+
+#[test]
+fn oversized_display_is_rejected() {
+    let err = Chip8::with_display_size(MAX_DISPLAY_WIDTH + 1, 32)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.kind,
+        ErrorKind::InvalidDisplaySize(MAX_DISPLAY_WIDTH + 1, 32)
+    );
+}
+
+#[test]
+fn tick_hook_is_called_once_per_frame() {
+    let count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let mut chip8 = chip8_with("1200");
+    let hook_count = std::sync::Arc::clone(&count);
+    chip8.set_tick_hook(Box::new(move |_| {
+        hook_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }));
+    for _ in 0..30 {
+        chip8.run_frame().unwrap();
+    }
+    assert_eq!(count.load(std::sync::atomic::Ordering::Relaxed), 30);
+}
+
+#[test]
+fn tick_hook_runs_after_timers_update() {
+    // LD V0, 5; LD DT, V0; JP 0x204
+    let mut chip8 = chip8_with("6005 F015 1204");
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let hook_seen = std::sync::Arc::clone(&seen);
+    chip8.set_tick_hook(Box::new(move |chip8| {
+        hook_seen.lock().unwrap().push(chip8.delay_timer());
+    }));
+    chip8.run_frame().unwrap();
+    chip8.run_frame().unwrap();
+    assert_eq!(*seen.lock().unwrap(), [4, 3]);
+}
+
+// FX0A（LD V0, K）の後は自分自身へのジャンプで止まるプログラム
+const KEY_WAIT: &str = "F00A 1202";
+
+#[test]
+fn key_wait_on_press_latches_when_pressed() {
+    let mut chip8 = chip8_with(KEY_WAIT);
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.pc(), 0x200);
+
+    chip8.set_key(0x7, true);
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.registers()[0], 0x7);
+    assert_eq!(chip8.pc(), 0x202);
+}
+
+#[test]
+fn key_wait_on_release_latches_when_released() {
+    let quirks = Quirks {
+        key_wait: KeyWaitQuirk::OnRelease,
+        ..Quirks::default()
+    };
+    let mut chip8 = chip8_with_quirks(quirks, KEY_WAIT);
+    chip8.set_key(0x7, true);
+    chip8.run_frame().unwrap();
+    chip8.run_frame().unwrap();
+    // 押されている間は待ち続ける
+    assert_eq!(chip8.pc(), 0x200);
+    assert_eq!(chip8.registers()[0], 0);
+
+    chip8.set_key(0x7, false);
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.registers()[0], 0x7);
+    assert_eq!(chip8.pc(), 0x202);
+}
+
+#[test]
+fn find_in_memory_locates_font_eight() {
+    let chip8 = Chip8::new();
+    assert_eq!(
+        chip8.find_in_memory(&[0xF0, 0x90, 0xF0, 0x90, 0xF0]),
+        [0x028]
+    );
+}
+
+#[test]
+fn find_in_memory_reports_overlapping_matches() {
+    let mut chip8 = Chip8::new();
+    for addr in 0x200..0x204 {
+        chip8.poke(addr, 0xAA).unwrap();
+    }
+    assert_eq!(chip8.find_in_memory(&[0xAA, 0xAA]), [0x200, 0x201, 0x202]);
+    assert!(chip8.find_in_memory(&[]).is_empty());
+}
+
+#[test]
+fn drawing_to_plane_two_only_sets_the_second_bit() {
+    // プレーン2を選択してフォントの"0"を(0, 0)に描画する
+    let mut chip8 = xo_chip_with("F201 A000 6000 D005");
+    steps(&mut chip8, 4);
+    assert_eq!(chip8.planes(), 0x02);
+    assert_eq!(chip8.display().get(0, 0), 0x02);
+    assert_eq!(chip8.display().get(4, 0), 0x00);
+}
+
+#[test]
+fn shifts_read_vy_in_vy_mode() {
+    let quirks = Quirks {
+        shift: ShiftQuirk::Vy,
+        ..Quirks::default()
+    };
+    // V0 = V1 >> 1（V1 = 0b0000_0101）
+    let mut chip8 = chip8_with_quirks(quirks, "60F0 6105 8016");
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.registers()[0x0], 0x02);
+    assert_eq!(chip8.registers()[0x1], 0x05);
+    assert_eq!(chip8.registers()[0xF], 1);
+
+    // V0 = V1 << 1（V1 = 0b1000_0001）
+    let mut chip8 = chip8_with_quirks(quirks, "600F 6181 801E");
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.registers()[0x0], 0x02);
+    assert_eq!(chip8.registers()[0x1], 0x81);
+    assert_eq!(chip8.registers()[0xF], 1);
+}
+
+// 何も描画しないフロントエンド
+struct NullFrontend;
+
+impl Frontend for NullFrontend {
+    fn render(&mut self, _display: &LogicalDisplay) {}
+}
+
+#[test]
+fn run_returns_after_stop_is_requested_from_another_thread() {
+    let (flag_tx, flag_rx) = std::sync::mpsc::channel();
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    let handle = std::thread::spawn(move || {
+        // 無限ループするプログラムを実行する
+        let mut chip8 = Chip8::new();
+        flag_tx.send(chip8.running_flag()).unwrap();
+        let result = chip8.run(&[0x12, 0x00], &mut NullFrontend);
+        done_tx.send(()).unwrap();
+        result
+    });
+
+    let running = flag_rx.recv().unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    running.store(false, Ordering::Relaxed);
+    done_rx
+        .recv_timeout(Duration::from_secs(1))
+        .expect("run did not stop");
+    assert!(handle.join().unwrap().is_ok());
+}
+
+#[test]
+fn run_returns_immediately_if_stop_was_requested() {
+    let mut chip8 = Chip8::new();
+    chip8.request_stop();
+    chip8.run(&[0x12, 0x00], &mut NullFrontend).unwrap();
+    assert_eq!(chip8.pc(), 0x200);
+}
+
+#[test]
+fn protected_region_rejects_register_store() {
+    // I = 0x100からV0〜V2を書き込む
+    let mut chip8 = chip8_with("A100 F255");
+    chip8.set_protect_interpreter_region(true);
+    steps(&mut chip8, 1);
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::ProtectedWrite(0x100));
+    assert_eq!(chip8.memory()[0x100], 0x00);
+}
+
+#[test]
+fn protected_region_is_writable_by_default() {
+    let mut chip8 = chip8_with("A100 6042 F055");
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.memory()[0x100], 0x42);
+}
+
+#[test]
+fn protected_region_rejects_bcd() {
+    let mut chip8 = chip8_with("A1FF F033");
+    chip8.set_protect_interpreter_region(true);
+    steps(&mut chip8, 1);
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::ProtectedWrite(0x1FF));
+}
+
+#[test]
+fn key_wait_times_out_after_the_budget() {
+    let mut chip8 = chip8_with(KEY_WAIT);
+    chip8.set_key_wait_timeout(Some(3));
+    let mut frames = 0;
+    let err = loop {
+        match chip8.run_frame() {
+            Ok(()) => frames += 1,
+            Err(err) => break err,
+        }
+        assert!(frames < 10, "key wait did not time out");
+    };
+    assert_eq!(err.kind, ErrorKind::KeyWaitTimeout(3));
+    assert_eq!(frames, 3);
+    assert_eq!(err.pc, Some(0x200));
+}
+
+#[test]
+fn key_wait_is_unlimited_by_default() {
+    let mut chip8 = chip8_with(KEY_WAIT);
+    for _ in 0..100 {
+        chip8.run_frame().unwrap();
+    }
+    assert_eq!(chip8.pc, 0x200);
+}
+
+#[test]
+fn poked_value_is_read_back_by_register_load() {
+    // I = 0x300からV0〜V1に読み込む
+    let mut chip8 = chip8_with("A300 F165");
+    chip8.poke(0x300, 0x12).unwrap();
+    chip8.poke(0x301, 0x34).unwrap();
+    steps(&mut chip8, 2);
+    assert_eq!(chip8.peek_register(0x0), Some(0x12));
+    assert_eq!(chip8.peek_register(0x1), Some(0x34));
+    assert_eq!(chip8.peek(0x300), Some(0x12));
+}
+
+#[test]
+fn peek_and_poke_check_bounds() {
+    let mut chip8 = Chip8::new();
+    assert_eq!(chip8.peek(0x1000), None);
+    assert!(chip8.poke(0x1000, 0x00).is_err());
+    assert_eq!(chip8.peek_register(0x10), None);
+    assert!(chip8.poke_register(0x10, 0x00).is_err());
+    chip8.poke_register(0x3, 0x99).unwrap();
+    assert_eq!(chip8.registers()[0x3], 0x99);
+}
+
+#[test]
+fn setting_sound_timer_to_zero_does_not_beep() {
+    let mut chip8 = chip8_with("6000 F018");
+    steps(&mut chip8, 2);
+    assert!(!chip8.take_beep_started());
+    assert!(!chip8.is_beeping());
+
+    // 鳴っているブザーは0を代入するとすぐに止まる
+    let mut chip8 = chip8_with("6005 F018 6000 F018");
+    steps(&mut chip8, 2);
+    assert!(chip8.take_beep_started());
+    steps(&mut chip8, 2);
+    assert!(!chip8.is_beeping());
+    assert!(!chip8.take_beep_started());
+}
+
+#[test]
+fn setting_delay_timer_to_zero_reads_zero_immediately() {
+    let mut chip8 = chip8_with("6005 F015 6000 F015 61FF F107");
+    steps(&mut chip8, 6);
+    assert_eq!(chip8.registers()[0x1], 0);
+}
+
+#[test]
+fn advance_converts_elapsed_time_into_cycles_and_ticks() {
+    // 600Hzで100msは60命令と6回のタイマーの更新になる
+    let mut chip8 = chip8_with("60FF F015 1204");
+    assert_eq!(chip8.advance(Duration::from_millis(100)).unwrap(), 60);
+    assert_eq!(chip8.cycle_count(), 60);
+    assert_eq!(chip8.delay_timer(), 0xFF - 6);
+}
+
+#[test]
+fn advance_carries_the_remainder_between_calls() {
+    let mut chip8 = chip8_with("60FF F015 1204");
+    let executed: usize = [33, 33, 34]
+        .into_iter()
+        .map(|ms| chip8.advance(Duration::from_millis(ms)).unwrap())
+        .sum();
+    assert_eq!(executed, 60);
+    assert_eq!(chip8.delay_timer(), 0xFF - 6);
+}
+
+#[test]
+fn index_wraps_at_the_memory_size() {
+    // I = 0xFFF; V0 = 2; ADD I, V0
+    let mut chip8 = chip8_with("AFFF 6002 F01E");
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.i(), 0x001);
+
+    // XO-CHIPでは0x1000を超えても折り返さない
+    let mut chip8 = xo_chip_with("AFFF 6002 F01E");
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.i(), 0x1001);
+
+    // 64KBの末尾を超えると0x10000で折り返す
+    let mut chip8 = xo_chip_with("F000 FFFF 6002 F01E");
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.i(), 0x0001);
+}
+
+#[test]
+fn register_store_wraps_at_the_memory_size() {
+    // I = 0xFFFからV0〜V1（0x11，0x22）を書き込む
+    let mut chip8 = chip8_with("6011 6122 AFFF F155");
+    steps(&mut chip8, 4);
+    assert_eq!(chip8.peek(0xFFF), Some(0x11));
+    assert_eq!(chip8.peek(0x000), Some(0x22));
+
+    let mut chip8 = xo_chip_with("6011 6122 AFFF F155");
+    steps(&mut chip8, 4);
+    assert_eq!(chip8.peek(0x1000), Some(0x22));
+
+    let mut chip8 = xo_chip_with("6011 6122 F000 FFFF F155");
+    steps(&mut chip8, 4);
+    assert_eq!(chip8.peek(0xFFFF), Some(0x11));
+    assert_eq!(chip8.peek(0x0000), Some(0x22));
+}
+
+#[test]
+fn register_range_wraps_at_the_memory_size() {
+    // I = 0xFFFFからV0〜V1をSAVEし，LOADでV2〜V3に読み戻す（書き込みは常に折り返し，読み込みはWrapの設定で折り返す）
+    let mut chip8 = xo_chip_with("6011 6122 F000 FFFF 5012 5233");
+    chip8.set_unmapped_read_policy(UnmappedReadPolicy::Wrap);
+    steps(&mut chip8, 5);
+    assert_eq!(chip8.peek(0xFFFF), Some(0x11));
+    assert_eq!(chip8.peek(0x0000), Some(0x22));
+    assert_eq!(chip8.registers()[0x2..=0x3], [0x11, 0x22]);
+}
+
+#[test]
+fn pc_past_the_end_of_64k_is_an_error() {
+    let mut chip8 = xo_chip_with("");
+    chip8.poke(0xFFFE, 0x60).unwrap();
+    chip8.pc = 0xFFFE;
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MemoryOutOfRange(0x10000));
+
+    // 条件付きスキップで末尾を超える場合も同じ
+    let mut chip8 = xo_chip_with("");
+    chip8.poke(0xFFFC, 0x30).unwrap();
+    chip8.pc = 0xFFFC;
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MemoryOutOfRange(0x10000));
+}
+
+#[test]
+fn pc_past_the_end_of_64k_wraps_with_pc_wrap() {
+    let mut chip8 = Chip8Builder::new()
+        .variant(Variant::XoChip)
+        .quirks(Quirks {
+            pc_wrap: true,
+            ..Quirks::default()
+        })
+        .build()
+        .unwrap();
+    chip8.poke(0xFFFC, 0x30).unwrap();
+    chip8.pc = 0xFFFC;
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x0000);
+}
+
+#[test]
+fn display_region_reports_only_draws_inside_it() {
+    // フォントの"0"を(x, 0)に描画して停止する
+    let draw_at = |x: u8| {
+        let mut chip8 = chip8_with(&format!("A000 60{:02X} 6100 D015 1208", x));
+        chip8.watch_display_region(32, 0, 16, 8);
+        chip8.run_frame().unwrap();
+        chip8
+    };
+
+    let mut outside = draw_at(0);
+    assert!(!outside.take_display_region_changed());
+
+    let mut inside = draw_at(40);
+    assert!(inside.take_display_region_changed());
+    inside.run_frame().unwrap();
+    assert!(!inside.take_display_region_changed());
+}
+
+#[test]
+fn display_region_is_clamped_when_the_display_shrinks() {
+    let lores = Chip8::new().save_state();
+    let mut chip8 = Chip8::with_display_size(128, 64).unwrap();
+    chip8.watch_display_region(100, 40, 20, 20);
+    chip8.load_state(&lores).unwrap();
+    chip8.run_frame().unwrap();
+    // 領域がディスプレイの外に出ても監視は続けられる
+    assert!(chip8.take_display_region_changed());
+    chip8.run_frame().unwrap();
+    assert!(!chip8.take_display_region_changed());
+}
+
+#[test]
+fn draw_with_vf_as_coordinate_sets_vf_once_at_the_end() {
+    // VF = 8を座標に"0"を(8, 8)に描画する（衝突なし）
+    let mut chip8 = chip8_with("A000 6F08 DFF5 6F08 DFF5");
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.registers()[0xF], 0);
+    assert_eq!(chip8.display().get(8, 8), 1);
+    assert_eq!(chip8.display().get(8, 12), 1);
+
+    // 同じ位置にもう一度描画すると全ての行が消え，VFは衝突の結果になる
+    steps(&mut chip8, 2);
+    assert_eq!(chip8.registers()[0xF], 1);
+    assert!((8..13).all(|y| chip8.display().get(8, y) == 0));
+}
+
+// 指定されたフレーム数だけrunを続けるフロントエンド
+struct FrameLimit {
+    frames: usize,                    // 残りのフレーム数
+    running: Option<Arc<AtomicBool>>, // runを続けるかを表すフラグ
+}
+
+impl Frontend for FrameLimit {
+    fn render(&mut self, _display: &LogicalDisplay) {}
+
+    fn attach_running_flag(&mut self, running: Arc<AtomicBool>) {
+        self.running = Some(running);
+    }
+
+    fn update(&mut self, _chip8: &mut Chip8) {
+        self.frames -= 1;
+        if self.frames == 0 {
+            if let Some(running) = &self.running {
+                running.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[test]
+fn run_paces_frames_with_the_clock() {
+    let clock = MockClock::new();
+    let start = clock.now();
+    let mut chip8 = Chip8::new();
+    chip8.set_clock(Box::new(clock.clone()));
+    let mut frontend = FrameLimit {
+        frames: 30,
+        running: None,
+    };
+    // DT = 0xFF; JP 0x204
+    chip8
+        .run(&[0x60, 0xFF, 0xF0, 0x15, 0x12, 0x04], &mut frontend)
+        .unwrap();
+
+    // 実際には待たずに，1フレームごとに1/60秒ずつ時計が進む
+    assert_eq!(clock.now() - start, FRAME_DURATION * 30);
+    assert_eq!(chip8.delay_timer(), 0xFF - 30);
+}
+
+#[test]
+fn advance_with_clock_follows_the_mock_clock() {
+    let clock = MockClock::new();
+    let mut chip8 = chip8_with("1200");
+    chip8.set_clock(Box::new(clock.clone()));
+    // 初回は基準の時刻を記録するだけ
+    assert_eq!(chip8.advance_with_clock().unwrap(), 0);
+    clock.advance(Duration::from_millis(50));
+    assert_eq!(chip8.advance_with_clock().unwrap(), 30);
+    assert_eq!(chip8.advance_with_clock().unwrap(), 0);
+}
+
+#[test]
+fn tick_makes_the_same_progress_as_run_frame() {
+    // ST = 3; "0"を描画してから加算を繰り返す
+    const PROGRAM: &str = "6003 F018 A000 D005 7101 1208";
+    let mut ticked = chip8_with(PROGRAM);
+    let mut framed = chip8_with(PROGRAM);
+
+    let first = ticked.tick().unwrap();
+    framed.run_frame().unwrap();
+    assert_eq!(
+        first,
+        TickResult {
+            redraw: true,
+            beep: true,
+            halted: false,
+        }
+    );
+
+    for _ in 0..5 {
+        let result = ticked.tick().unwrap();
+        framed.run_frame().unwrap();
+        assert!(!result.redraw);
+        assert_eq!(result.beep, framed.is_beeping());
+    }
+    assert_eq!(ticked.registers(), framed.registers());
+    assert_eq!(ticked.pc(), framed.pc());
+    assert_eq!(ticked.cycle_count(), framed.cycle_count());
+    assert_eq!(ticked.sound_timer(), framed.sound_timer());
+    assert_eq!(ticked.frame_hash(), framed.frame_hash());
+}
+
+#[test]
+fn pre_step_hook_vetoes_an_opcode() {
+    let mut chip8 = chip8_with("6001 6142 6203");
+    chip8.set_pre_step_hook(Some(Box::new(|_, opcode| opcode != 0x6142)));
+    steps(&mut chip8, 1);
+    assert_eq!(chip8.pc(), 0x202);
+
+    // 拒否された命令は実行されず，pcも進まない
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.pc(), 0x202);
+    assert_eq!(chip8.registers()[0x1], 0x00);
+    assert_eq!(chip8.cycle_count(), 1);
+
+    chip8.set_pre_step_hook(None);
+    steps(&mut chip8, 1);
+    assert_eq!(chip8.registers()[0x1], 0x42);
+}
+
+#[test]
+fn post_step_hook_sees_executed_opcodes() {
+    let executed = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut chip8 = chip8_with("6001 6142 6203");
+    let log = Arc::clone(&executed);
+    chip8.set_post_step_hook(Some(Box::new(move |chip8, opcode| {
+        log.lock().unwrap().push((opcode, chip8.pc()));
+    })));
+    steps(&mut chip8, 3);
+    assert_eq!(
+        *executed.lock().unwrap(),
+        [(0x6001, 0x202), (0x6142, 0x204), (0x6203, 0x206)]
+    );
+}
+
+#[test]
+fn return_with_an_empty_stack_is_an_error() {
+    let mut chip8 = chip8_with("00EE");
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::StackUnderflow);
+    assert_eq!(err.pc, Some(0x200));
+    assert_eq!(chip8.stack_depth(), 0);
+}
+
+// depth段の入れ子のサブルーチンを呼び出して戻り，0x202で停止するプログラム
+//
+// 0x200でCALL 0x300を実行し，0x300 + 4kのサブルーチンは次のサブルーチンを呼び出してから戻る．
+// 最も深いサブルーチンはすぐに戻る．
+fn nested_calls(depth: usize) -> Chip8 {
+    let mut rom = vec![0x23, 0x00, 0x12, 0x02];
+    rom.resize(0x100, 0x00);
+    for level in 1..depth {
+        let next = 0x2300 + 4 * level as u16;
+        rom.extend_from_slice(&next.to_be_bytes());
+        rom.extend_from_slice(&[0x00, 0xEE]);
+    }
+    rom.extend_from_slice(&[0x00, 0xEE]);
+    let mut chip8 = Chip8::new();
+    chip8.load(&rom);
+    chip8
+}
+
+#[test]
+fn nested_calls_fill_the_stack_and_return_through_every_level() {
+    let mut chip8 = nested_calls(STACK_SIZE);
+    for level in 0..STACK_SIZE {
+        chip8.step().unwrap();
+        assert_eq!(chip8.stack_depth(), level + 1);
+        assert_eq!(chip8.pc(), 0x300 + 4 * level as u16);
+    }
+
+    // 最も深いサブルーチンから順に，呼び出した命令の次に戻る
+    for level in (0..STACK_SIZE).rev() {
+        chip8.step().unwrap();
+        assert_eq!(chip8.stack_depth(), level);
+        let expected = if level == 0 {
+            0x202
+        } else {
+            0x302 + 4 * (level as u16 - 1)
+        };
+        assert_eq!(chip8.pc(), expected, "level {}", level);
+        if level > 0 {
+            // 戻った先は呼び出したサブルーチンのRET
+            assert_eq!(chip8.opcode_at(chip8.pc()), Some(0x00EE));
+        }
+    }
+}
+
+#[test]
+fn call_beyond_the_stack_size_is_an_error() {
+    let mut chip8 = nested_calls(STACK_SIZE + 1);
+    steps(&mut chip8, STACK_SIZE);
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::StackOverflow);
+
+    // COSMAC VIPの12段
+    let mut chip8 = nested_calls(13);
+    chip8.set_stack_size(12);
+    steps(&mut chip8, 12);
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::StackOverflow);
+}
+
+#[test]
+fn run_with_timeout_stops_a_tight_loop() {
+    let mut chip8 = chip8_with("1200");
+    let limit = Duration::from_millis(50);
+    let start = Instant::now();
+    let executed = chip8.run_with_timeout(limit).unwrap();
+    let elapsed = start.elapsed();
+    assert!(executed > 0);
+    assert!(elapsed >= limit);
+    assert!(
+        elapsed < limit + Duration::from_millis(500),
+        "{:?}",
+        elapsed
+    );
+    assert_eq!(chip8.cycle_count(), executed as u64);
+}
+
+#[test]
+fn run_cycles_with_timeout_stops_at_the_cycle_count_first() {
+    let mut chip8 = chip8_with("1200");
+    let executed = chip8
+        .run_cycles_with_timeout(25, Duration::from_secs(10))
+        .unwrap();
+    assert_eq!(executed, 25);
+}
+
+#[test]
+fn unknown_opcode_error_carries_pc_and_opcode() {
+    let mut chip8 = chip8_with("1234");
+    chip8.poke(0x234, 0xFF).unwrap();
+    chip8.poke(0x235, 0xFF).unwrap();
+    steps(&mut chip8, 1);
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::UnknownOpcode(0xFFFF));
+    assert_eq!(err.pc, Some(0x234));
+    assert_eq!(err.opcode, Some(0xFFFF));
+    assert_eq!(err.to_string(), "unknown opcode 0xFFFF at pc 0x0234");
+}
+
+#[test]
+fn arithmetic_with_vf_as_destination_leaves_the_flag() {
+    // （VFの初期値，V1，命令，VFに残るフラグ）．いずれも演算の結果はフラグと異なる
+    let cases = [
+        (0xF0, 0x20, "8F14", 1), // ADD: 0x110で桁あふれ
+        (0x10, 0x20, "8F14", 0), // ADD: 0x30
+        (0x30, 0x10, "8F15", 1), // SUB: 0x20で借りなし
+        (0x10, 0x30, "8F15", 0), // SUB: 0xE0で借りあり
+        (0x31, 0x00, "8FF6", 1), // SHR: 0x18で1が溢れる
+        (0x32, 0x00, "8FF6", 0), // SHR: 0x19
+        (0x10, 0x30, "8F17", 1), // SUBN: 0x20で借りなし
+        (0x30, 0x10, "8F17", 0), // SUBN: 0xE0で借りあり
+        (0x81, 0x00, "8FFE", 1), // SHL: 0x02で1が溢れる
+        (0x41, 0x00, "8FFE", 0), // SHL: 0x82
+    ];
+    for (vf, v1, opcode, flag) in cases {
+        let mut chip8 = chip8_with(&format!("6F{:02X} 61{:02X} {}", vf, v1, opcode));
+        steps(&mut chip8, 3);
+        assert_eq!(
+            chip8.registers()[0xF],
+            flag,
+            "{} with VF={:#04X}",
+            opcode,
+            vf
+        );
+    }
+}
+
+#[test]
+fn scroll_fills_edges_according_to_the_scroll_quirk() {
+    // 四隅の画素を立て，SCD 1（00C1），SCR（00FB），SCL（00FC）を実行した後の画素
+    let scrolled = |scroll: ScrollQuirk, opcode: &str| {
+        let mut chip8 = Chip8Builder::new()
+            .variant(Variant::SuperChip)
+            .quirks(Quirks {
+                scroll,
+                ..Quirks::default()
+            })
+            .display_size(128, 64)
+            .build()
+            .unwrap();
+        for (x, y) in [(0, 0), (127, 0), (0, 63), (127, 63)] {
+            chip8.display.set(x, y, 1);
+        }
+        chip8.load_hex(opcode).unwrap();
+        chip8.step().unwrap();
+        chip8
+    };
+
+    // 下端の行は消えて上端の行は空く（Wrapでは上端に戻る）
+    let blank = scrolled(ScrollQuirk::Blank, "00C1");
+    assert_eq!((blank.display.get(0, 0), blank.display.get(0, 1)), (0, 1));
+    assert_eq!(blank.display.get(127, 63), 0);
+    let wrap = scrolled(ScrollQuirk::Wrap, "00C1");
+    assert_eq!((wrap.display.get(0, 0), wrap.display.get(127, 0)), (1, 1));
+    assert_eq!(wrap.display.get(0, 1), 1);
+
+    // 右端の列は消えて左端の4列は空く（Wrapでは左端に戻る）
+    let blank = scrolled(ScrollQuirk::Blank, "00FB");
+    assert_eq!((blank.display.get(0, 0), blank.display.get(4, 0)), (0, 1));
+    assert_eq!(blank.display.get(3, 63), 0);
+    let wrap = scrolled(ScrollQuirk::Wrap, "00FB");
+    assert_eq!((wrap.display.get(4, 0), wrap.display.get(3, 0)), (1, 1));
+    assert_eq!(wrap.display.get(3, 63), 1);
+
+    // 左端の列は消えて右端の4列は空く（Wrapでは右端に戻る）
+    let blank = scrolled(ScrollQuirk::Blank, "00FC");
+    assert_eq!(
+        (blank.display.get(127, 0), blank.display.get(123, 0)),
+        (0, 1)
+    );
+    assert_eq!(blank.display.get(124, 63), 0);
+    let wrap = scrolled(ScrollQuirk::Wrap, "00FC");
+    assert_eq!((wrap.display.get(123, 0), wrap.display.get(124, 0)), (1, 1));
+    assert_eq!(wrap.display.get(124, 63), 1);
+}
+
+#[test]
+fn stress_rom_fills_the_whole_screen() {
+    let mut chip8 = Chip8::new();
+    chip8.load(&stress_rom());
+    let lit = |chip8: &Chip8| chip8.display.pixels().iter().filter(|&&p| p != 0).count();
+
+    // 最初の1周（0x214のJP 0x200に達するまで）で画面全体が点灯する
+    let mut cycles = 0;
+    while chip8.pc() != 0x214 {
+        chip8.step().unwrap();
+        cycles += 1;
+        assert!(cycles < 1000, "first pass did not finish");
+    }
+    assert_eq!(lit(&chip8), 64 * 32);
+
+    // 以降は1周ごとに全体が消灯と点灯を繰り返す
+    let mut fully_lit = 0;
+    for _ in 0..5000 {
+        chip8.step().unwrap();
+        if chip8.pc() == 0x214 && lit(&chip8) == 64 * 32 {
+            fully_lit += 1;
+        }
+    }
+    assert!(fully_lit >= 10, "{}", fully_lit);
+}
+
+#[test]
+fn display_wait_keeps_the_drawn_sprite_until_the_deferred_erase() {
+    // "0"をXORで描画し，ADD V1, 1を8回実行して戻るループ（1周が1フレームの10命令）
+    let program = format!("A000 6000 D005 {} 1204", ["7101"; 8].join(" "));
+    let lit_at_frame_end = |display_wait: DisplayWaitQuirk| {
+        let quirks = Quirks {
+            display_wait,
+            ..Quirks::default()
+        };
+        let mut chip8 = chip8_with_quirks(quirks, &program);
+        (0..6)
+            .map(|_| {
+                chip8.run_frame().unwrap();
+                chip8.display.get(0, 0)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // すぐに描画する場合は，フレームごとに描画と消去が交互に見える
+    assert_eq!(
+        lit_at_frame_end(DisplayWaitQuirk::Immediate),
+        [1, 0, 1, 0, 1, 0]
+    );
+    // 垂直帰線期間を待つ場合は，各フレームの最初のDRWが次のフレームまで待つため，
+    // 消去を待つ間のフレームにも描画した状態が残る（COSMAC VIPのちらつき）
+    assert_eq!(
+        lit_at_frame_end(DisplayWaitQuirk::Vblank),
+        [0, 1, 1, 0, 0, 1]
+    );
+}
+
+#[test]
+fn rnd_masks_the_value_from_a_custom_rand_source() {
+    // RND V0, 0x0F; RND V1, 0xF0; RND V2, 0x00
+    let mut chip8 = chip8_with("C00F C1F0 C200");
+    chip8.set_rand_source(Box::new(FixedRand(0xA5)));
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.registers()[..3], [0xA5 & 0x0F, 0xA5 & 0xF0, 0x00]);
+}
+
+#[test]
+fn sys_is_skipped_by_default() {
+    let mut chip8 = chip8_with("0123 6001");
+    steps(&mut chip8, 2);
+    assert_eq!(chip8.pc(), 0x204);
+    assert_eq!(chip8.registers()[0], 1);
+}
+
+#[test]
+fn sys_errors_with_error_quirk() {
+    let quirks = Quirks {
+        sys: SysQuirk::Error,
+        ..Quirks::default()
+    };
+    let mut chip8 = chip8_with_quirks(quirks, "0123");
+    let err = chip8.step().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::UnknownOpcode(0x0123));
+    assert_eq!(err.pc, Some(0x200));
+}
+
+// V0に値を入れ，I = 0x300からBCDを書き込んだ3バイト
+fn bcd_of(value: u8) -> [Option<u8>; 3] {
+    let mut chip8 = chip8_with(&format!("60{:02X} A300 F033", value));
+    steps(&mut chip8, 3);
+    [chip8.peek(0x300), chip8.peek(0x301), chip8.peek(0x302)]
+}
+
+#[test]
+fn bcd_stores_hundreds_tens_and_ones() {
+    assert_eq!(bcd_of(234), [Some(2), Some(3), Some(4)]);
+    assert_eq!(bcd_of(0), [Some(0), Some(0), Some(0)]);
+    assert_eq!(bcd_of(255), [Some(2), Some(5), Some(5)]);
+}
+
+#[test]
+fn bcd_at_the_end_of_memory_wraps_to_the_start() {
+    // I = 0xFFE: 2，3をメモリの末尾に書き，4は先頭に折り返す
+    let mut chip8 = chip8_with("60EA AFFE F033");
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.peek(0xFFE), Some(2));
+    assert_eq!(chip8.peek(0xFFF), Some(3));
+    assert_eq!(chip8.peek(0x000), Some(4));
+    assert_eq!(chip8.pc(), 0x206);
+}
+
+#[test]
+fn edges_are_handled_per_axis() {
+    // (60, 30)に8x4の塗りつぶしたスプライトを描画する（右下の角が画面の外）
+    let drawn = |h_edge: EdgeQuirk, v_edge: EdgeQuirk| {
+        let quirks = Quirks {
+            h_edge,
+            v_edge,
+            ..Quirks::default()
+        };
+        let mut chip8 = chip8_with_quirks(quirks, "A300 603C 611E D014");
+        for addr in 0x300..0x304 {
+            chip8.poke(addr, 0xFF).unwrap();
+        }
+        steps(&mut chip8, 4);
+        let d = chip8.display();
+        // 画面内，右端から折り返した位置，下端から折り返した位置，右下から折り返した位置
+        [d.get(63, 31), d.get(0, 31), d.get(63, 0), d.get(0, 0)]
+    };
+
+    assert_eq!(drawn(EdgeQuirk::Clip, EdgeQuirk::Clip), [1, 0, 0, 0]);
+    assert_eq!(drawn(EdgeQuirk::Clip, EdgeQuirk::Wrap), [1, 0, 1, 0]);
+    assert_eq!(drawn(EdgeQuirk::Wrap, EdgeQuirk::Clip), [1, 1, 0, 0]);
+    assert_eq!(drawn(EdgeQuirk::Wrap, EdgeQuirk::Wrap), [1, 1, 1, 1]);
+}
+
+#[test]
+fn disassemble_at_lists_instructions_from_pc() {
+    let chip8 = chip8_with("6005 A234 D015 00E0 1200");
+    assert_eq!(
+        chip8.disassemble_at(0x202, 3),
+        [
+            (0x202, 0xA234, "LD I, 0x234".to_string()),
+            (0x204, 0xD015, "DRW V0, V1, 5".to_string()),
+            (0x206, 0x00E0, "CLS".to_string()),
+        ]
+    );
+    // メモリの末尾で終わる
+    assert_eq!(chip8.disassemble_at(0xFFC, 10).len(), 2);
+}
+
+#[test]
+fn flipped_sprites_are_mirrored() {
+    // 左寄りの非対称な1行（11100000）を(0, 0)に描画する
+    let drawn = |flip: bool| {
+        let mut chip8 = chip8_with("A300 6000 D001 D001");
+        chip8.poke(0x300, 0xE0).unwrap();
+        chip8.set_flip_sprites(flip);
+        steps(&mut chip8, 3);
+        let row: Vec<_> = (0..8).map(|x| chip8.display().get(x, 0)).collect();
+        // 同じ位置にもう一度描画すると，反転していても全ての画素が衝突する
+        chip8.step().unwrap();
+        (row, chip8.registers()[0xF])
+    };
+
+    assert_eq!(drawn(false), (vec![1, 1, 1, 0, 0, 0, 0, 0], 1));
+    assert_eq!(drawn(true), (vec![0, 0, 0, 0, 0, 1, 1, 1], 1));
+}
+
+#[test]
+fn key_set_mid_frame_takes_effect_the_next_frame() {
+    // キー0が押されている間V1を増やし続けるループ
+    const COUNT_WHILE_PRESSED: &str = "6000 E0A1 7101 1202";
+
+    // 1フレーム目の5命令目の後にキー0を押す
+    let mut chip8 = chip8_with(COUNT_WHILE_PRESSED);
+    let mut executed = 0;
+    chip8.set_post_step_hook(Some(Box::new(move |chip8, _| {
+        executed += 1;
+        if executed == 5 {
+            chip8.set_key(0, true);
+        }
+    })));
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.registers()[1], 0);
+    chip8.run_frame().unwrap();
+    assert!(chip8.registers()[1] > 0);
+
+    // フレームごとのフックで押したキーも次のフレームから反映される
+    let mut chip8 = chip8_with(COUNT_WHILE_PRESSED);
+    chip8.set_tick_hook(Box::new(|chip8| chip8.set_key(0, true)));
+    chip8.run_cycles(5).unwrap();
+    assert_eq!(chip8.registers()[1], 0);
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.registers()[1], 0);
+    chip8.run_frame().unwrap();
+    assert!(chip8.registers()[1] > 0);
+}
+
+// 32バイトを0xFFで埋め，(0, 0)にDXY0を描画した後の点灯している画素数とVF
+fn draw_n_zero(variant: Variant) -> (usize, u8) {
+    let mut chip8 = Chip8Builder::new()
+        .variant(variant)
+        .display_size(128, 64)
+        .build()
+        .unwrap();
+    chip8.load_hex("A300 6000 6F05 D000").unwrap();
+    for addr in 0x300..0x320 {
+        chip8.poke(addr, 0xFF).unwrap();
+    }
+    steps(&mut chip8, 4);
+    let display = chip8.display();
+    let lit = display.pixels().iter().filter(|&&p| p != 0).count();
+    let all_in_square = (0..16).all(|y| (0..16).all(|x| display.get(x, y) == 1));
+    assert!(lit == 0 || all_in_square, "{:?}", variant);
+    (lit, chip8.registers()[0xF])
+}
+
+#[test]
+fn draw_with_zero_rows_is_a_no_op_on_chip8() {
+    // VFも変更しない
+    assert_eq!(draw_n_zero(Variant::Chip8), (0, 5));
+}
+
+#[test]
+fn draw_with_zero_rows_draws_16x16_on_super_chip() {
+    assert_eq!(draw_n_zero(Variant::SuperChip), (256, 0));
+    assert_eq!(draw_n_zero(Variant::XoChip), (256, 0));
+}
+
+#[test]
+fn hex_string_is_loaded_at_0x200() {
+    let mut chip8 = Chip8::new();
+    chip8.load_hex("00E0 A22A\n600C").unwrap();
+    let bytes: Vec<_> = (0x200..0x207)
+        .map(|addr| chip8.peek(addr).unwrap())
+        .collect();
+    assert_eq!(bytes, [0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C, 0x00]);
+    assert_eq!(chip8.pc(), 0x200);
+}
+
+#[test]
+fn invalid_hex_string_is_rejected() {
+    let mut chip8 = Chip8::new();
+    let err = chip8.load_hex("00E0 A22").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::OddHexLength(7));
+    let err = chip8.load_hex("00EG").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidHexDigit('G'));
+}
+
+#[test]
+fn display_wait_parks_only_the_first_draw_of_a_frame() {
+    // "0"を(0, 0)と(8, 0)に描画してからV2を増やす
+    let quirks = Quirks {
+        display_wait: DisplayWaitQuirk::Vblank,
+        ..Quirks::default()
+    };
+    let mut chip8 = chip8_with_quirks(quirks, "A000 6000 6108 D005 D105 7201 120C");
+
+    // 1フレーム目は最初のDRWで待ち，何も描画しない
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.pc(), 0x206);
+    assert_eq!(
+        (chip8.display().get(0, 0), chip8.display().get(8, 0)),
+        (0, 0)
+    );
+
+    // 2フレーム目は待ち終えたDRWに続いて，2つ目のDRWと後の計算も同じフレームで実行する
+    chip8.run_frame().unwrap();
+    assert_eq!(
+        (chip8.display().get(0, 0), chip8.display().get(8, 0)),
+        (1, 1)
+    );
+    assert_eq!(chip8.registers()[2], 1);
+}
+
+#[test]
+fn default_keymap_uses_the_canonical_layout() {
+    // キーボードの4行が，キーパッドの123C/456D/789E/A0BFの4行に対応する
+    let rows = ["1234", "qwer", "asdf", "zxcv"];
+    let keypad = [
+        [0x1, 0x2, 0x3, 0xC],
+        [0x4, 0x5, 0x6, 0xD],
+        [0x7, 0x8, 0x9, 0xE],
+        [0xA, 0x0, 0xB, 0xF],
+    ];
+    let chip8 = Chip8::new();
+    assert_eq!(chip8.keymap(), &DEFAULT_KEYMAP);
+    for (row, keys) in rows.iter().zip(keypad) {
+        for (c, key) in row.chars().zip(keys) {
+            assert!(DEFAULT_KEYMAP.contains(&(c, key)), "{} => {:X}", c, key);
+        }
+    }
+}
+
+#[test]
+fn keymap_can_be_customized() {
+    let mut keymap = DEFAULT_KEYMAP;
+    keymap[0] = ('9', 0x1);
+    let mut chip8 = Chip8::new();
+    chip8.set_keymap(keymap);
+    assert_eq!(chip8.keymap()[0], ('9', 0x1));
+}
+
+#[test]
+fn step_to_next_draw_stops_on_the_first_draw() {
+    // 4命令の計算の後に"0"を描画し，さらに描画を続ける
+    let mut chip8 = chip8_with("6000 6100 7001 A000 D015 D015");
+    assert_eq!(chip8.step_to_next_draw(100), Ok(5));
+    assert_eq!(chip8.pc(), 0x20A);
+    assert_eq!(chip8.display().get(1, 0), 1);
+
+    // 画面を変更しないまま上限に達した場合はエラー
+    let mut chip8 = chip8_with("7001 1200");
+    let err = chip8.step_to_next_draw(50).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::CycleLimitReached(50));
+}
+
+#[test]
+fn shared_display_is_never_torn() {
+    // 毎フレーム，8x15のスプライトで画面全体をXORで塗り，DTで次のフレームまで待つ
+    // フレームの途中では一部だけが塗られているが，フレームの終了時には全て点灯か全て消灯になる
+    let mut chip8 = Chip8Builder::new().cpu_hz(12_000).build().unwrap();
+    chip8
+        .load_hex("A220 6401 6100 6000 D01F 7008 3040 1208 710F 312D 1206 F415 F307 3300 1218 1204")
+        .unwrap();
+    for addr in 0x220..0x22F {
+        chip8.poke(addr, 0xFF).unwrap();
+    }
+    let shared = chip8.shared_display();
+    let done = Arc::new(AtomicBool::new(false));
+
+    let cpu = {
+        let done = Arc::clone(&done);
+        std::thread::spawn(move || {
+            for frame in 0..200 {
+                chip8.run_frame().unwrap();
+                let lit = chip8.display().pixels().iter().all(|&p| p != 0);
+                assert_eq!(lit, frame % 2 == 0);
+            }
+            done.store(true, Ordering::SeqCst);
+        })
+    };
+    let reader = std::thread::spawn(move || {
+        let mut reads = 0;
+        while !done.load(Ordering::SeqCst) {
+            let frame = shared.read();
+            let lit = frame.pixels().iter().filter(|&&p| p != 0).count();
+            assert!(
+                lit == 0 || lit == 64 * 32,
+                "torn frame with {} pixels lit",
+                lit
+            );
+            reads += 1;
+        }
+        reads
+    });
+
+    cpu.join().unwrap();
+    assert!(reader.join().unwrap() > 0);
+}
+
+#[test]
+fn reset_timers_clears_both_timers_and_the_beep() {
+    // V0 = 30; DT = V0; ST = V0; V1 = 7
+    let mut chip8 = chip8_with("601E F015 F018 6107");
+    steps(&mut chip8, 4);
+    assert!(chip8.is_beeping());
+    chip8.reset_timers();
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (0, 0));
+    assert!(!chip8.is_beeping());
+    assert!(!chip8.take_beep_started());
+    // 他の状態は変わらない
+    assert_eq!(chip8.registers()[..2], [30, 7]);
+    assert_eq!(chip8.pc(), 0x208);
+}
+
+// pc_wrapを有効にし，メモリの末尾の命令（0xFFE）から実行するChip8
+fn chip8_at_the_end_of_memory(opcode: u16) -> Chip8 {
+    let mut chip8 = Chip8::with_quirks(Quirks {
+        pc_wrap: true,
+        ..Quirks::default()
+    });
+    let [high, low] = opcode.to_be_bytes();
+    chip8.poke(0xFFE, high).unwrap();
+    chip8.poke(0xFFF, low).unwrap();
+    chip8.pc = 0xFFE;
+    chip8
+}
+
+#[test]
+fn pc_wraps_to_address_zero_with_pc_wrap() {
+    // LD V0, 1の次は0x000のLD V1, 0x42を実行する
+    let mut chip8 = chip8_at_the_end_of_memory(0x6001);
+    chip8.poke(0x000, 0x61).unwrap();
+    chip8.poke(0x001, 0x42).unwrap();
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x000);
+    chip8.step().unwrap();
+    assert_eq!(chip8.registers()[..2], [1, 0x42]);
+    assert_eq!(chip8.pc(), 0x002);
+}
+
+#[test]
+fn jump_and_call_at_the_end_of_memory_use_the_wrapped_pc() {
+    // JP 0xFFE（自分自身へのジャンプ）は停止として検出する
+    let mut chip8 = chip8_at_the_end_of_memory(0x1FFE);
+    chip8.set_halt_on_idle(true);
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0xFFE);
+    assert!(chip8.is_halted());
+
+    // CALL 0x300から戻ると折り返した0x000に進む
+    let mut chip8 = chip8_at_the_end_of_memory(0x2300);
+    chip8.poke(0x300, 0x00).unwrap();
+    chip8.poke(0x301, 0xEE).unwrap();
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x300);
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x000);
+}
+
+#[test]
+fn rnd_hook_records_the_raw_and_masked_values() {
+    // RND V0, 0x0F; RND V1, 0x3C
+    let mut chip8 = chip8_with("C00F C13C");
+    chip8.set_rand_source(Box::new(FixedRand(0xA5)));
+    let records = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let log = Arc::clone(&records);
+    chip8.set_rnd_hook(Some(Box::new(move |record| {
+        log.lock().unwrap().push(record)
+    })));
+    steps(&mut chip8, 2);
+    assert_eq!(
+        *records.lock().unwrap(),
+        [
+            RndRecord {
+                pc: 0x200,
+                raw: 0xA5,
+                kk: 0x0F,
+                result: 0x05
+            },
+            RndRecord {
+                pc: 0x202,
+                raw: 0xA5,
+                kk: 0x3C,
+                result: 0x24
+            },
+        ]
+    );
+}
+
+#[test]
+fn disassembly_names_call_targets_with_symbols() {
+    // CALL 0x300; CALL 0x400（名前なし）
+    let mut chip8 = chip8_with("2300 2400");
+    chip8.set_symbols(Symbols::parse("0300 draw_player").unwrap());
+    let lines: Vec<_> = chip8
+        .disassemble_at(0x200, 2)
+        .into_iter()
+        .map(|(_, _, text)| text)
+        .collect();
+    assert_eq!(lines, ["CALL draw_player", "CALL 0x400"]);
+}
+
+#[test]
+fn key_wait_parks_the_frame_but_advances_timers() {
+    // DT = 0x10; LD V1, K; ADD V2, 1; JP 0x208
+    let mut chip8 = chip8_with("6010 F015 F10A 7201 1208");
+    chip8.run_frame().unwrap();
+    assert!(chip8.is_waiting_for_key());
+    assert_eq!(chip8.pc(), 0x204);
+
+    // 待っている間は命令を進めないが，タイマーは減る
+    chip8.run_frame().unwrap();
+    assert!(chip8.is_waiting_for_key());
+    assert_eq!(chip8.pc(), 0x204);
+    assert_eq!(chip8.registers()[2], 0);
+    assert_eq!(chip8.delay_timer(), 0x10 - 2);
+
+    chip8.set_key(0x9, true);
+    chip8.run_frame().unwrap();
+    assert!(!chip8.is_waiting_for_key());
+    assert_eq!(chip8.registers()[1], 0x9);
+    assert_eq!(chip8.registers()[2], 1);
+}
+
+#[test]
+fn display_log_records_changes_at_their_cycles() {
+    // 3命令目で"0"を描画し，同じ位置への空のスプライト（0x300）は変化なし，6命令目でCLS
+    let mut chip8 = chip8_with("A000 6000 D005 A300 D001 00E0 120C");
+    chip8.set_display_log(true);
+    steps(&mut chip8, 7);
+    assert_eq!(
+        chip8.take_display_log(),
+        [
+            DisplayChange {
+                cycle: 2,
+                pixels: 14
+            },
+            DisplayChange {
+                cycle: 5,
+                pixels: 14
+            },
+        ]
+    );
+    // 取り出した変化は消える
+    assert!(chip8.take_display_log().is_empty());
+
+    // 記録しない場合は空
+    let mut chip8 = chip8_with("A000 6000 D005");
+    steps(&mut chip8, 3);
+    assert!(chip8.take_display_log().is_empty());
+}
+
+#[test]
+fn high_and_low_switch_the_resolution() {
+    // HIGH; "0"を描画; LOW
+    let mut chip8 = Chip8Builder::new()
+        .variant(Variant::SuperChip)
+        .build()
+        .unwrap();
+    chip8.load_hex("00FF A000 6000 D005 00FE").unwrap();
+    chip8.step().unwrap();
+    assert_eq!(
+        (chip8.display().width(), chip8.display().height()),
+        (128, 64)
+    );
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.display().get(0, 0), 1);
+    chip8.step().unwrap();
+    assert_eq!(
+        (chip8.display().width(), chip8.display().height()),
+        (64, 32)
+    );
+    assert!(chip8.display().pixels().iter().all(|&p| p == 0));
+}
+
+// "0"を(0, 0)に描画してからSCD 4（00C4）を実行し，左端の列の上から10画素を返す
+fn scrolled_down_by_4(hires: bool, lores_scroll: LoresScrollQuirk) -> Vec<u8> {
+    let mut chip8 = Chip8Builder::new()
+        .variant(Variant::SuperChip)
+        .quirks(Quirks {
+            lores_scroll,
+            ..Quirks::default()
+        })
+        .build()
+        .unwrap();
+    let mode = if hires { "00FF" } else { "00FE" };
+    chip8
+        .load_hex(&format!("{} A000 6000 D005 00C4", mode))
+        .unwrap();
+    steps(&mut chip8, 5);
+    (0..10).map(|y| chip8.display().get(0, y)).collect()
+}
+
+#[test]
+fn scroll_down_by_4_in_hires_and_lores() {
+    // "0"の左端の列は5画素とも点灯している
+    let shifted_by =
+        |n: usize| -> Vec<u8> { (0..10).map(|y| (y >= n && y < n + 5) as u8).collect() };
+    assert_eq!(
+        scrolled_down_by_4(true, LoresScrollQuirk::Full),
+        shifted_by(4)
+    );
+    assert_eq!(
+        scrolled_down_by_4(true, LoresScrollQuirk::Half),
+        shifted_by(4)
+    );
+    assert_eq!(
+        scrolled_down_by_4(false, LoresScrollQuirk::Full),
+        shifted_by(4)
+    );
+    // 低解像度で半分の移動量にする設定では2画素だけ移動する
+    assert_eq!(
+        scrolled_down_by_4(false, LoresScrollQuirk::Half),
+        shifted_by(2)
+    );
+
+    // SCD 0は何もしない
+    let mut chip8 = xo_chip_with("A000 6000 D005 00C0");
+    steps(&mut chip8, 4);
+    assert_eq!(chip8.display().get(0, 0), 1);
+}
+
+#[test]
+fn memory_map_reports_the_rom_extent() {
+    // 64バイト（ADD V0, 1を32個）のROM
+    let chip8 = chip8_with(&["7001"; 32].join(" "));
+    let map = chip8.memory_map();
+    let lines: Vec<_> = map.lines().collect();
+    assert!(
+        lines.contains(&"rom    0x0200-0x023F (64 bytes)"),
+        "{}",
+        map
+    );
+    assert!(
+        lines.contains(&"font   0x0000-0x004F (80 bytes)"),
+        "{}",
+        map
+    );
+    assert!(lines.contains(&"stack  0/16"), "{}", map);
+    // 1文字は64バイトで，I（0x000）とフォント，pc（0x200）の順に並ぶ
+    assert!(lines[0].starts_with("[IF......P..."), "{}", map);
+    assert_eq!(lines[0].len(), 64 + 2);
+
+    assert!(Chip8::new().memory_map().contains("rom    (not loaded)"));
+}
+
+#[test]
+fn load_store_advances_i_with_increment_quirk() {
+    // I = 0x300; LD [I], V2; LD V1, [I]
+    let with = |load_store: LoadStoreQuirk| {
+        let quirks = Quirks {
+            load_store,
+            ..Quirks::default()
+        };
+        let mut chip8 = chip8_with_quirks(quirks, "A300 F255 F165");
+        steps(&mut chip8, 2);
+        let after_store = chip8.i();
+        chip8.step().unwrap();
+        (after_store, chip8.i())
+    };
+    assert_eq!(with(LoadStoreQuirk::KeepI), (0x300, 0x300));
+    assert_eq!(with(LoadStoreQuirk::IncrementI), (0x303, 0x305));
+}
+
+#[test]
+fn load_store_follows_each_profile() {
+    // I = 0x300; LD [I], V2
+    for (profile, i) in [
+        (QuirkProfile::CosmacVip, 0x303),
+        (QuirkProfile::SuperChip, 0x300),
+        (QuirkProfile::XoChip, 0x303),
+    ] {
+        let mut chip8 = chip8_with_quirks(profile.quirks(), "A300 F255");
+        steps(&mut chip8, 2);
+        assert_eq!(chip8.i(), i, "{:?}", profile);
+    }
+}
+
+#[test]
+fn load_store_increment_wraps_at_the_end_of_memory() {
+    let quirks = Quirks {
+        load_store: LoadStoreQuirk::IncrementI,
+        ..Quirks::default()
+    };
+    // I = 0xFFE; LD [I], V2（0xFFE，0xFFF，0x000に書き込み，Iは0x001になる）
+    let mut chip8 = chip8_with_quirks(quirks, "62AB AFFE F255");
+    steps(&mut chip8, 3);
+    assert_eq!(chip8.i(), 0x001);
+    assert_eq!(chip8.peek(0x000), Some(0xAB));
+}
+
+#[test]
+fn fps_is_estimated_from_the_clock() {
+    let clock = MockClock::new();
+    let mut chip8 = chip8_with("1200");
+    chip8.set_clock(Box::new(clock.clone()));
+    assert_eq!(chip8.fps(), 0.0);
+
+    // 2秒間に120フレーム
+    for _ in 0..120 {
+        clock.advance(Duration::from_secs(2) / 120);
+        chip8.run_frame().unwrap();
+    }
+    assert_eq!(chip8.frames(), 120);
+    assert!((chip8.fps() - 60.0).abs() < 0.5, "{}", chip8.fps());
+
+    // 読み込み直すと数え直す
+    chip8.load_hex("1200").unwrap();
+    assert_eq!(chip8.frames(), 0);
+    assert_eq!(chip8.fps(), 0.0);
+}
+
+#[test]
+fn shifts_with_x_equal_to_y_agree_under_both_quirks() {
+    for shift in [ShiftQuirk::VxOnly, ShiftQuirk::Vy] {
+        let quirks = Quirks {
+            shift,
+            ..Quirks::default()
+        };
+        // V3 = V3 >> 1（V3 = 0b1000_0001）
+        let mut chip8 = chip8_with_quirks(quirks, "6381 8336");
+        steps(&mut chip8, 2);
+        assert_eq!(chip8.registers()[0x3], 0x40, "{:?}", shift);
+        assert_eq!(chip8.registers()[0xF], 1, "{:?}", shift);
+
+        // V3 = V3 << 1（V3 = 0b1000_0001）
+        let mut chip8 = chip8_with_quirks(quirks, "6381 833E");
+        steps(&mut chip8, 2);
+        assert_eq!(chip8.registers()[0x3], 0x02, "{:?}", shift);
+        assert_eq!(chip8.registers()[0xF], 1, "{:?}", shift);
+    }
+}
+
+#[test]
+fn shifts_into_vf_leave_the_shifted_out_bit() {
+    // VF = 0b1000_0001，V1 = 0b0000_0010として，VFをシフトする
+    let shift_vf = |shift, opcode| {
+        let quirks = Quirks {
+            shift,
+            ..Quirks::default()
+        };
+        let mut chip8 = chip8_with_quirks(quirks, &format!("6F81 6102 {}", opcode));
+        steps(&mut chip8, 3);
+        chip8.registers()[0xF]
+    };
+    // VxOnlyではVF自身（0x81），VyではV1（0x02）をシフトし，シフトした値ではなく溢れたビットが残る
+    assert_eq!(shift_vf(ShiftQuirk::VxOnly, "8F16"), 1);
+    assert_eq!(shift_vf(ShiftQuirk::Vy, "8F16"), 0);
+    assert_eq!(shift_vf(ShiftQuirk::VxOnly, "8F1E"), 1);
+    assert_eq!(shift_vf(ShiftQuirk::Vy, "8F1E"), 0);
+}
+
+// 範囲外の読み込みの設定をpolicyとし，メモリの最後のバイトを0xAAとしたChip8で命令を実行する
+//
+// 最後の命令の結果を返す（それまでの命令は成功するものとする）．
+fn run_past_the_end(
+    chip8: &mut Chip8,
+    policy: UnmappedReadPolicy,
+    n: usize,
+) -> Result<(), Chip8Error> {
+    chip8.set_unmapped_read_policy(policy);
+    let last = (chip8.bus.len() - 1) as u16;
+    chip8.poke(last, 0xAA).unwrap();
+    steps(chip8, n - 1);
+    chip8.step()
+}
+
+#[test]
+fn fx65_past_the_end_follows_the_unmapped_read_policy() {
+    // I = 0xFFFからV0〜V1を読み込むと，V1はメモリの外（0x1000）から読む
+    let v1 = |policy| {
+        let mut chip8 = chip8_with("AFFF F165");
+        run_past_the_end(&mut chip8, policy, 2).map(|()| {
+            assert_eq!(chip8.registers()[0x0], 0xAA);
+            chip8.registers()[0x1]
+        })
+    };
+    assert_eq!(v1(UnmappedReadPolicy::Zero).unwrap(), 0x00);
+    assert_eq!(v1(UnmappedReadPolicy::Ones).unwrap(), 0xFF);
+    // Wrapではメモリの先頭（フォントの"0"の1行目）から読む
+    assert_eq!(v1(UnmappedReadPolicy::Wrap).unwrap(), 0xF0);
+    let err = v1(UnmappedReadPolicy::Error).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MemoryOutOfRange(0x1000));
+}
+
+#[test]
+fn load_range_past_the_end_follows_the_unmapped_read_policy() {
+    // XO-CHIPでI = 0xFFFFからV0〜V1をLOADすると，V1は64KBの外（0x10000）から読む
+    let v1 = |policy| {
+        let mut chip8 = xo_chip_with("F000 FFFF 5013");
+        run_past_the_end(&mut chip8, policy, 2).map(|()| {
+            assert_eq!(chip8.registers()[0x0], 0xAA);
+            chip8.registers()[0x1]
+        })
+    };
+    assert_eq!(v1(UnmappedReadPolicy::Zero).unwrap(), 0x00);
+    assert_eq!(v1(UnmappedReadPolicy::Ones).unwrap(), 0xFF);
+    assert_eq!(v1(UnmappedReadPolicy::Wrap).unwrap(), 0xF0);
+    let err = v1(UnmappedReadPolicy::Error).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MemoryOutOfRange(0x10000));
+}
+
+#[test]
+fn drw_past_the_end_follows_the_unmapped_read_policy() {
+    // I = 0xFFFから2行のスプライトを描くと，2行目はメモリの外（0x1000）から読む
+    let rows = |policy| {
+        let mut chip8 = chip8_with("AFFF 6000 D002");
+        run_past_the_end(&mut chip8, policy, 3).map(|()| {
+            let row = |y| {
+                (0..8)
+                    .map(|x| chip8.display().get(x, y))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(row(0), [1, 0, 1, 0, 1, 0, 1, 0]);
+            row(1)
+        })
+    };
+    assert_eq!(rows(UnmappedReadPolicy::Zero).unwrap(), [0; 8]);
+    assert_eq!(rows(UnmappedReadPolicy::Ones).unwrap(), [1; 8]);
+    assert_eq!(
+        rows(UnmappedReadPolicy::Wrap).unwrap(),
+        [1, 1, 1, 1, 0, 0, 0, 0]
+    );
+    let err = rows(UnmappedReadPolicy::Error).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MemoryOutOfRange(0x1000));
+}
+
+#[test]
+fn core_uses_logical_dimensions_only() {
+    // 命令は論理的なディスプレイの画素だけを扱い，表示の大きさはレンダラーが決める
+    let mut chip8 = chip8_with("6040 6120 D015");
+    steps(&mut chip8, 3);
+    let display = chip8.display();
+    assert_eq!(
+        (display.width(), display.height()),
+        (LORES_WIDTH, LORES_HEIGHT)
+    );
+    assert_eq!(display.pixels().len(), LORES_WIDTH * LORES_HEIGHT);
+
+    // コアとそのテストは描画先の大きさの定数を参照しない
+    for source in [
+        include_str!("lib.rs"),
+        include_str!("display.rs"),
+        include_str!("tests.rs"),
+    ] {
+        for name in [concat!("SCREEN", "_WIDTH"), concat!("SCREEN", "_HEIGHT")] {
+            assert!(!source.contains(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{LORES_HEIGHT, LORES_WIDTH};
+
+// エミュレートするCHIP-8の系統
+//
+// 系統によって使える命令が異なる．既定値は元のCHIP-8．
+// 後の系統ほど大きい（前の系統の命令を全て使える）として順序を比較できる．
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Variant {
+    #[default]
+    Chip8, // COSMAC VIPのCHIP-8
+    SuperChip, // SUPER-CHIP 1.1
+    XoChip,    // XO-CHIP
+}
+
+impl Variant {
+    // 全ての系統
+    pub const ALL: [Variant; 3] = [Variant::Chip8, Variant::SuperChip, Variant::XoChip];
+
+    // メモリの大きさ（XO-CHIPは64KB，それ以外は4KB）
+    pub fn memory_size(self) -> usize {
+        match self {
+            Variant::XoChip => 0x10000,
+            Variant::Chip8 | Variant::SuperChip => 0x1000,
+        }
+    }
+
+    // 起動時の論理的なディスプレイの大きさ（横，縦の画素数）
+    // SUPER-CHIPとXO-CHIPも低解像度で起動する
+    pub fn display_size(self) -> (usize, usize) {
+        match self {
+            Variant::Chip8 | Variant::SuperChip | Variant::XoChip => (LORES_WIDTH, LORES_HEIGHT),
+        }
+    }
+}
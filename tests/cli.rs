@@ -0,0 +1,195 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{self, Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+// エミュレータのバイナリを引数を指定して実行する
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chip8-rust"))
+        .args(args)
+        .output()
+        .expect("failed to run chip8-rust")
+}
+
+// 標準入力にデータを渡してエミュレータのバイナリを実行する
+fn run_with_stdin(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chip8-rust"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run chip8-rust");
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// テスト用のROMを一時ディレクトリに書き出し，そのパスを返す
+fn rom_file(name: &str, rom: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("chip8-rust-{}-{}.ch8", name, process::id()));
+    fs::write(&path, rom).unwrap();
+    path
+}
+
+// 出力したASCIIアートの左上が"0"になっていることを確認する
+fn assert_zero_drawn(stdout: &str) {
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 32, "{}", stdout);
+    for (line, expected) in lines.iter().zip(ZERO_ROWS) {
+        assert!(line.starts_with(expected), "{}", stdout);
+    }
+}
+
+// フォントの"0"を(0, 0)に描画して停止するROM（LD I, 0; LD V0, 0; DRW V0, V0, 5; JP 0x206）
+const DRAW_ZERO: [u8; 8] = [0xA0, 0x00, 0x60, 0x00, 0xD0, 0x05, 0x12, 0x06];
+
+// "0"を描画した画面の左上の5行
+const ZERO_ROWS: [&str; 5] = ["####....", "#..#....", "#..#....", "#..#....", "####...."];
+
+#[test]
+fn scale_zero_is_rejected() {
+    let output = run(&["--scale", "0", "rom.ch8"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--scale must be an integer between 1 and 20 (got 0)"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn rom_is_read_from_stdin() {
+    let output = run_with_stdin(&["--cycles", "10", "--dump-frame-on-exit", "-"], &DRAW_ZERO);
+    assert!(output.status.success());
+    assert_zero_drawn(&String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn unreadable_rom_is_an_error() {
+    let path = std::env::temp_dir().join(format!("chip8-rust-missing-{}.ch8", process::id()));
+    let output = run(&[path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("error: "), "{}", stderr);
+}
+
+#[test]
+fn frame_is_dumped_on_exit() {
+    let rom = rom_file("dump-frame", &DRAW_ZERO);
+    let path = rom.to_str().unwrap();
+    let output = run(&["--cycles", "3", "--dump-frame-on-exit", path]);
+    fs::remove_file(&rom).unwrap();
+    assert!(output.status.success());
+    assert_zero_drawn(&String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn super_chip_exit_stops_and_dumps_the_frame() {
+    // DRAW_ZEROの最後のJP 0x206をEXITに置き換え，SUPER-CHIPとして実行する
+    let dir = std::env::temp_dir().join(format!("chip8-rust-exit-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("chip8.toml"), "variant = \"super_chip\"\n").unwrap();
+    let rom = dir.join("exit.ch8");
+    fs::write(&rom, [0xA0, 0x00, 0x60, 0x00, 0xD0, 0x05, 0x00, 0xFD]).unwrap();
+
+    let start = Instant::now();
+    let output = run(&[
+        "--timeout",
+        "10000",
+        "--dump-frame-on-exit",
+        rom.to_str().unwrap(),
+    ]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("timed out"), "{}", stderr);
+    assert_zero_drawn(&String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn cycles_runs_a_bounded_number_of_instructions() {
+    // JP 0x200（停止しない無限ループ）
+    let rom = rom_file("cycles", &[0x12, 0x00]);
+    let start = Instant::now();
+    let output = run(&["--cycles", "100", rom.to_str().unwrap()]);
+    fs::remove_file(&rom).unwrap();
+    assert!(output.status.success());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn trace_writes_executed_instructions() {
+    let rom = rom_file("trace", &DRAW_ZERO);
+    let trace = std::env::temp_dir().join(format!("chip8-rust-trace-{}.txt", process::id()));
+    let output = run(&[
+        "--cycles",
+        "4",
+        "--trace",
+        trace.to_str().unwrap(),
+        rom.to_str().unwrap(),
+    ]);
+    fs::remove_file(&rom).unwrap();
+    assert!(output.status.success());
+    let text = fs::read_to_string(&trace).unwrap();
+    fs::remove_file(&trace).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        lines[..4],
+        [
+            "0200 A000 LD",
+            "0202 6000 LD",
+            "0204 D005 DRW",
+            "0206 1206 JP"
+        ]
+    );
+}
+
+#[test]
+fn info_prints_size_and_hash() {
+    let rom = rom_file("info", &DRAW_ZERO);
+    let output = run(&["info", rom.to_str().unwrap()]);
+    fs::remove_file(&rom).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("size:    8 bytes"), "{}", stdout);
+    assert!(stdout.contains("header:  (none)"), "{}", stdout);
+    assert!(stdout.contains("variant: Chip8"), "{}", stdout);
+    assert!(
+        stdout
+            .contains("sha256:  f9b5215c6c0d6769339089adf42b59674e017b4f379f67b84b111fc7cebc4c5c"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn rom_named_info_runs_after_double_dash() {
+    let dir = std::env::temp_dir().join(format!("chip8-rust-info-rom-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("info"), DRAW_ZERO).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8-rust"))
+        .args(["--cycles", "3", "--dump-frame-on-exit", "--", "info"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_zero_drawn(&String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn timeout_stops_a_tight_loop() {
+    // JP 0x200（停止しない無限ループ）
+    let rom = rom_file("timeout", &[0x12, 0x00]);
+    let start = Instant::now();
+    let output = run(&["--timeout", "100", rom.to_str().unwrap()]);
+    fs::remove_file(&rom).unwrap();
+    assert!(output.status.success());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("timed out after 100 ms"), "{}", stderr);
+}
@@ -0,0 +1,30 @@
+use chip8_rust::reference::{compare_trace, TraceError};
+use chip8_rust::Chip8;
+
+// 同梱のROMと，そのROMを実行した参照トレース
+const ROM: &[u8] = include_bytes!("data/countdown.ch8");
+const TRACE: &str = include_str!("data/countdown.trace");
+
+fn chip8() -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load(ROM);
+    chip8
+}
+
+#[test]
+fn bundled_trace_matches() {
+    assert_eq!(compare_trace(&mut chip8(), TRACE), Ok(14));
+}
+
+#[test]
+fn first_divergence_is_reported() {
+    // 8命令目（行番号10）のIを書き換える
+    let mut modified: Vec<_> = TRACE.lines().collect();
+    modified[9] = "0206 70FF 02 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 0304";
+    let err = compare_trace(&mut chip8(), &modified.join("\n")).unwrap_err();
+    let TraceError::Diverged { step, line, .. } = &err else {
+        panic!("unexpected error: {}", err);
+    };
+    assert_eq!((*step, *line), (7, 10));
+    assert!(err.to_string().contains("I: 0304 != 0305"), "{}", err);
+}